@@ -1,16 +1,18 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use indicatif::ProgressDrawTarget;
-use rand::{thread_rng, Rng};
+use rand::{Rng, SeedableRng};
 use weekend_raytracer::{
     camera::Camera,
     color::Color,
-    hittables::{BvhNode, HittableList, Sphere},
+    hittables::{BvhNode, HittableList, Sphere, World},
     material::{Dielectric, Lambertian, Material, Metal},
     math::{Point3, Vec3},
+    sampler::Pcg32,
 };
 
 fn sphere(c: &mut Criterion) {
     let mut world = HittableList::default();
+    let mut rng = Pcg32::seed_from_u64(0);
 
     let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
     world.push(Sphere::stationary(
@@ -21,25 +23,27 @@ fn sphere(c: &mut Criterion) {
 
     for a in -11..11 {
         for b in -11..11 {
-            let chose_mat = thread_rng().gen_range(0.0..1.0);
+            let chose_mat = rng.gen_range(0.0..1.0);
             let center = Point3::new(
-                a as f64 + 0.9 * thread_rng().gen_range(0.0..1.0),
+                a as f64 + 0.9 * rng.gen_range(0.0..1.0),
                 0.2,
-                b as f64 + 0.9 * thread_rng().gen_range(0.0..1.0),
+                b as f64 + 0.9 * rng.gen_range(0.0..1.0),
             );
 
             if (center - Point3::new(4, 0.2, 0)).length() > 0.9 {
-                let center2 = center + Vec3::new(0, thread_rng().gen_range(0.0..0.5), 0);
+                let center2 = center + Vec3::new(0, rng.gen_range(0.0..0.5), 0);
                 world.push(Sphere::moving(
                     center,
                     center2,
                     0.2,
                     if chose_mat < 0.8 {
-                        let albedo = Color::from(Vec3::random(0.0..1.0) * Vec3::random(0.0..1.0));
+                        let albedo = Color::from(
+                            Vec3::random(&mut rng, 0.0..1.0) * Vec3::random(&mut rng, 0.0..1.0),
+                        );
                         Material::from(Lambertian::new(albedo))
                     } else if chose_mat < 0.95 {
-                        let albedo = Color::from(Vec3::random(0.5..1.0));
-                        let fuzz = thread_rng().gen_range(0.0..0.5);
+                        let albedo = Color::from(Vec3::random(&mut rng, 0.5..1.0));
+                        let fuzz = rng.gen_range(0.0..0.5);
                         Material::from(Metal::new(albedo, fuzz))
                     } else {
                         Material::from(Dielectric::new(1.5))
@@ -65,7 +69,7 @@ fn sphere(c: &mut Criterion) {
         Metal::new(Color::new(0.7, 0.6, 0.5), 0.0),
     ));
 
-    let world = BvhNode::new(world.into_iter().collect());
+    let world = World::new(BvhNode::new(world.into_iter().collect()));
 
     let camera = Camera::builder()
         .aspect_ratio(16.0 / 9.0)
@@ -1,15 +1,55 @@
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressIterator};
-use itertools::iproduct;
-use rand::{thread_rng, Rng};
-use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use itertools::Itertools;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelBridge,
+    ParallelIterator,
+};
 
 use crate::{
-    color::Color,
-    hittables::Hit,
-    material::ScatterAndEmit,
-    math::{cross, Point3, Ray, Vec3},
+    color::{Color, Wavelengths},
+    film::{Aovs, Film},
+    hittables::World,
+    integrator::{Integrate, Integrator, IntegratorType, RenderContext},
+    math::{cross, Point3, Ray, RayDifferentials, Vec3},
+    sampler::{GenerateSamples, Sampler, SamplerType},
 };
 
+mod aperture;
+pub use aperture::*;
+
+mod physical;
+pub use physical::*;
+
+mod shutter;
+pub use shutter::*;
+
+mod animation;
+pub use animation::*;
+
+mod filter;
+pub use filter::Filter;
+use filter::FilterSampler;
+
+mod tiles;
+use tiles::spiral_tiles;
+
+mod aov;
+
+mod adaptive;
+pub use adaptive::AdaptiveSampling;
+use adaptive::PixelEstimate;
+
+mod progressive;
+pub use progressive::*;
+
+mod importance;
+pub use importance::ImportanceSample;
+
+mod splats;
+pub use splats::Splats;
+
 #[derive(Debug, derive_builder::Builder)]
 #[builder(pattern = "owned", build_fn(private, name = "build_private"))]
 pub struct CameraParams {
@@ -23,6 +63,14 @@ pub struct CameraParams {
     vfov_degrees: f64,
     #[builder(default = "10")]
     max_depth: i32,
+    /// Algorithm for computing the light along camera rays. `max_depth`, `russian_roulette_depth`
+    /// and `background` are passed on to it.
+    #[builder(setter, default)]
+    integrator: IntegratorType,
+    /// Number of bounces after which paths are randomly terminated with a probability based on
+    /// their throughput, or `None` to always trace paths until `max_depth`
+    #[builder(setter, default = "Some(3)")]
+    russian_roulette_depth: Option<u32>,
     #[builder(default = "Point3::new(0, 0, -1)")]
     look_at: Point3,
     #[builder(default = "Vec3::new(0, 1, 0)")]
@@ -33,6 +81,39 @@ pub struct CameraParams {
     look_from: Point3,
     #[builder(setter, default = "None")]
     defocus_angle: Option<f64>,
+    #[builder(setter, default)]
+    aperture: Aperture,
+    #[builder(setter, default = "None")]
+    physical_camera: Option<PhysicalCamera>,
+
+    #[builder(setter, default = "0.0")]
+    shutter_open: f64,
+    #[builder(setter, default = "1.0")]
+    shutter_close: f64,
+    #[builder(setter, default)]
+    shutter_curve: ShutterCurve,
+    #[builder(setter, default)]
+    animation: CameraAnimation,
+
+    /// Edge length of the square tiles that the image is rendered in
+    #[builder(setter, default = "32")]
+    tile_size: usize,
+    #[builder(setter, default = "None")]
+    adaptive_sampling: Option<AdaptiveSampling>,
+    /// Whether to render AOVs (see [`Aovs`]) alongside the image
+    #[builder(setter, default = "false")]
+    aovs: bool,
+    /// Whether to render spectrally, see [`Wavelengths`]
+    #[builder(setter, default = "false")]
+    spectral: bool,
+    #[builder(setter, default)]
+    filter: Filter,
+    #[builder(setter, default)]
+    sampler: SamplerType,
+    /// Seed from which the random numbers of every pixel sample are derived. Renders with the
+    /// same seed are identical, regardless of how the work is scheduled across threads.
+    #[builder(setter, default = "0")]
+    seed: u64,
 
     #[builder(setter, default = "Color::black()")]
     background: Color,
@@ -42,35 +123,71 @@ pub struct CameraParams {
 }
 
 #[derive(Debug, Clone)]
-struct DefocusDisk {
+struct DefocusAperture {
     u: Vec3,
     v: Vec3,
+    shape: Aperture,
 }
 
-impl DefocusDisk {
-    fn sample(&self, center: Point3) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
+impl DefocusAperture {
+    fn sample(&self, center: Point3, u: (f64, f64)) -> Point3 {
+        let p = self.shape.sample(u);
         center + (p.x * self.u) + (p.y * self.v)
     }
+
+    fn area(&self) -> f64 {
+        self.shape.area() * cross(&self.u, &self.v).length()
+    }
 }
 
+/// Position and orientation of the camera, from which the viewport is derived.
 #[derive(Debug, Clone)]
-pub struct Camera {
-    image_width: usize,
-    image_height: usize,
-    pixel_samples_scale: f64,
-    sqrt_spp: usize,     // Square root of number of samples per pixel
-    recip_sqrt_spp: f64, // 1 / sqrt_spp
-    max_depth: i32,
-    center: Point3,
+struct Viewpoint {
+    look_from: Point3,
+    look_at: Point3,
+    v_up: Vec3,
+    vfov_degrees: f64,
+    focus_dist: f64,
+}
 
+#[derive(Debug, Clone)]
+struct Viewport {
+    center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
+    /// Unit vector along the viewing direction
+    forward: Vec3,
+    defocus_aperture: Option<DefocusAperture>,
+}
 
-    defocus_disk: Option<DefocusDisk>,
-
-    background: Color,
+#[derive(Debug, Clone)]
+pub struct Camera {
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    sampler: Sampler,
+    sampler_type: SamplerType,
+    seed: u64,
+    filter: FilterSampler,
+    tile_size: usize,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    aovs: bool,
+    spectral: bool,
+    integrator: Integrator,
+
+    viewpoint: Viewpoint,
+    viewport: Viewport,
+    defocus_radius: Option<f64>,
+    aperture: Aperture,
+    physical_camera: Option<PhysicalCamera>,
+    animation: CameraAnimation,
+
+    exposure: f64,
+
+    shutter_open: f64,
+    shutter_close: f64,
+    shutter_curve: ShutterCurve,
 
     progress_bar: ProgressBar,
 }
@@ -81,140 +198,608 @@ impl CameraParamsBuilder {
 
         let image_height = ((params.image_width as f64 / params.aspect_ratio) as usize).max(1);
 
-        let theta = params.vfov_degrees.to_radians();
+        let vfov_degrees = params
+            .physical_camera
+            .as_ref()
+            .map(|physical| physical.vfov_degrees(params.aspect_ratio))
+            .unwrap_or(params.vfov_degrees);
+        let viewpoint = Viewpoint {
+            look_from: params.look_from,
+            look_at: params.look_at,
+            v_up: params.v_up,
+            vfov_degrees,
+            focus_dist: params.focus_dist,
+        };
+
+        let defocus_radius = match &params.physical_camera {
+            Some(physical) => Some(physical.aperture_radius()),
+            None => params.defocus_angle.map(|defocus_angle| {
+                params.focus_dist * f64::tan((defocus_angle / 2.0).to_radians())
+            }),
+        };
+
+        let viewport = Camera::viewport(
+            &viewpoint,
+            params.image_width,
+            image_height,
+            defocus_radius,
+            &params.aperture,
+        );
+
+        let sampler = Sampler::new(params.sampler, params.samples_per_pixel, params.seed);
+
+        Camera {
+            image_width: params.image_width,
+            image_height,
+            samples_per_pixel: params.samples_per_pixel,
+            sampler,
+            sampler_type: params.sampler,
+            seed: params.seed,
+            filter: FilterSampler::new(params.filter),
+            tile_size: params.tile_size,
+            adaptive_sampling: params.adaptive_sampling,
+            aovs: params.aovs,
+            spectral: params.spectral,
+            integrator: Integrator::new(
+                params.integrator,
+                params.max_depth,
+                params.russian_roulette_depth,
+                params.background,
+            ),
+
+            viewpoint,
+            viewport,
+            defocus_radius,
+            aperture: params.aperture,
+            exposure: params
+                .physical_camera
+                .as_ref()
+                .map(PhysicalCamera::exposure)
+                .unwrap_or(1.0),
+            physical_camera: params.physical_camera,
+            animation: params.animation,
+
+            shutter_open: params.shutter_open,
+            shutter_close: params.shutter_close,
+            shutter_curve: params.shutter_curve,
+
+            progress_bar: ProgressBar::with_draw_target(
+                Some(image_height as u64),
+                params.progress_draw_target,
+            ),
+        }
+    }
+}
+
+impl Camera {
+    pub fn builder() -> CameraParamsBuilder {
+        CameraParamsBuilder::default()
+    }
+
+    fn viewport(
+        viewpoint: &Viewpoint,
+        image_width: usize,
+        image_height: usize,
+        defocus_radius: Option<f64>,
+        aperture: &Aperture,
+    ) -> Viewport {
+        let theta = viewpoint.vfov_degrees.to_radians();
         let h = f64::tan(theta / 2.0);
-        let viewport_height = 2.0 * h * params.focus_dist;
-        let viewport_width = viewport_height * (params.image_width as f64 / image_height as f64);
+        let viewport_height = 2.0 * h * viewpoint.focus_dist;
+        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
 
-        let w = (params.look_from - params.look_at).normalized();
-        let u = cross(&params.v_up, &w).normalized();
+        let w = (viewpoint.look_from - viewpoint.look_at).normalized();
+        let u = cross(&viewpoint.v_up, &w).normalized();
         let v = cross(&w, &u);
 
         let viewport_u = viewport_width * u; // Vector across viewport horizontal edge
         let viewport_v = viewport_height * (-v); // Vector down viewport vertical edge
 
-        let pixel_delta_u = viewport_u / params.image_width as f64;
+        let pixel_delta_u = viewport_u / image_width as f64;
         let pixel_delta_v = viewport_v / image_height as f64;
 
         let viewport_upper_left =
-            params.look_from - params.focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
+            viewpoint.look_from - viewpoint.focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
 
         let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
 
-        let defocus_disk = params.defocus_angle.map(|defocus_angle| {
-            let defocus_radius = params.focus_dist * f64::tan((defocus_angle / 2.0).to_radians());
-            DefocusDisk {
-                u: defocus_radius * u,
-                v: defocus_radius * v,
-            }
+        let defocus_aperture = defocus_radius.map(|defocus_radius| DefocusAperture {
+            u: defocus_radius * u,
+            v: defocus_radius * v,
+            shape: aperture.clone(),
         });
 
-        let sqrt_spp = f64::sqrt(params.samples_per_pixel as f64) as usize;
-        let pixel_samples_scale = 1.0 / (sqrt_spp * sqrt_spp) as f64;
-        let recip_sqrt_spp = 1.0 / (sqrt_spp as f64);
-
-        Camera {
-            image_width: params.image_width,
-            image_height,
-            pixel_samples_scale,
-            sqrt_spp,
-            recip_sqrt_spp,
-            max_depth: params.max_depth,
-            center: params.look_from,
-
+        Viewport {
+            center: viewpoint.look_from,
             pixel00_loc,
             pixel_delta_u,
             pixel_delta_v,
-            defocus_disk,
-            background: params.background,
+            forward: -w,
+            defocus_aperture,
+        }
+    }
 
-            progress_bar: ProgressBar::with_draw_target(
-                Some(image_height as u64),
-                params.progress_draw_target,
+    /// Returns the camera for the given frame of an animation: the keyframed parameters are
+    /// evaluated at the start of the frame, and the shutter is open for the frame's interval.
+    pub fn at_frame(&self, frame: usize, timing: &FrameTiming) -> Camera {
+        let time = timing.frame_time(frame);
+
+        let mut viewpoint = self.viewpoint.clone();
+        if let Some(look_from) = &self.animation.look_from {
+            viewpoint.look_from = look_from.at(time);
+        }
+        if let Some(look_at) = &self.animation.look_at {
+            viewpoint.look_at = look_at.at(time);
+        }
+        if let (Some(vfov_degrees), None) = (&self.animation.vfov_degrees, &self.physical_camera) {
+            viewpoint.vfov_degrees = vfov_degrees.at(time);
+        }
+
+        let (shutter_open, shutter_close) = timing.shutter_interval(frame);
+        Camera {
+            viewport: Self::viewport(
+                &viewpoint,
+                self.image_width,
+                self.image_height,
+                self.defocus_radius,
+                &self.aperture,
             ),
+            viewpoint,
+            shutter_open,
+            shutter_close,
+            ..self.clone()
         }
     }
-}
 
-impl Camera {
-    pub fn builder() -> CameraParamsBuilder {
-        CameraParamsBuilder::default()
+    pub fn render(&self, world: &World, output: &mut impl std::io::Write) -> std::io::Result<()> {
+        self.render_film(world).write_ppm(output)
+    }
+
+    pub fn render_film(&self, world: &World) -> Film {
+        if let Some(adaptive_sampling) = &self.adaptive_sampling {
+            return self.render_film_adaptive(world, adaptive_sampling);
+        }
+
+        let splats = Splats::new(self.image_width, self.image_height);
+        let context = self.render_context(world, &splats);
+        if self.integrator.renders_by_sample_index() {
+            return self.render_film_by_sample_index(&context);
+        }
+
+        let tiles = spiral_tiles(self.image_width, self.image_height, self.tile_size);
+        self.progress_bar.reset();
+        self.progress_bar.set_length(tiles.len() as u64);
+
+        // Threads take the next tile in spiral order whenever they are done with one
+        let rendered_tiles = tiles
+            .into_iter()
+            .par_bridge()
+            .map(|tile| {
+                let estimates = tile
+                    .pixels()
+                    .map(|(i, j)| self.render_pixel(i, j, &context))
+                    .collect_vec();
+                self.progress_bar.inc(1);
+                (tile, estimates)
+            })
+            .collect::<Vec<_>>();
+        self.progress_bar.finish();
+
+        let mut film = Film::new(self.image_width, self.image_height);
+        let splat_scale =
+            self.splat_scale(self.image_width * self.image_height * self.samples_per_pixel);
+        for (tile, estimates) in rendered_tiles {
+            for ((i, j), estimate) in tile.pixels().zip(estimates) {
+                Self::set_film_pixel(&mut film, i, j, &estimate, splat_scale * splats.get(i, j));
+            }
+        }
+        film
+    }
+
+    /// Renders all pixels one sample index at a time, for integrators whose samples share data
+    /// between pixels.
+    fn render_film_by_sample_index(&self, context: &RenderContext) -> Film {
+        let (width, height) = (self.image_width, self.image_height);
+        let mut estimates = vec![PixelEstimate::default(); width * height];
+        let all_pixels = vec![true; width * height];
+        self.progress_bar.reset();
+        self.progress_bar.set_length(self.samples_per_pixel as u64);
+        for _ in 0..self.samples_per_pixel {
+            self.add_samples(context, &self.sampler, &mut estimates, &all_pixels, |_| 1);
+            self.progress_bar.inc(1);
+        }
+        self.progress_bar.finish();
+        self.film_from_estimates(&estimates, context.splats)
     }
 
-    pub fn render(
+    fn render_context<'a>(&'a self, world: &'a World, splats: &'a Splats) -> RenderContext<'a> {
+        RenderContext {
+            world,
+            camera: self,
+            splats,
+            photons: None,
+        }
+    }
+
+    fn render_pixel(&self, i: usize, j: usize, context: &RenderContext) -> PixelEstimate {
+        let mut sampler = self.sampler.clone();
+        let mut estimate = PixelEstimate::default();
+        for _ in 0..self.samples_per_pixel {
+            self.add_sample(i, j, context, &mut sampler, &mut estimate);
+        }
+        estimate
+    }
+
+    /// Takes the next sample of pixel i, j and adds it to the pixel's estimate.
+    fn add_sample(
         &self,
-        world: &impl Hit,
-        output: &mut impl std::io::Write,
-    ) -> std::io::Result<()> {
-        writeln!(output, "P3")?;
-        writeln!(output, "{} {}", self.image_width, self.image_height)?;
-        writeln!(output, "255")?;
-
-        let mut colors = Vec::with_capacity(self.image_width);
-        for j in (0..self.image_height).progress_with(self.progress_bar.clone()) {
-            (0..self.image_width)
-                .into_par_iter()
-                .map(|i| {
-                    self.pixel_samples_scale
-                        * iproduct!((0..self.sqrt_spp), (0..self.sqrt_spp))
-                            .map(|(s_i, s_j)| self.get_ray(i, j, s_i, s_j))
-                            .map(|ray| self.ray_color(&ray, self.max_depth, world))
-                            .sum::<Color>()
-                })
-                .collect_into_vec(&mut colors);
-
-            for color in &colors {
-                writeln!(output, "{color}")?;
+        i: usize,
+        j: usize,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        estimate: &mut PixelEstimate,
+    ) {
+        sampler.start_pixel_sample((i, j), estimate.count());
+        let (ray, weight) = self.get_ray(i, j, sampler);
+        let scale = self.exposure * weight;
+        let wavelengths = ray.wavelengths();
+        if self.aovs {
+            let mut aovs = Aovs::default();
+            let color = self
+                .integrator
+                .radiance(&ray, context, sampler, Some(&mut aovs));
+            for pass in aovs.light_passes_mut() {
+                *pass = scale * wavelengths.to_rgb(*pass);
             }
+            estimate.add_aovs(&aovs);
+            estimate.add(scale * wavelengths.to_rgb(color));
+        } else {
+            let color = self.integrator.radiance(&ray, context, sampler, None);
+            estimate.add(scale * wavelengths.to_rgb(color));
         }
-        Ok(())
     }
 
-    fn get_ray(&self, i: usize, j: usize, s_i: usize, s_j: usize) -> Ray {
-        // Construct a camera ray originating from the defocus disk and directed at a randomly
-        // sampled point around the pixel location i, j for stratified sample square s_i, s_j.
+    fn render_film_adaptive(&self, world: &World, adaptive_sampling: &AdaptiveSampling) -> Film {
+        let (width, height) = (self.image_width, self.image_height);
+        let mut estimates = vec![PixelEstimate::default(); width * height];
+        let mut active = vec![true; width * height];
+        let splats = Splats::new(width, height);
+        let context = self.render_context(world, &splats);
+
+        // Every round gives all active pixels another batch of samples, until all converged.
+        self.progress_bar.reset();
+        self.progress_bar.set_length((width * height) as u64);
+        while active.contains(&true) {
+            self.add_samples(
+                &context,
+                &self.sampler,
+                &mut estimates,
+                &active,
+                |estimate| {
+                    adaptive_sampling
+                        .min_samples
+                        .max(1)
+                        .min(adaptive_sampling.max_samples - estimate.count())
+                },
+            );
+
+            active = adaptive_sampling.pixels_to_refine(&estimates, width, height);
+            self.progress_bar
+                .set_position(active.iter().filter(|active| !**active).count() as u64);
+        }
+        self.progress_bar.finish();
+
+        self.film_from_estimates(&estimates, &splats)
+    }
 
-        let offset = self.sample_square_stratified(s_i, s_j);
-        let pixel_sample = self.pixel00_loc
-            + ((i as f64 + offset.x) * self.pixel_delta_u)
-            + ((j as f64 + offset.y) * self.pixel_delta_v);
+    /// Renders in passes that each double the number of samples per pixel, until
+    /// `samples_per_pixel` is reached or one of the stopping criteria of `progressive` is met. If a
+    /// checkpoint is given, the render continues from there instead of starting from scratch.
+    /// Samplers stratify the samples of every pass on their own.
+    ///
+    /// The final sample count, the total render time and the estimated error are stored in the
+    /// film's metadata.
+    pub fn render_progressive(
+        &self,
+        world: &World,
+        progressive: &Progressive,
+        resume: Option<Checkpoint>,
+    ) -> anyhow::Result<Film> {
+        let (width, height) = (self.image_width, self.image_height);
+        let settings = self.render_settings(&progressive.scene);
+        let mut checkpoint = match resume {
+            Some(checkpoint) => {
+                anyhow::ensure!(
+                    (checkpoint.width, checkpoint.height) == (width, height),
+                    "checkpoint is {}x{}, but the image is {width}x{height}",
+                    checkpoint.width,
+                    checkpoint.height
+                );
+                anyhow::ensure!(
+                    checkpoint.seed == self.seed,
+                    "checkpoint was rendered with seed {}, but the seed is {}",
+                    checkpoint.seed,
+                    self.seed
+                );
+                checkpoint.settings.ensure_matches(&settings)?;
+                checkpoint
+            }
+            None => Checkpoint {
+                width,
+                height,
+                seed: self.seed,
+                settings,
+                elapsed: Duration::ZERO,
+                estimates: vec![PixelEstimate::default(); width * height],
+                splats: Splats::new(width, height),
+            },
+        };
+        let context = self.render_context(world, &checkpoint.splats);
+
+        let target = self.samples_per_pixel;
+        let all_pixels = vec![true; width * height];
+        let session_start = Instant::now();
+        let (resumed_elapsed, resumed_samples) =
+            (checkpoint.elapsed, checkpoint.samples_per_pixel());
+        let mut last_checkpoint = Instant::now();
+
+        self.progress_bar.reset();
+        self.progress_bar.set_length(target as u64);
+        loop {
+            let done = checkpoint.samples_per_pixel();
+            checkpoint.elapsed = resumed_elapsed + session_start.elapsed();
+            self.progress_bar.set_position(done.min(target) as u64);
+
+            let mut pass_target = (2 * done).clamp(1, target);
+            if let Some(time_budget) = progressive.time_budget {
+                // Only start as many samples as are expected to finish within the budget, based
+                // on how long the samples of this session took so far.
+                let remaining = time_budget.saturating_sub(checkpoint.elapsed);
+                let session_samples = done - resumed_samples;
+                if session_samples > 0 {
+                    let time_per_sample =
+                        session_start.elapsed().as_secs_f64() / session_samples as f64;
+                    let affordable = (remaining.as_secs_f64() / time_per_sample) as usize;
+                    pass_target = pass_target.min(done + affordable);
+                } else if remaining.is_zero() {
+                    pass_target = done;
+                }
+            }
+            if let Some(noise_target) = progressive.noise_target {
+                // The error falls with the square root of the sample count
+                let rmse = checkpoint.rmse();
+                if rmse <= noise_target {
+                    pass_target = done;
+                } else if rmse.is_finite() {
+                    let needed = (done as f64 * (rmse / noise_target).powi(2)).ceil() as usize;
+                    pass_target = pass_target.min(needed.max(done + 1));
+                }
+            }
+            if pass_target <= done {
+                break;
+            }
 
-        let ray_origin = self
-            .defocus_disk
-            .as_ref()
-            .map(|d| d.sample(self.center))
-            .unwrap_or(self.center);
-        let ray_direction = pixel_sample - ray_origin;
-        let ray_time = thread_rng().gen();
-        Ray::new(ray_origin, ray_direction, ray_time)
+            // Every pass is stratified on its own, so that its samples are well distributed no
+            // matter how far the render gets. Stratifying for the target instead would scatter
+            // the samples of a render that stops early, e.g. at a time budget, over far more
+            // strata than it fills.
+            let sampler = Sampler::new(self.sampler_type, pass_target - done, self.seed);
+            self.add_samples(
+                &context,
+                &sampler,
+                &mut checkpoint.estimates,
+                &all_pixels,
+                |estimate| pass_target.saturating_sub(estimate.count()),
+            );
+
+            if let Some(path) = &progressive.checkpoint_path {
+                if last_checkpoint.elapsed() >= progressive.checkpoint_interval {
+                    checkpoint.elapsed = resumed_elapsed + session_start.elapsed();
+                    checkpoint.save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        self.progress_bar.finish();
+
+        if let Some(path) = &progressive.checkpoint_path {
+            checkpoint.save(path)?;
+        }
+
+        let mut film = self.film_from_estimates(&checkpoint.estimates, &checkpoint.splats);
+        film.set_metadata("samples per pixel", checkpoint.samples_per_pixel());
+        film.set_metadata(
+            "render time",
+            format!("{:.3}s", checkpoint.elapsed.as_secs_f64()),
+        );
+        film.set_metadata("estimated rmse", format!("{:.6}", checkpoint.rmse()));
+        Ok(film)
     }
 
-    fn sample_square_stratified(&self, s_i: usize, s_j: usize) -> Vec3 {
-        // Returns the vector to a random point in the square sub-pixel specified by grid
-        // indices s_i and s_j, for an idealized unit square pixel [-.5,-.5] to [+.5,+.5].
+    /// Everything besides the seed and the image size that the samples of a progressive render
+    /// depend on, see [`RenderSettings`].
+    fn render_settings(&self, scene: &str) -> RenderSettings {
+        RenderSettings {
+            scene: scene.to_string(),
+            sampler: format!("{:?}", self.sampler_type),
+            filter: format!("{:?}", self.filter.filter()),
+            integrator: format!("{:?}", self.integrator),
+            spectral: self.spectral,
+            exposure: self.exposure,
+        }
+    }
+
+    /// Gives every active pixel `batch_size(estimate)` more samples, taken with `sampler`.
+    fn add_samples(
+        &self,
+        context: &RenderContext,
+        sampler: &Sampler,
+        estimates: &mut [PixelEstimate],
+        active: &[bool],
+        batch_size: impl Fn(&PixelEstimate) -> usize + Sync,
+    ) {
+        let width = self.image_width;
+        if self.integrator.renders_by_sample_index() {
+            return self
+                .add_samples_by_sample_index(context, sampler, estimates, active, batch_size);
+        }
 
-        let px = ((s_i as f64 + thread_rng().gen::<f64>()) * self.recip_sqrt_spp) - 0.5;
-        let py = ((s_j as f64 + thread_rng().gen::<f64>()) * self.recip_sqrt_spp) - 0.5;
+        estimates
+            .par_iter_mut()
+            .zip(active.par_iter())
+            .enumerate()
+            .filter(|(_, (_, active))| **active)
+            .for_each(|(index, (estimate, _))| {
+                let (i, j) = (index % width, index / width);
+                let mut sampler = sampler.clone();
+                for _ in 0..batch_size(estimate) {
+                    self.add_sample(i, j, context, &mut sampler, estimate);
+                }
+            });
+    }
 
-        Vec3::new(px, py, 0)
+    /// Like `add_samples`, but takes the samples one sample index at a time, tracing the photons
+    /// and splatting the pass of each index once for all pixels. Both only depend on the seed and
+    /// the index, so the result is the same as if every pixel had been sampled on its own.
+    fn add_samples_by_sample_index(
+        &self,
+        context: &RenderContext,
+        sampler: &Sampler,
+        estimates: &mut [PixelEstimate],
+        active: &[bool],
+        batch_size: impl Fn(&PixelEstimate) -> usize + Sync,
+    ) {
+        let width = self.image_width;
+        let ranges = estimates
+            .iter()
+            .zip(active)
+            .map(|(estimate, active)| {
+                let start = estimate.count();
+                let end = if *active {
+                    start + batch_size(estimate)
+                } else {
+                    start
+                };
+                start..end
+            })
+            .collect_vec();
+        let first = ranges.iter().map(|range| range.start).min().unwrap_or(0);
+        let end = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+
+        for sample_index in first..end {
+            let samples = ranges
+                .iter()
+                .filter(|range| range.contains(&sample_index))
+                .count();
+            self.integrator.splat_pass(context, sample_index, samples);
+            let photons = self.integrator.trace_photons(context, sample_index);
+            let context = RenderContext {
+                photons: photons.as_ref(),
+                ..*context
+            };
+            estimates
+                .par_iter_mut()
+                .zip(ranges.par_iter())
+                .enumerate()
+                .filter(|(_, (_, range))| range.contains(&sample_index))
+                .for_each(|(index, (estimate, _))| {
+                    let (i, j) = (index % width, index / width);
+                    let mut sampler = sampler.clone();
+                    self.add_sample(i, j, &context, &mut sampler, estimate);
+                });
+        }
     }
 
-    fn ray_color(&self, r: &Ray, depth: i32, world: &impl Hit) -> Color {
-        if depth <= 0 {
-            return Color::black();
+    fn film_from_estimates(&self, estimates: &[PixelEstimate], splats: &Splats) -> Film {
+        let width = self.image_width;
+        let mut film = Film::new(width, self.image_height);
+        let splat_scale = self.splat_scale(estimates.iter().map(PixelEstimate::count).sum());
+        for (index, estimate) in estimates.iter().enumerate() {
+            let (i, j) = (index % width, index / width);
+            Self::set_film_pixel(&mut film, i, j, estimate, splat_scale * splats.get(i, j));
         }
-        if let Some(hit_record) = world.hit(r, &(0.001..=f64::INFINITY).into()) {
-            let color_from_emission = hit_record.material.emit(&hit_record);
-            if let Some(scattered) = hit_record.material.scatter(r, &hit_record) {
-                let color_from_scatter =
-                    scattered.attenuation * self.ray_color(&scattered.ray, depth - 1, world);
-                color_from_emission + color_from_scatter
-            } else {
-                color_from_emission
-            }
+        film
+    }
+
+    /// Scale of the splats in the film. Every camera sample may splat to any pixel, so the splats
+    /// are averaged over the samples per pixel of the whole image, rather than of their pixel.
+    fn splat_scale(&self, total_samples: usize) -> f64 {
+        let pixels = self.image_width * self.image_height;
+        self.exposure * pixels as f64 / total_samples.max(1) as f64
+    }
+
+    fn set_film_pixel(film: &mut Film, i: usize, j: usize, estimate: &PixelEstimate, splat: Color) {
+        film.set_pixel(i, j, estimate.color() + splat, estimate.count());
+        if let Some(aovs) = estimate.aovs() {
+            film.set_aovs(i, j, aovs);
+        }
+    }
+
+    fn get_ray(&self, i: usize, j: usize, sampler: &mut Sampler) -> (Ray, f64) {
+        // Construct a camera ray originating from the defocus aperture and directed at a point
+        // around the pixel location i, j sampled from the reconstruction filter. Returns the ray
+        // and the filter weight of its sample.
+
+        let ((offset_x, offset_y), weight) = self.filter.sample(sampler.get_pixel_2d());
+        let raster = (i as f64 + 0.5 + offset_x, j as f64 + 0.5 + offset_y);
+        (self.ray_through(raster, sampler), weight)
+    }
+
+    /// Ray from a point on the lens through the continuous raster coordinates `raster`, in which
+    /// pixel i, j covers [i, i+1) x [j, j+1). The inverse of [`Camera::world_to_raster`].
+    pub fn ray_through(&self, (x, y): (f64, f64), sampler: &mut Sampler) -> Ray {
+        // pixel00_loc is the center of the first pixel
+        let pixel_sample = self.viewport.pixel00_loc
+            + ((x - 0.5) * self.viewport.pixel_delta_u)
+            + ((y - 0.5) * self.viewport.pixel_delta_v);
+
+        let ray_origin = self
+            .viewport
+            .defocus_aperture
+            .as_ref()
+            .map(|d| d.sample(self.viewport.center, sampler.get_2d()))
+            .unwrap_or(self.viewport.center);
+        let ray_direction = pixel_sample - ray_origin;
+        let ray_time = self.sample_time(sampler.get_1d());
+
+        // Offset rays through the neighboring pixels, moved closer to account for the spacing of
+        // the samples within a pixel, like PBRT does
+        let differentials = RayDifferentials {
+            rx_origin: ray_origin,
+            rx_direction: ray_direction + self.viewport.pixel_delta_u,
+            ry_origin: ray_origin,
+            ry_direction: ray_direction + self.viewport.pixel_delta_v,
+        };
+        let scale = (1.0 / (self.samples_per_pixel as f64).sqrt()).max(0.125);
+        let ray = Ray::new(ray_origin, ray_direction, ray_time).with_differentials(Some(
+            differentials.scaled(&ray_origin, &ray_direction, scale),
+        ));
+        if self.spectral {
+            ray.with_wavelengths(Wavelengths::sample(sampler.get_1d()))
         } else {
-            // If the ray hits nothing, return the background color.
-            self.background
+            ray
         }
     }
+
+    /// Width and height of the image in pixels.
+    pub fn image_size(&self) -> (usize, usize) {
+        (self.image_width, self.image_height)
+    }
+
+    /// Size of a pixel projected to the distance of the point the camera looks at.
+    pub fn pixel_footprint(&self) -> f64 {
+        let distance = (self.viewpoint.look_at - self.viewpoint.look_from).length();
+        self.viewport.pixel_delta_v.length() * distance / self.viewpoint.focus_dist
+    }
+
+    /// Seed of all random numbers of the render.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Maps a uniform sample to a time at which the shutter is open, distributed like the
+    /// shutter's transmission.
+    pub fn sample_time(&self, u: f64) -> f64 {
+        self.shutter_open + (self.shutter_close - self.shutter_open) * self.shutter_curve.sample(u)
+    }
 }
@@ -0,0 +1,149 @@
+use std::io::{Read, Write};
+
+use itertools::iproduct;
+
+use crate::{color::Color, film::Aovs};
+
+use super::aov::AovAccumulator;
+
+/// Settings for adaptive sampling: after `min_samples`, pixels receive further batches of samples
+/// while the estimated noise in their neighbourhood is above `noise_threshold`, up to
+/// `max_samples`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    /// Largest acceptable standard error of the gamma corrected pixel luminance, which is roughly
+    /// the noise visible in the final image (on a scale where 1 is white).
+    pub noise_threshold: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            max_samples: 1024,
+            noise_threshold: 0.01,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    // A pixel is only considered converged if all pixels within this radius are. Otherwise, pixels
+    // whose first few samples all happened to miss a rare but bright path (e.g. all black samples
+    // in a dimly lit corner) would look converged and stop early, leaving fireflies or dark speckles.
+    const NEIGHBOURHOOD_RADIUS: usize = 1;
+
+    /// Returns for every pixel whether it needs another batch of samples.
+    pub(super) fn pixels_to_refine(
+        &self,
+        estimates: &[PixelEstimate],
+        width: usize,
+        height: usize,
+    ) -> Vec<bool> {
+        let r = Self::NEIGHBOURHOOD_RADIUS;
+        let errors: Vec<f64> = estimates.iter().map(PixelEstimate::error).collect();
+
+        iproduct!(0..height, 0..width)
+            .map(|(y, x)| {
+                let neighbourhood_error = iproduct!(
+                    y.saturating_sub(r)..(y + r + 1).min(height),
+                    x.saturating_sub(r)..(x + r + 1).min(width)
+                )
+                .map(|(ny, nx)| errors[ny * width + nx])
+                .fold(0.0, f64::max);
+
+                estimates[y * width + x].count < self.max_samples
+                    && neighbourhood_error > self.noise_threshold
+            })
+            .collect()
+    }
+}
+
+/// Running sum of a pixel's samples, plus mean and variance of their luminance using Welford's
+/// algorithm, and the sums of the AOVs if they are rendered.
+#[derive(Debug, Clone, Default)]
+pub(super) struct PixelEstimate {
+    sum: Color,
+    count: usize,
+    mean: f64,
+    m2: f64,
+    aovs: Option<Box<AovAccumulator>>,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, color: Color) {
+        self.sum = self.sum + color;
+        self.count += 1;
+
+        let luminance = color.luminance();
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    pub fn add_aovs(&mut self, aovs: &Aovs) {
+        self.aovs.get_or_insert_with(Default::default).add(aovs);
+    }
+
+    pub fn aovs(&self) -> Option<Aovs> {
+        self.aovs.as_ref().map(|aovs| aovs.aovs())
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn color(&self) -> Color {
+        (1.0 / self.count.max(1) as f64) * self.sum
+    }
+
+    /// Standard error of the mean after gamma correction. Gamma correction takes the square root,
+    /// so by error propagation the standard error of the mean gets divided by 2 * sqrt(mean).
+    pub fn error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        let standard_error = f64::sqrt(variance / self.count as f64);
+        standard_error / (2.0 * self.mean.max(1e-4).sqrt())
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let [r, g, b] = self.sum.components();
+        for value in [r, g, b, self.mean, self.m2] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&(self.count as u64).to_le_bytes())?;
+        match &self.aovs {
+            Some(aovs) => {
+                writer.write_all(&[1])?;
+                aovs.write_to(writer)
+            }
+            None => writer.write_all(&[0]),
+        }
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> std::io::Result<Self> {
+        let mut values = [[0; 8]; 6];
+        for bytes in &mut values {
+            reader.read_exact(bytes)?;
+        }
+        let [r, g, b, mean, m2] = std::array::from_fn(|i| f64::from_le_bytes(values[i]));
+
+        let mut has_aovs = [0];
+        reader.read_exact(&mut has_aovs)?;
+        let aovs = match has_aovs[0] {
+            0 => None,
+            _ => Some(Box::new(AovAccumulator::read_from(reader)?)),
+        };
+
+        Ok(Self {
+            sum: Color::new(r, g, b),
+            count: u64::from_le_bytes(values[5]) as usize,
+            mean,
+            m2,
+            aovs,
+        })
+    }
+}
@@ -0,0 +1,41 @@
+use crate::math::{Keyframes, Point3};
+
+/// Keyframed camera parameters. Parameters without keyframes keep the value the camera was built
+/// with.
+#[derive(Debug, Clone, Default)]
+pub struct CameraAnimation {
+    pub look_from: Option<Keyframes<Point3>>,
+    pub look_at: Option<Keyframes<Point3>>,
+    /// Ignored if the camera uses a physical camera, where the field of view follows from the lens.
+    pub vfov_degrees: Option<Keyframes<f64>>,
+}
+
+/// Maps frame numbers of an animation to scene time, which is measured in seconds.
+#[derive(Debug, Clone)]
+pub struct FrameTiming {
+    pub fps: f64,
+    /// Fraction of the frame duration during which the shutter is open, given as the angle of a
+    /// rotary disc shutter. 360 degrees means that the motion blur of subsequent frames connects.
+    pub shutter_angle_degrees: f64,
+}
+
+impl Default for FrameTiming {
+    fn default() -> Self {
+        Self {
+            fps: 24.0,
+            shutter_angle_degrees: 180.0,
+        }
+    }
+}
+
+impl FrameTiming {
+    pub fn frame_time(&self, frame: usize) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    /// Times at which the shutter opens and closes for the given frame.
+    pub fn shutter_interval(&self, frame: usize) -> (f64, f64) {
+        let open = self.frame_time(frame);
+        (open, open + self.shutter_angle_degrees / 360.0 / self.fps)
+    }
+}
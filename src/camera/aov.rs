@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+
+use crate::{color::Color, film::Aovs, math::Vec3};
+
+/// Running sums of the AOVs of a pixel's samples.
+///
+/// Light passes and albedo are averaged over all samples, normal, depth and position only over
+/// the samples that hit a surface. IDs can't be averaged, so the pixel gets the IDs of the first
+/// sample that hit something.
+#[derive(Debug, Clone, Default)]
+pub(super) struct AovAccumulator {
+    sum: Aovs,
+    count: usize,
+    hits: usize,
+}
+
+impl AovAccumulator {
+    pub fn add(&mut self, sample: &Aovs) {
+        self.count += 1;
+        self.sum.albedo = self.sum.albedo + sample.albedo;
+        self.sum.emission = self.sum.emission + sample.emission;
+        self.sum.direct = self.sum.direct + sample.direct;
+        self.sum.indirect = self.sum.indirect + sample.indirect;
+        self.sum.diffuse = self.sum.diffuse + sample.diffuse;
+        self.sum.specular = self.sum.specular + sample.specular;
+
+        if sample.is_hit() {
+            self.hits += 1;
+            self.sum.normal += sample.normal;
+            self.sum.depth += sample.depth;
+            self.sum.position += sample.position;
+            if !self.sum.is_hit() {
+                self.sum.material_id = sample.material_id;
+                self.sum.object_id = sample.object_id;
+            }
+        }
+    }
+
+    pub fn aovs(&self) -> Aovs {
+        let per_sample = 1.0 / self.count.max(1) as f64;
+        let per_hit = 1.0 / self.hits.max(1) as f64;
+        let normal = self.sum.normal;
+        Aovs {
+            albedo: per_sample * self.sum.albedo,
+            normal: if normal.near_zero() {
+                Vec3::zero()
+            } else {
+                normal.normalized()
+            },
+            depth: if self.hits == 0 {
+                f64::INFINITY
+            } else {
+                per_hit * self.sum.depth
+            },
+            position: per_hit * self.sum.position,
+            material_id: self.sum.material_id,
+            object_id: self.sum.object_id,
+
+            emission: per_sample * self.sum.emission,
+            direct: per_sample * self.sum.direct,
+            indirect: per_sample * self.sum.indirect,
+            diffuse: per_sample * self.sum.diffuse,
+            specular: per_sample * self.sum.specular,
+        }
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let sum = &self.sum;
+        let vectors = [sum.normal, sum.position];
+        let colors = [
+            sum.albedo,
+            sum.emission,
+            sum.direct,
+            sum.indirect,
+            sum.diffuse,
+            sum.specular,
+        ];
+        let values = vectors
+            .iter()
+            .flat_map(|v| [v.x, v.y, v.z])
+            .chain(colors.iter().flat_map(Color::components))
+            .chain([sum.depth]);
+        for value in values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for value in [
+            sum.material_id as u64,
+            sum.object_id as u64,
+            self.count as u64,
+            self.hits as u64,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> std::io::Result<Self> {
+        let mut read_u64 = || -> std::io::Result<u64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let mut values = [0.0; 25];
+        for value in &mut values {
+            *value = f64::from_bits(read_u64()?);
+        }
+        let vector = |i: usize| Vec3::new(values[i], values[i + 1], values[i + 2]);
+        let color = |i: usize| Color::new(values[i], values[i + 1], values[i + 2]);
+        let (material_id, object_id) = (read_u64()? as u32, read_u64()? as u32);
+        let (count, hits) = (read_u64()? as usize, read_u64()? as usize);
+
+        Ok(Self {
+            sum: Aovs {
+                normal: vector(0),
+                position: vector(3),
+                albedo: color(6),
+                emission: color(9),
+                direct: color(12),
+                indirect: color(15),
+                diffuse: color(18),
+                specular: color(21),
+                depth: values[24],
+                material_id,
+                object_id,
+            },
+            count,
+            hits,
+        })
+    }
+}
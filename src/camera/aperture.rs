@@ -0,0 +1,161 @@
+use std::{f64::consts::PI, path::Path};
+
+use crate::math::Vec3;
+use image::io::Reader as ImageReader;
+
+/// Shape of the lens opening, which determines the shape of out-of-focus highlights (bokeh).
+#[derive(Debug, Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circular,
+    /// Regular polygon formed by straight aperture blades.
+    Polygonal(PolygonalAperture),
+    /// Arbitrary shape given by a grayscale image, where brighter pixels let through more light.
+    Mask(ApertureMask),
+}
+
+impl Aperture {
+    pub fn polygonal(blades: usize, rotation_degrees: f64) -> Self {
+        assert!(blades >= 3, "A polygonal aperture needs at least 3 blades");
+        Self::Polygonal(PolygonalAperture {
+            blades,
+            rotation_degrees,
+        })
+    }
+
+    pub fn mask(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::Mask(ApertureMask::new(path)?))
+    }
+
+    /// Maps a uniform sample from [0,1) x [0,1) to a point on the aperture, scaled so that the
+    /// aperture fits into the unit disk (or, for masks, into the square [-1,1] x [-1,1]).
+    pub fn sample(&self, u: (f64, f64)) -> Vec3 {
+        match self {
+            Self::Circular => Vec3::in_unit_disk_from_sample(u),
+            Self::Polygonal(polygon) => polygon.sample(u),
+            Self::Mask(mask) => mask.sample(u),
+        }
+    }
+
+    /// Area of the aperture, at the scale of [`Aperture::sample`]. For masks, partially
+    /// transparent pixels count with their transparency.
+    pub fn area(&self) -> f64 {
+        match self {
+            Self::Circular => PI,
+            Self::Polygonal(polygon) => polygon.area(),
+            Self::Mask(mask) => mask.area,
+        }
+    }
+}
+
+/// Regular polygon inscribed in the unit circle, with one corner per blade. Only constructed by
+/// [`Aperture::polygonal`], which ensures that there are at least 3 blades.
+#[derive(Debug, Clone)]
+pub struct PolygonalAperture {
+    blades: usize,
+    rotation_degrees: f64,
+}
+
+impl PolygonalAperture {
+    fn sample(&self, u: (f64, f64)) -> Vec3 {
+        // Pick one of the triangles spanned by the center and two adjacent corners, then sample
+        // that triangle uniformly, reusing the remainder of the first dimension. All triangles
+        // have the same area.
+        let scaled = u.0 * self.blades as f64;
+        let triangle = (scaled as usize).min(self.blades - 1);
+        let u0 = scaled - triangle as f64;
+
+        let corner = |k: usize| {
+            let angle =
+                self.rotation_degrees.to_radians() + 2.0 * PI * k as f64 / self.blades as f64;
+            Vec3::new(angle.cos(), angle.sin(), 0)
+        };
+        let (b, c) = (corner(triangle), corner(triangle + 1));
+
+        let s = u0.sqrt();
+        s * (1.0 - u.1) * b + s * u.1 * c
+    }
+
+    fn area(&self) -> f64 {
+        let n = self.blades as f64;
+        0.5 * n * f64::sin(2.0 * PI / n)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // Cumulative distributions for importance sampling the mask: one over the rows, and one per
+    // row over the columns of that row.
+    row_cdf: Vec<f64>,
+    column_cdfs: Vec<Vec<f64>>,
+    area: f64,
+}
+
+fn cumulative(weights: impl Iterator<Item = f64>) -> Vec<f64> {
+    weights
+        .scan(0.0, |acc, w| {
+            *acc += w;
+            Some(*acc)
+        })
+        .collect()
+}
+
+// Returns the index of the bucket of the CDF that u falls into, and u remapped to [0,1) within that
+// bucket so it can be reused.
+fn sample_cdf(cdf: &[f64], u: f64) -> (usize, f64) {
+    let total = cdf.last().copied().unwrap_or_default();
+    let target = u * total;
+    let i = cdf.partition_point(|&c| c <= target).min(cdf.len() - 1);
+    let start = if i > 0 { cdf[i - 1] } else { 0.0 };
+    let width = cdf[i] - start;
+    let remapped = if width > 0.0 {
+        (target - start) / width
+    } else {
+        0.5
+    };
+    (i, remapped.clamp(0.0, 1.0 - f64::EPSILON))
+}
+
+impl ApertureMask {
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        let img = ImageReader::open(path)?.decode()?.to_luma32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let max_luma = img.pixels().map(|p| p[0] as f64).fold(0.0, f64::max);
+
+        let column_cdfs = (0..height)
+            .map(|y| cumulative((0..width).map(|x| img[(x as u32, y as u32)][0].max(0.0) as f64)))
+            .collect::<Vec<_>>();
+        let row_cdf = cumulative(column_cdfs.iter().map(|row| row[width - 1]));
+
+        anyhow::ensure!(
+            row_cdf.last().is_some_and(|&total| total > 0.0),
+            "Aperture mask {} is completely black",
+            path.display()
+        );
+
+        let pixel_size = 2.0 / width.max(height) as f64;
+        let area = row_cdf[height - 1] / max_luma * pixel_size * pixel_size;
+
+        Ok(Self {
+            width,
+            height,
+            row_cdf,
+            column_cdfs,
+            area,
+        })
+    }
+
+    fn sample(&self, (u1, u2): (f64, f64)) -> Vec3 {
+        let (y, jitter_y) = sample_cdf(&self.row_cdf, u1);
+        let (x, jitter_x) = sample_cdf(&self.column_cdfs[y], u2);
+
+        // Map pixel coordinates to [-1,1], preserving the aspect ratio of the mask. Image rows go
+        // downwards, so flip y.
+        let scale = 2.0 / self.width.max(self.height) as f64;
+        let px = (x as f64 + jitter_x - self.width as f64 / 2.0) * scale;
+        let py = (self.height as f64 / 2.0 - y as f64 - jitter_y) * scale;
+        Vec3::new(px, py, 0)
+    }
+}
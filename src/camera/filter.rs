@@ -0,0 +1,206 @@
+use std::f64::consts::PI;
+
+/// Reconstruction filter that weights the samples taken around a pixel center.
+///
+/// All filters are separable, i.e. the weight of an offset (x, y) is `evaluate(x) * evaluate(y)`.
+/// Filters are applied by importance sampling: pixel samples are placed according to the filter
+/// shape instead of uniformly within the pixel, which leaves the samples of neighbouring pixels
+/// independent of each other.
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    /// Constant weight within `radius`. A radius of 0.5 averages over exactly one pixel.
+    Box { radius: f64 },
+    /// Weight falling off linearly to zero at `radius`.
+    Tent { radius: f64 },
+    /// Gaussian with standard deviation `sigma`, shifted down to reach zero at `radius`.
+    Gaussian { radius: f64, sigma: f64 },
+    /// Mitchell-Netravali cubic with parameters `b` and `c`, stretched to `radius`.
+    Mitchell { radius: f64, b: f64, c: f64 },
+    /// Sinc windowed by a wider sinc with `tau` lobes.
+    Lanczos { radius: f64, tau: f64 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// Largest supported radius in pixels. Filters are tabulated with a fixed number of entries per
+    /// pixel, so the radius bounds their memory.
+    pub const MAX_RADIUS: f64 = 32.0;
+
+    // Panics unless the radius is positive and at most MAX_RADIUS, see FilterSampler::new
+    fn checked(radius: f64) -> f64 {
+        assert!(
+            radius > 0.0 && radius <= Self::MAX_RADIUS,
+            "A filter radius needs to be positive and at most {} pixels, not {radius}",
+            Self::MAX_RADIUS
+        );
+        radius
+    }
+
+    pub fn box_filter(radius: f64) -> Self {
+        Self::Box {
+            radius: Self::checked(radius),
+        }
+    }
+
+    pub fn tent(radius: f64) -> Self {
+        Self::Tent {
+            radius: Self::checked(radius),
+        }
+    }
+
+    pub fn gaussian(radius: f64) -> Self {
+        let radius = Self::checked(radius);
+        Self::Gaussian {
+            radius,
+            sigma: radius / 3.0,
+        }
+    }
+
+    pub fn mitchell(radius: f64) -> Self {
+        Self::Mitchell {
+            radius: Self::checked(radius),
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub fn lanczos(radius: f64) -> Self {
+        Self::Lanczos {
+            radius: Self::checked(radius),
+            tau: 3.0,
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Self::Box { radius }
+            | Self::Tent { radius }
+            | Self::Gaussian { radius, .. }
+            | Self::Mitchell { radius, .. }
+            | Self::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    /// Weight of a sample at offset `x` (in pixels) from the pixel center, along one axis.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let radius = self.radius();
+        if x.abs() > radius {
+            return 0.0;
+        }
+        match *self {
+            Self::Box { .. } => 1.0,
+            Self::Tent { .. } => radius - x.abs(),
+            Self::Gaussian { sigma, .. } => {
+                let gaussian = |x: f64| f64::exp(-x * x / (2.0 * sigma * sigma));
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Self::Mitchell { b, c, .. } => {
+                let x = (2.0 * x / radius).abs();
+                if x <= 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            Self::Lanczos { tau, .. } => sinc(x) * sinc(x / tau),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        f64::sin(PI * x) / (PI * x)
+    }
+}
+
+/// Tabulated distribution of the absolute value of a filter, for placing pixel samples.
+#[derive(Debug, Clone)]
+pub(super) struct FilterSampler {
+    filter: Filter,
+    radius: f64,
+    values: Vec<f64>,
+    cdf: Vec<f64>,
+    // Ratio of the integral of |f| to the integral of f. Samples distributed proportionally to
+    // |f| need this weight (with the sign of f) so that their weights average to one.
+    weight: f64,
+}
+
+impl FilterSampler {
+    const RESOLUTION: usize = 64;
+
+    /// Panics unless the filter's radius is positive and at most [`Filter::MAX_RADIUS`], and its
+    /// weights integrate to a positive value. The variants of [`Filter`] can be built with any
+    /// parameters, so this is where they are checked.
+    pub(super) fn new(filter: Filter) -> Self {
+        let radius = Filter::checked(filter.radius());
+        let count = (Self::RESOLUTION as f64 * radius).ceil().max(1.0) as usize;
+        let bin_width = 2.0 * radius / count as f64;
+        let values = (0..count)
+            .map(|bin| filter.evaluate(-radius + (bin as f64 + 0.5) * bin_width))
+            .collect::<Vec<_>>();
+
+        let mut cdf = Vec::with_capacity(count + 1);
+        cdf.push(0.0);
+        for value in &values {
+            cdf.push(cdf.last().unwrap() + value.abs());
+        }
+        let absolute_integral = *cdf.last().unwrap();
+        let integral = values.iter().sum::<f64>();
+        assert!(
+            integral > 0.0 && absolute_integral.is_finite(),
+            "{filter:?} needs to have a positive total weight"
+        );
+        cdf.iter_mut().for_each(|c| *c /= absolute_integral);
+
+        Self {
+            filter,
+            radius,
+            values,
+            cdf,
+            weight: absolute_integral / integral,
+        }
+    }
+
+    pub(super) fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Maps a uniform sample in [0,1) x [0,1) to an offset from the pixel center, distributed
+    /// according to the filter, and the weight of the sample.
+    pub(super) fn sample(&self, (u1, u2): (f64, f64)) -> ((f64, f64), f64) {
+        let ((x, sign_x), (y, sign_y)) = (self.sample_1d(u1), self.sample_1d(u2));
+        ((x, y), sign_x * sign_y * self.weight * self.weight)
+    }
+
+    // Returns the offset along one axis and the sign of the filter there
+    fn sample_1d(&self, u: f64) -> (f64, f64) {
+        let bin = self
+            .cdf
+            .partition_point(|c| *c <= u)
+            .clamp(1, self.cdf.len() - 1)
+            - 1;
+        let (start, end) = (self.cdf[bin], self.cdf[bin + 1]);
+        let remainder = if end > start {
+            (u - start) / (end - start)
+        } else {
+            0.5
+        };
+        let bin_width = 2.0 * self.radius / (self.cdf.len() - 1) as f64;
+        let x = -self.radius + (bin as f64 + remainder) * bin_width;
+        (x, self.values[bin].signum())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::math::{dot, Point3, Ray, Vec3};
+
+use super::Camera;
+
+/// Connection from a point in the scene to the camera, see [`Camera::sample_importance`].
+#[derive(Debug, Clone)]
+pub struct ImportanceSample {
+    /// Point on the lens that the point is connected to
+    pub lens_point: Point3,
+    /// Continuous raster coordinates at which the point appears
+    pub raster: (f64, f64),
+    /// Importance emitted by the camera towards the point
+    pub importance: f64,
+    /// Probability density, with respect to solid angle at the point, of the direction towards
+    /// `lens_point`
+    pub pdf: f64,
+}
+
+// The importance is the camera's counterpart to radiance: it describes how much light arriving
+// along a ray contributes to the image. It is normalized so that integrating it over the lens and
+// the directions that hit the image gives 1, and it's the same for every pixel, like the pdf of
+// the camera rays generated by `get_ray`.
+impl Camera {
+    /// Maps a world point, as seen from a point on the lens, to continuous raster coordinates, in
+    /// which pixel i, j covers [i, i+1) x [j, j+1). Returns `None` if the point isn't in front of
+    /// the camera or falls outside the image. Besides splatting light, this finds the pixel that
+    /// shows a point, e.g. for picking objects or drawing overlays.
+    pub fn world_to_raster(&self, lens_point: &Point3, p: &Point3) -> Option<(f64, f64)> {
+        let viewport = &self.viewport;
+        let direction = *p - *lens_point;
+        let cos_theta = dot(&direction, &viewport.forward);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        // Intersect the ray from the lens through the point with the plane of focus, in which the
+        // pixel grid lies
+        let t = dot(&(viewport.pixel00_loc - *lens_point), &viewport.forward) / cos_theta;
+        let offset = (*lens_point + t * direction) - viewport.pixel00_loc;
+        let x = dot(&offset, &viewport.pixel_delta_u) / viewport.pixel_delta_u.length_squared();
+        let y = dot(&offset, &viewport.pixel_delta_v) / viewport.pixel_delta_v.length_squared();
+
+        // pixel00_loc is the center of the first pixel
+        let (x, y) = (x + 0.5, y + 0.5);
+        let inside = (0.0..self.image_width as f64).contains(&x)
+            && (0.0..self.image_height as f64).contains(&y);
+        inside.then_some((x, y))
+    }
+
+    /// Importance emitted by the camera along ray `r`, which starts on the lens.
+    pub fn importance(&self, r: &Ray) -> f64 {
+        let Some(cos_theta) = self.cos_to_forward(r.direction()) else {
+            return 0.0;
+        };
+        let p = *r.origin() + *r.direction();
+        if self.world_to_raster(r.origin(), &p).is_none() {
+            return 0.0;
+        }
+        1.0 / (self.image_plane_area() * self.lens_area() * cos_theta.powi(4))
+    }
+
+    /// Densities with which `get_ray` generates a ray in `direction`: with respect to area on the
+    /// lens, and with respect to solid angle.
+    pub fn importance_pdf(&self, direction: &Vec3) -> (f64, f64) {
+        let Some(cos_theta) = self.cos_to_forward(direction) else {
+            return (0.0, 0.0);
+        };
+        (
+            1.0 / self.lens_area(),
+            1.0 / (self.image_plane_area() * cos_theta.powi(3)),
+        )
+    }
+
+    /// Samples a point on the lens to connect point `p` to. Returns `None` if `p` isn't visible in
+    /// the image from that point, ignoring occlusion.
+    pub fn sample_importance(&self, p: &Point3, u: (f64, f64)) -> Option<ImportanceSample> {
+        let viewport = &self.viewport;
+        let lens_point = viewport
+            .defocus_aperture
+            .as_ref()
+            .map(|aperture| aperture.sample(viewport.center, u))
+            .unwrap_or(viewport.center);
+        let raster = self.world_to_raster(&lens_point, p)?;
+
+        let to_lens = lens_point - *p;
+        let distance_squared = to_lens.length_squared();
+        let cos_lens = dot(&to_lens, &viewport.forward).abs() / distance_squared.sqrt();
+        let pdf = distance_squared / (cos_lens * self.lens_area());
+        let importance = self.importance(&Ray::new(lens_point, *p - lens_point, 0.0));
+        Some(ImportanceSample {
+            lens_point,
+            raster,
+            importance,
+            pdf,
+        })
+    }
+
+    fn cos_to_forward(&self, direction: &Vec3) -> Option<f64> {
+        let cos_theta = dot(&direction.normalized(), &self.viewport.forward);
+        (cos_theta > 0.0).then_some(cos_theta)
+    }
+
+    /// Area of the image on a plane at distance 1 in front of the lens
+    fn image_plane_area(&self) -> f64 {
+        let viewport = &self.viewport;
+        let focus_dist = dot(&(viewport.pixel00_loc - viewport.center), &viewport.forward);
+        let width = self.image_width as f64 * viewport.pixel_delta_u.length();
+        let height = self.image_height as f64 * viewport.pixel_delta_v.length();
+        width * height / (focus_dist * focus_dist)
+    }
+
+    /// Area of the lens, or 1 for pinhole cameras
+    fn lens_area(&self) -> f64 {
+        self.viewport
+            .defocus_aperture
+            .as_ref()
+            .map(|aperture| aperture.area())
+            .unwrap_or(1.0)
+    }
+}
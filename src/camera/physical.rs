@@ -0,0 +1,52 @@
+/// Lens and sensor settings of a real camera.
+///
+/// Setting these on the camera replaces `vfov_degrees` and `defocus_angle`: the field of view
+/// follows from sensor width and focal length, and the size of the aperture from focal length and
+/// f-stop. In addition, the image brightness is scaled by the exposure resulting from f-stop, ISO
+/// and shutter time, so scene radiance needs to be specified in physically plausible units.
+#[derive(Debug, Clone)]
+pub struct PhysicalCamera {
+    pub sensor_width_mm: f64,
+    pub focal_length_mm: f64,
+    pub f_stop: f64,
+    pub iso: f64,
+    pub shutter_seconds: f64,
+    /// How many scene units correspond to one meter, used to convert the aperture size.
+    pub units_per_meter: f64,
+}
+
+impl Default for PhysicalCamera {
+    fn default() -> Self {
+        // 35mm full frame sensor with a normal lens
+        Self {
+            sensor_width_mm: 36.0,
+            focal_length_mm: 50.0,
+            f_stop: 2.8,
+            iso: 100.0,
+            shutter_seconds: 1.0 / 60.0,
+            units_per_meter: 1.0,
+        }
+    }
+}
+
+impl PhysicalCamera {
+    pub fn vfov_degrees(&self, aspect_ratio: f64) -> f64 {
+        let sensor_height_mm = self.sensor_width_mm / aspect_ratio;
+        (2.0 * f64::atan(sensor_height_mm / (2.0 * self.focal_length_mm))).to_degrees()
+    }
+
+    /// Radius of the aperture (the entrance pupil) in scene units.
+    pub fn aperture_radius(&self) -> f64 {
+        let diameter_mm = self.focal_length_mm / self.f_stop;
+        0.5 * diameter_mm * 0.001 * self.units_per_meter
+    }
+
+    /// Factor that maps scene radiance to pixel values.
+    ///
+    /// Based on the saturation based sensitivity model: a luminance of 1.2 * 2^EV100 saturates the
+    /// sensor, where EV100 = log2(N^2 / t * 100 / S).
+    pub fn exposure(&self) -> f64 {
+        let ev100 = f64::log2(self.f_stop.powi(2) / self.shutter_seconds * 100.0 / self.iso);
+        1.0 / (1.2 * 2f64.powf(ev100))
+    }
+}
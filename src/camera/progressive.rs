@@ -0,0 +1,251 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, ensure};
+
+use super::{adaptive::PixelEstimate, Splats};
+
+/// Settings for progressive rendering: the image is rendered in passes that each double the
+/// number of samples per pixel, until the camera's `samples_per_pixel` is reached or one of the
+/// optional stopping criteria is met.
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    /// Where to write checkpoints. The final state is always written there as well, so that the
+    /// render can be resumed later to reach a higher sample count.
+    pub checkpoint_path: Option<PathBuf>,
+    /// Minimum time between two checkpoints. Checkpoints are only written between passes.
+    pub checkpoint_interval: Duration,
+    /// Stop before the total render time (including time spent before resuming) exceeds this.
+    /// The size of the last pass is reduced to what is expected to fit into the budget.
+    pub time_budget: Option<Duration>,
+    /// Stop once the estimated RMSE of the image is at most this. The error is measured like the
+    /// noise threshold of [`super::AdaptiveSampling`], i.e. in gamma corrected luminance.
+    pub noise_target: Option<f64>,
+    /// Name of the rendered scene, which checkpoints record so that they can't be resumed with a
+    /// different one.
+    pub scene: String,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Self {
+            checkpoint_path: None,
+            checkpoint_interval: Duration::from_secs(60),
+            time_budget: None,
+            noise_target: None,
+            scene: String::new(),
+        }
+    }
+}
+
+/// Everything besides the seed and the image size that the samples of a checkpoint depend on.
+/// Samples rendered with other settings would estimate a different image, so a checkpoint can
+/// only be resumed with the settings it was written with.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct RenderSettings {
+    pub(super) scene: String,
+    pub(super) sampler: String,
+    pub(super) filter: String,
+    pub(super) integrator: String,
+    pub(super) spectral: bool,
+    pub(super) exposure: f64,
+}
+
+impl RenderSettings {
+    /// Fails with the first setting that differs from `current`.
+    pub(super) fn ensure_matches(&self, current: &Self) -> anyhow::Result<()> {
+        let describe = |settings: &Self| {
+            [
+                ("scene", settings.scene.clone()),
+                ("sampler", settings.sampler.clone()),
+                ("filter", settings.filter.clone()),
+                ("integrator", settings.integrator.clone()),
+                (
+                    "spectral rendering",
+                    if settings.spectral { "on" } else { "off" }.to_string(),
+                ),
+                ("exposure", settings.exposure.to_string()),
+            ]
+        };
+        for ((name, recorded), (_, current)) in describe(self).into_iter().zip(describe(current)) {
+            ensure!(
+                recorded == current,
+                "checkpoint was rendered with {name} {recorded}, but the {name} is {current}"
+            );
+        }
+        Ok(())
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for text in [&self.scene, &self.sampler, &self.filter, &self.integrator] {
+            writer.write_all(&(text.len() as u64).to_le_bytes())?;
+            writer.write_all(text.as_bytes())?;
+        }
+        writer.write_all(&[self.spectral as u8])?;
+        writer.write_all(&self.exposure.to_le_bytes())
+    }
+
+    fn read_from(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut read_text = || -> anyhow::Result<String> {
+            let mut length = [0; 8];
+            reader.read_exact(&mut length)?;
+            let length = u64::from_le_bytes(length);
+            // Read through `take`, so that a corrupt length can't allocate more than the file has
+            let mut text = String::new();
+            reader.by_ref().take(length).read_to_string(&mut text)?;
+            ensure!(
+                text.len() as u64 == length,
+                "checkpoint settings are truncated"
+            );
+            Ok(text)
+        };
+        let (scene, sampler, filter, integrator) =
+            (read_text()?, read_text()?, read_text()?, read_text()?);
+
+        let mut spectral = [0];
+        reader.read_exact(&mut spectral)?;
+        let mut exposure = [0; 8];
+        reader.read_exact(&mut exposure)?;
+        Ok(Self {
+            scene,
+            sampler,
+            filter,
+            integrator,
+            spectral: spectral[0] != 0,
+            exposure: f64::from_le_bytes(exposure),
+        })
+    }
+}
+
+/// State of a progressive render: the accumulated samples of every pixel, the light splatted to
+/// them, and the seed and the [`RenderSettings`] they were generated with.
+///
+/// The random numbers of a sample depend on the seed, the pixel and the index of the sample, and
+/// for the stratified sampler also on the size of the pass it belongs to, which determines its
+/// strata. Passes double the sample count, so resuming continues the render exactly where it
+/// stopped, even with a higher sample count. Only if the last pass was cut short, by a sample
+/// count that isn't a power of two, a time budget or a noise target, do the following passes,
+/// and thus the image, differ from an uninterrupted render.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) seed: u64,
+    pub(super) settings: RenderSettings,
+    pub(super) elapsed: Duration,
+    pub(super) estimates: Vec<PixelEstimate>,
+    pub(super) splats: Splats,
+}
+
+impl Checkpoint {
+    /// Identifies checkpoint files and the version of their layout, in the last two digits,
+    /// which is incremented whenever the layout changes. Version 01 was used for several
+    /// layouts, which therefore can't be told apart.
+    const MAGIC: &'static [u8; 8] = b"WRCKPT03";
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of samples that all pixels have received.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.estimates
+            .iter()
+            .map(PixelEstimate::count)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Total time spent rendering so far.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Estimated root mean square error of the image, over the standard errors of all pixels.
+    pub fn rmse(&self) -> f64 {
+        let mean_squared_error = self
+            .estimates
+            .iter()
+            .map(|estimate| estimate.error().powi(2))
+            .sum::<f64>()
+            / self.estimates.len().max(1) as f64;
+        mean_squared_error.sqrt()
+    }
+
+    /// Writes the checkpoint to a temporary file first and then renames it, so that an
+    /// interrupted write never destroys the previous checkpoint.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let temporary_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        writer.write_all(Self::MAGIC)?;
+        for value in [self.width as u64, self.height as u64, self.seed] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        self.settings.write_to(&mut writer)?;
+        writer.write_all(&self.elapsed.as_secs_f64().to_le_bytes())?;
+        for estimate in &self.estimates {
+            estimate.write_to(&mut writer)?;
+        }
+        self.splats.write_to(&mut writer)?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(temporary_path, path)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        if reader.read_exact(&mut magic).is_err() || magic[..6] != Self::MAGIC[..6] {
+            bail!("{} is not a checkpoint file", path.display());
+        }
+        if &magic != Self::MAGIC {
+            bail!(
+                "{} was written by an incompatible version ({})",
+                path.display(),
+                String::from_utf8_lossy(&magic)
+            );
+        }
+
+        let mut read_u64 = || -> std::io::Result<u64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let (width, height, seed) = (read_u64()? as usize, read_u64()? as usize, read_u64()?);
+        let settings = RenderSettings::read_from(&mut reader)?;
+        let mut elapsed = [0; 8];
+        reader.read_exact(&mut elapsed)?;
+        let elapsed = Duration::try_from_secs_f64(f64::from_le_bytes(elapsed))?;
+
+        let estimates = (0..width * height)
+            .map(|_| PixelEstimate::read_from(&mut reader))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let splats = Splats::read_from(&mut reader, width, height)?;
+        ensure!(
+            reader.read(&mut [0])? == 0,
+            "unexpected data at the end of {}",
+            path.display()
+        );
+
+        Ok(Self {
+            width,
+            height,
+            seed,
+            settings,
+            elapsed,
+            estimates,
+            splats,
+        })
+    }
+}
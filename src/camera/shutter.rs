@@ -0,0 +1,38 @@
+/// How far the shutter is open over the course of the exposure. Ray times are distributed
+/// proportionally to this curve, which shapes the trails left by moving objects.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ShutterCurve {
+    /// The shutter opens and closes instantaneously.
+    #[default]
+    Box,
+    /// The shutter opens linearly until the middle of the interval, then closes linearly.
+    Triangle,
+    /// Opening and closing each take `ramp` (at most 0.5) of the interval.
+    Trapezoid { ramp: f64 },
+}
+
+impl ShutterCurve {
+    /// Maps a uniform random number in [0,1) to a point in time in [0,1) distributed according
+    /// to the curve.
+    pub fn sample(&self, u: f64) -> f64 {
+        let ramp = match self {
+            Self::Box => return u,
+            Self::Triangle => 0.5,
+            Self::Trapezoid { ramp } => ramp.clamp(0.0, 0.5),
+        };
+        if ramp == 0.0 {
+            return u;
+        }
+
+        // Invert the CDF of the trapezoid, whose plateau height h makes the area 1.
+        let h = 1.0 / (1.0 - ramp);
+        let ramp_area = 0.5 * h * ramp;
+        if u < ramp_area {
+            f64::sqrt(2.0 * ramp * u / h)
+        } else if u < 1.0 - ramp_area {
+            ramp + (u - ramp_area) / h
+        } else {
+            1.0 - f64::sqrt(2.0 * ramp * (1.0 - u) / h)
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    io::{Read, Write},
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use crate::color::Color;
+
+/// Light that integrators add to arbitrary pixels, rather than to the pixel whose sample they
+/// are computing, e.g. when connecting light paths to the camera.
+///
+/// Splats from all threads are summed in fixed point, since integer addition (unlike floating
+/// point addition) doesn't depend on the order of the splats. This keeps renders deterministic.
+#[derive(Debug)]
+pub struct Splats {
+    width: usize,
+    height: usize,
+    sums: Vec<[AtomicI64; 3]>,
+}
+
+impl Splats {
+    /// Fixed point scale, resolving about 2e-10
+    const SCALE: f64 = (1u64 << 32) as f64;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            sums: (0..width * height).map(|_| Default::default()).collect(),
+        }
+    }
+
+    /// Adds light to the pixel containing the continuous raster coordinates `raster`.
+    pub fn add(&self, (x, y): (f64, f64), color: Color) {
+        let (i, j) = (x.floor(), y.floor());
+        if i < 0.0 || j < 0.0 || i >= self.width as f64 || j >= self.height as f64 {
+            return;
+        }
+        let values = color.components();
+        if !values.iter().all(|value| value.is_finite()) {
+            return;
+        }
+        let sums = &self.sums[j as usize * self.width + i as usize];
+        for (sum, value) in sums.iter().zip(values) {
+            sum.fetch_add((value * Self::SCALE) as i64, Ordering::Relaxed);
+        }
+    }
+
+    /// Sum of the light added to pixel i, j.
+    pub fn get(&self, i: usize, j: usize) -> Color {
+        let [r, g, b] = self.sums[j * self.width + i]
+            .each_ref()
+            .map(|sum| sum.load(Ordering::Relaxed) as f64 / Self::SCALE);
+        Color::new(r, g, b)
+    }
+
+    pub(super) fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for sum in self.sums.iter().flatten() {
+            writer.write_all(&sum.load(Ordering::Relaxed).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_from(
+        reader: &mut impl Read,
+        width: usize,
+        height: usize,
+    ) -> std::io::Result<Self> {
+        let splats = Self::new(width, height);
+        for sum in splats.sums.iter().flatten() {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            sum.store(i64::from_le_bytes(bytes), Ordering::Relaxed);
+        }
+        Ok(splats)
+    }
+}
+
+impl Clone for Splats {
+    fn clone(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            sums: self
+                .sums
+                .iter()
+                .map(|sums| {
+                    sums.each_ref()
+                        .map(|sum| sum.load(Ordering::Relaxed).into())
+                })
+                .collect(),
+        }
+    }
+}
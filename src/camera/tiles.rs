@@ -0,0 +1,46 @@
+use std::f64::consts::PI;
+
+use itertools::iproduct;
+
+/// Rectangular block of pixels that is rendered as one unit of work.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Tile {
+    pub(super) x0: usize,
+    pub(super) y0: usize,
+    pub(super) x1: usize,
+    pub(super) y1: usize,
+}
+
+impl Tile {
+    pub(super) fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        iproduct!(self.y0..self.y1, self.x0..self.x1).map(|(j, i)| (i, j))
+    }
+}
+
+/// Splits the image into square tiles of `size` pixels and orders them in a spiral from the
+/// image center outwards, so that the interesting part of the image usually finishes first.
+pub(super) fn spiral_tiles(width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let size = size.max(1);
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let center = ((columns as f64 - 1.0) / 2.0, (rows as f64 - 1.0) / 2.0);
+
+    let mut tiles = iproduct!(0..rows, 0..columns).collect::<Vec<_>>();
+    // Sort by the ring around the center first, then clockwise by angle within a ring.
+    let spiral_position = |&(row, column): &(usize, usize)| {
+        let (dx, dy) = (column as f64 - center.0, row as f64 - center.1);
+        let ring = dx.abs().max(dy.abs()).ceil() as usize;
+        let angle = f64::atan2(dy, dx).rem_euclid(2.0 * PI);
+        (ring, (angle * 1e6) as u64)
+    };
+    tiles.sort_by_key(spiral_position);
+
+    tiles
+        .into_iter()
+        .map(|(row, column)| Tile {
+            x0: column * size,
+            y0: row * size,
+            x1: ((column + 1) * size).min(width),
+            y1: ((row + 1) * size).min(height),
+        })
+        .collect()
+}
@@ -1,8 +1,11 @@
 use std::ops::{Add, Mul};
 
-use crate::math::Vec3;
+use crate::math::{Lerp, Vec3};
 
-#[derive(Debug, Clone, Copy, derive_more::From)]
+mod spectrum;
+pub use spectrum::*;
+
+#[derive(Debug, Clone, Copy, Default, derive_more::From)]
 pub struct Color(Vec3);
 
 fn linear_to_gamma(linear_component: f64) -> f64 {
@@ -22,6 +25,25 @@ impl Color {
         Self(Vec3::new(1, 1, 1))
     }
 
+    pub fn is_black(&self) -> bool {
+        self.0 == Vec3::zero()
+    }
+
+    /// Relative luminance of the linear color (Rec. 709 primaries).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0.x + 0.7152 * self.0.y + 0.0722 * self.0.z
+    }
+
+    /// Linear red, green and blue components.
+    pub fn components(&self) -> [f64; 3] {
+        [self.0.x, self.0.y, self.0.z]
+    }
+
+    /// Gamma corrected 8-bit sRGB values.
+    pub fn to_rgb8(&self) -> [u8; 3] {
+        [self.red(), self.green(), self.blue()]
+    }
+
     fn red(&self) -> u8 {
         (linear_to_gamma(self.0.x).clamp(0.0, 0.999) * 256.0) as u8
     }
@@ -49,6 +71,12 @@ impl Mul<Color> for f64 {
     }
 }
 
+impl Lerp for Color {
+    fn lerp(a: Self, b: Self, t: f64) -> Self {
+        Self(Vec3::lerp(a.0, b.0, t))
+    }
+}
+
 impl Mul for Color {
     type Output = Color;
 
@@ -0,0 +1,240 @@
+use std::sync::OnceLock;
+
+use palette::{convert::FromColorUnclamped, white_point::D65, LinSrgb, Xyz};
+
+use super::Color;
+
+/// Range of wavelengths that spectral rendering samples, in nanometers. It covers the range of
+/// Smits' spectra, outside of which the eye is hardly sensitive.
+const LAMBDA_MIN: f64 = 380.0;
+const LAMBDA_MAX: f64 = 720.0;
+
+/// Meaning of the components of the colors along a path: red, green and blue, or the light at
+/// three wavelengths sampled for the path.
+///
+/// In spectral mode, each camera ray samples a hero wavelength and two more at equal distances
+/// from it (Wilkie et al., 2014). RGB colors of materials and lights are upsampled to spectra
+/// and evaluated at these wavelengths, and the light arriving at the camera is converted back
+/// to RGB via XYZ. Products of spectra are exact where products of RGB colors aren't, and
+/// materials can depend on the wavelength, e.g. dispersive dielectrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Wavelengths {
+    #[default]
+    Rgb,
+    Sampled {
+        /// In nanometers, starting with the hero wavelength
+        lambdas: [f64; 3],
+        /// Whether only the hero wavelength is left, because the path scattered into a
+        /// direction that depends on the wavelength
+        hero_only: bool,
+    },
+}
+
+impl Wavelengths {
+    /// Samples three wavelengths, uniformly distributed over the visible range.
+    pub fn sample(u: f64) -> Self {
+        let lambdas = [0.0, 1.0, 2.0].map(|i| {
+            let u = (u + i / 3.0).fract();
+            LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+        });
+        Self::Sampled {
+            lambdas,
+            hero_only: false,
+        }
+    }
+
+    pub fn is_spectral(&self) -> bool {
+        matches!(self, Self::Sampled { .. })
+    }
+
+    /// Whether the secondary wavelengths were terminated, see [`Wavelengths::terminate_secondary`].
+    pub fn is_hero_only(&self) -> bool {
+        matches!(
+            self,
+            Self::Sampled {
+                hero_only: true,
+                ..
+            }
+        )
+    }
+
+    /// The hero wavelength in nanometers, which decides wavelength dependent scattering, or
+    /// `None` in RGB mode.
+    pub fn hero(&self) -> Option<f64> {
+        match self {
+            Self::Rgb => None,
+            Self::Sampled { lambdas, .. } => Some(lambdas[0]),
+        }
+    }
+
+    /// Drops all but the hero wavelength. Returns the factor that the light of the path has to
+    /// be multiplied with, which moves the light of the dropped wavelengths to the hero.
+    pub fn terminate_secondary(&mut self) -> Color {
+        match self {
+            Self::Sampled {
+                hero_only: hero_only @ false,
+                ..
+            } => {
+                *hero_only = true;
+                Color::new(3.0, 0.0, 0.0)
+            }
+            _ => Color::white(),
+        }
+    }
+
+    /// Reflectance of a surface with the given linear RGB albedo, using Smits' (1999) upsampling.
+    pub fn reflectance(&self, rgb: Color) -> Color {
+        match self {
+            Self::Rgb => rgb,
+            Self::Sampled { lambdas, .. } => {
+                let [r, g, b] = lambdas.map(|lambda| smits(&rgb, lambda));
+                Color::new(r, g, b)
+            }
+        }
+    }
+
+    /// Light emitted by a light source with the given linear RGB color. Its spectrum is the
+    /// upsampled color times the spectrum of D65, the white of sRGB, so that white lights stay
+    /// white.
+    pub fn illuminant(&self, rgb: Color) -> Color {
+        match self {
+            Self::Rgb => rgb,
+            Self::Sampled { lambdas, .. } => {
+                let [r, g, b] = lambdas.map(|lambda| smits(&rgb, lambda) * d65(lambda));
+                Color::new(r, g, b)
+            }
+        }
+    }
+
+    /// Converts light at these wavelengths to linear RGB.
+    pub fn to_rgb(&self, light: Color) -> Color {
+        let Self::Sampled { lambdas, .. } = self else {
+            return light;
+        };
+
+        // Monte Carlo estimate of the integral of the light times the color matching functions,
+        // with the uniform density of the wavelengths
+        let mut xyz = [0.0; 3];
+        for (lambda, value) in lambdas.iter().zip(light.components()) {
+            let weight = value * (LAMBDA_MAX - LAMBDA_MIN) / 3.0;
+            for (sum, cmf) in xyz.iter_mut().zip(cie_xyz(*lambda)) {
+                *sum += weight * cmf;
+            }
+        }
+        let [r, g, b] = xyz_to_rgb(xyz);
+        let [white_r, white_g, white_b] = *white();
+        Color::new(r / white_r, g / white_g, b / white_b)
+    }
+}
+
+/// Linear RGB of the D65 spectrum, which the spectrally rendered colors are divided by, so that
+/// white stays white despite the approximations of the spectra.
+fn white() -> &'static [f64; 3] {
+    static WHITE: OnceLock<[f64; 3]> = OnceLock::new();
+    WHITE.get_or_init(|| {
+        const STEPS: usize = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+        let mut xyz = [0.0; 3];
+        for i in 0..STEPS {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+            for (sum, cmf) in xyz.iter_mut().zip(cie_xyz(lambda)) {
+                *sum += step * d65(lambda) * cmf;
+            }
+        }
+        xyz_to_rgb(xyz)
+    })
+}
+
+fn xyz_to_rgb([x, y, z]: [f64; 3]) -> [f64; 3] {
+    let rgb = LinSrgb::from_color_unclamped(Xyz::<D65, f64>::new(x, y, z));
+    [rgb.red, rgb.green, rgb.blue]
+}
+
+/// CIE 1931 color matching functions, using the multi-lobe Gaussian fit of Wyman et al. (2013).
+fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        f64::exp(-0.5 * ((lambda - mu) / sigma).powi(2))
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+/// Relative spectral power of CIE illuminant D65 from 380nm to 720nm in steps of 10nm
+const D65_SPECTRUM: [f64; 35] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86, 115.92, 108.81,
+    109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33, 95.79, 88.69, 90.01, 89.60,
+    87.70, 83.29, 83.70, 80.03, 80.21, 82.28, 78.28, 69.72, 71.61, 74.35, 61.60,
+];
+
+fn d65(lambda: f64) -> f64 {
+    interpolate(&D65_SPECTRUM, (lambda - 380.0) / 10.0) / 100.0
+}
+
+/// Smits' spectra of the primaries and secondaries, in 10 bins from 380nm to 720nm
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Value at `lambda` of Smits' spectrum for `rgb`: the smallest component times white, plus
+/// the secondary and primary that make up the rest.
+fn smits(rgb: &Color, lambda: f64) -> f64 {
+    let bin_width = (LAMBDA_MAX - LAMBDA_MIN) / 10.0;
+    let spectrum = |values: &[f64]| interpolate(values, (lambda - LAMBDA_MIN) / bin_width - 0.5);
+
+    let [r, g, b] = rgb.components();
+    if r <= g && r <= b {
+        let rest = if g <= b {
+            (g - r) * spectrum(&SMITS_CYAN) + (b - g) * spectrum(&SMITS_BLUE)
+        } else {
+            (b - r) * spectrum(&SMITS_CYAN) + (g - b) * spectrum(&SMITS_GREEN)
+        };
+        r * spectrum(&SMITS_WHITE) + rest
+    } else if g <= r && g <= b {
+        let rest = if r <= b {
+            (r - g) * spectrum(&SMITS_MAGENTA) + (b - r) * spectrum(&SMITS_BLUE)
+        } else {
+            (b - g) * spectrum(&SMITS_MAGENTA) + (r - b) * spectrum(&SMITS_RED)
+        };
+        g * spectrum(&SMITS_WHITE) + rest
+    } else {
+        let rest = if r <= g {
+            (r - b) * spectrum(&SMITS_YELLOW) + (g - r) * spectrum(&SMITS_GREEN)
+        } else {
+            (g - b) * spectrum(&SMITS_YELLOW) + (r - g) * spectrum(&SMITS_RED)
+        };
+        b * spectrum(&SMITS_WHITE) + rest
+    }
+}
+
+/// Linearly interpolates between the values at integer positions, clamping at the ends.
+fn interpolate(values: &[f64], x: f64) -> f64 {
+    let i = (x.floor().max(0.0) as usize).min(values.len() - 2);
+    let t = (x - i as f64).clamp(0.0, 1.0);
+    values[i] * (1.0 - t) + values[i + 1] * t
+}
@@ -0,0 +1,261 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::RgbImage;
+
+use crate::color::Color;
+
+mod aov;
+use aov::id_color;
+pub use aov::{Aovs, Pass};
+
+mod denoise;
+pub use denoise::Denoiser;
+
+/// The rendered image, holding the final color of every pixel and how many samples it took.
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    sample_counts: Vec<usize>,
+    metadata: Vec<(String, String)>,
+    aovs: Option<Vec<Aovs>>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::black(); width * height],
+            sample_counts: vec![0; width * height],
+            metadata: Vec::new(),
+            aovs: None,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> usize {
+        self.sample_counts[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color, sample_count: usize) {
+        self.pixels[y * self.width + x] = color;
+        self.sample_counts[y * self.width + x] = sample_count;
+    }
+
+    /// Auxiliary outputs of a pixel, if they were rendered.
+    pub fn aovs(&self, x: usize, y: usize) -> Option<&Aovs> {
+        self.aovs.as_ref().map(|aovs| &aovs[y * self.width + x])
+    }
+
+    pub fn set_aovs(&mut self, x: usize, y: usize, aovs: Aovs) {
+        let size = self.width * self.height;
+        self.aovs.get_or_insert_with(|| vec![Aovs::default(); size])[y * self.width + x] = aovs;
+    }
+
+    /// Key-value pairs describing the render, e.g. statistics, that are stored in the output file.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl ToString) {
+        let key = key.into();
+        let value = value.to_string();
+        match self.metadata.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.metadata.push((key, value)),
+        }
+    }
+
+    /// Visualizes how many samples each pixel took, from black (no samples) over blue, red and
+    /// yellow up to white for the pixels with the most samples.
+    pub fn sample_heatmap(&self) -> Film {
+        let ramp = [
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(1.0, 1.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ];
+
+        let max_count = self.sample_counts.iter().copied().max().unwrap_or(0).max(1);
+        let pixels = self
+            .sample_counts
+            .iter()
+            .map(|&count| {
+                let x = count as f64 / max_count as f64 * (ramp.len() - 1) as f64;
+                let i = (x.floor() as usize).min(ramp.len() - 2);
+                let t = x - i as f64;
+                (1.0 - t) * ramp[i] + t * ramp[i + 1]
+            })
+            .collect();
+
+        Film {
+            pixels,
+            aovs: None,
+            ..self.clone()
+        }
+    }
+
+    /// Visualizes an AOV pass as an image, if AOVs were rendered. Color passes are shown as they
+    /// are, normals are mapped from [-1,1] to [0,1], depth and position are normalized to the
+    /// range of values in the image (near is bright), and IDs get random colors.
+    pub fn aov_image(&self, pass: Pass) -> Option<Film> {
+        let aovs = self.aovs.as_ref()?;
+
+        // Colors are gamma corrected when saving, so values that should appear linearly in the
+        // image are squared beforehand.
+        let linear = |v: [f64; 3]| Color::new(v[0] * v[0], v[1] * v[1], v[2] * v[2]);
+        let hits = || aovs.iter().filter(|aovs| aovs.is_hit());
+        let pixels = match pass {
+            Pass::Normal => aovs
+                .iter()
+                .map(|aovs| linear([0, 1, 2].map(|i| 0.5 + 0.5 * aovs.normal[i])))
+                .collect(),
+            Pass::Depth => {
+                let max_depth = hits().map(|aovs| aovs.depth).fold(0.0, f64::max);
+                aovs.iter()
+                    .map(|aovs| {
+                        let near = (1.0 - aovs.depth / max_depth.max(1e-8)).max(0.0);
+                        linear([near; 3])
+                    })
+                    .collect()
+            }
+            Pass::Position => {
+                let (min, max) = hits().fold(
+                    ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]),
+                    |(min, max), aovs| {
+                        let p = aovs.position;
+                        (
+                            [0, 1, 2].map(|i| min[i].min(p[i])),
+                            [0, 1, 2].map(|i| max[i].max(p[i])),
+                        )
+                    },
+                );
+                aovs.iter()
+                    .map(|aovs| {
+                        if !aovs.is_hit() {
+                            return Color::black();
+                        }
+                        let p = aovs.position;
+                        linear([0, 1, 2].map(|i| (p[i] - min[i]) / (max[i] - min[i]).max(1e-8)))
+                    })
+                    .collect()
+            }
+            Pass::MaterialId => aovs.iter().map(|aovs| id_color(aovs.material_id)).collect(),
+            Pass::ObjectId => aovs.iter().map(|aovs| id_color(aovs.object_id)).collect(),
+            _ => aovs
+                .iter()
+                .map(|aovs| {
+                    let [r, g, b] = aovs.values(pass)[..] else {
+                        unreachable!("color passes have three channels")
+                    };
+                    Color::new(r, g, b)
+                })
+                .collect(),
+        };
+
+        Some(Film {
+            pixels,
+            aovs: None,
+            ..self.clone()
+        })
+    }
+
+    /// Saves the film as a single-part OpenEXR file with 32-bit float channels. The beauty image
+    /// is stored in the R, G and B channels, and every AOV pass, if rendered, as a layer of
+    /// channels prefixed with its name (e.g. `albedo.R`). The metadata is stored as attributes.
+    pub fn save_exr(&self, path: &Path) -> anyhow::Result<()> {
+        use exr::prelude::{
+            AnyChannel, AnyChannels, AttributeValue, Encoding, FlatSamples, Image, Layer,
+            LayerAttributes, SmallVec, Text, WritableImage,
+        };
+
+        let channel =
+            |name: &str, values: Vec<f32>| AnyChannel::new(name, FlatSamples::F32(values));
+
+        let mut channels = Vec::new();
+        for (i, name) in ["R", "G", "B"].into_iter().enumerate() {
+            let values = self.pixels.iter().map(|c| c.components()[i] as f32);
+            channels.push(channel(name, values.collect()));
+        }
+        if let Some(aovs) = &self.aovs {
+            for pass in Pass::ALL {
+                for (i, name) in pass.channels().iter().enumerate() {
+                    let values = aovs.iter().map(|aovs| aovs.values(pass)[i] as f32);
+                    let name = format!("{}.{name}", pass.name());
+                    channels.push(channel(&name, values.collect()));
+                }
+            }
+        }
+
+        let mut attributes = LayerAttributes::named("beauty");
+        for (key, value) in &self.metadata {
+            attributes.other.insert(
+                Text::new_or_panic(key),
+                AttributeValue::Text(Text::new_or_panic(value)),
+            );
+        }
+
+        let layer = Layer::new(
+            (self.width, self.height),
+            attributes,
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        Image::from_layer(layer).write().to_file(path)?;
+        Ok(())
+    }
+
+    pub fn write_ppm(&self, output: &mut impl std::io::Write) -> std::io::Result<()> {
+        writeln!(output, "P3")?;
+        for (key, value) in &self.metadata {
+            writeln!(output, "# {key}: {value}")?;
+        }
+        writeln!(output, "{} {}", self.width, self.height)?;
+        writeln!(output, "255")?;
+
+        for color in &self.pixels {
+            writeln!(output, "{color}")?;
+        }
+        Ok(())
+    }
+
+    /// Saves the film as an 8-bit image, with the format determined by the file extension. The
+    /// metadata is stored as text chunks in PNG files, other formats don't include it.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let img = RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            image::Rgb(self.pixel(x as usize, y as usize).to_rgb8())
+        });
+
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if !is_png || self.metadata.is_empty() {
+            img.save(path)?;
+            return Ok(());
+        }
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (key, value) in &self.metadata {
+            encoder.add_text_chunk(key.clone(), value.clone())?;
+        }
+        encoder.write_header()?.write_image_data(img.as_raw())?;
+        Ok(())
+    }
+}
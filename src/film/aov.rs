@@ -0,0 +1,138 @@
+use crate::{color::Color, math::Vec3, sampler::hash};
+
+/// Arbitrary output variables (AOVs) of a pixel, or of a single camera sample.
+///
+/// The geometric passes, from `albedo` to `object_id`, describe the first surface hit by the camera
+/// ray. IDs are 0 where no surface was hit, and the depth is infinite there.
+///
+/// The light passes split the beauty image by the kind of path: `emission + direct + indirect` and
+/// `emission + diffuse + specular` both add up to it. `emission` is the light seen directly,
+/// including the background, `direct` the light reaching the camera after one bounce, and
+/// `indirect` after several. `diffuse` and `specular` sort the light that bounced at least once
+/// by the kind of the first bounce.
+#[derive(Debug, Clone, Default)]
+pub struct Aovs {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub position: Vec3,
+    pub material_id: u32,
+    pub object_id: u32,
+
+    pub emission: Color,
+    pub direct: Color,
+    pub indirect: Color,
+    pub diffuse: Color,
+    pub specular: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    MaterialId,
+    ObjectId,
+    Emission,
+    Direct,
+    Indirect,
+    Diffuse,
+    Specular,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 11] = [
+        Self::Albedo,
+        Self::Normal,
+        Self::Depth,
+        Self::Position,
+        Self::MaterialId,
+        Self::ObjectId,
+        Self::Emission,
+        Self::Direct,
+        Self::Indirect,
+        Self::Diffuse,
+        Self::Specular,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Albedo => "albedo",
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Position => "position",
+            Self::MaterialId => "material_id",
+            Self::ObjectId => "object_id",
+            Self::Emission => "emission",
+            Self::Direct => "direct",
+            Self::Indirect => "indirect",
+            Self::Diffuse => "diffuse",
+            Self::Specular => "specular",
+        }
+    }
+
+    /// Names of the channels of the pass, as used in EXR files.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Self::Normal | Self::Position => &["X", "Y", "Z"],
+            Self::Depth => &["Z"],
+            Self::MaterialId | Self::ObjectId => &["id"],
+            _ => &["R", "G", "B"],
+        }
+    }
+}
+
+impl Aovs {
+    /// Geometric passes of a sample that didn't hit any surface. The background counts as its
+    /// albedo, its light has to be added to the light passes separately.
+    pub(crate) fn missed(background: Color) -> Self {
+        Self {
+            albedo: background,
+            depth: f64::INFINITY,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_hit(&self) -> bool {
+        self.object_id != 0
+    }
+
+    /// Values of the channels of a pass, in the order of [`Pass::channels`].
+    pub fn values(&self, pass: Pass) -> Vec<f64> {
+        let vec3 = |v: &Vec3| vec![v.x, v.y, v.z];
+        match pass {
+            Pass::Albedo => self.albedo.components().to_vec(),
+            Pass::Normal => vec3(&self.normal),
+            Pass::Depth => vec![self.depth],
+            Pass::Position => vec3(&self.position),
+            Pass::MaterialId => vec![self.material_id as f64],
+            Pass::ObjectId => vec![self.object_id as f64],
+            Pass::Emission => self.emission.components().to_vec(),
+            Pass::Direct => self.direct.components().to_vec(),
+            Pass::Indirect => self.indirect.components().to_vec(),
+            Pass::Diffuse => self.diffuse.components().to_vec(),
+            Pass::Specular => self.specular.components().to_vec(),
+        }
+    }
+
+    pub(crate) fn light_passes_mut(&mut self) -> [&mut Color; 5] {
+        [
+            &mut self.emission,
+            &mut self.direct,
+            &mut self.indirect,
+            &mut self.diffuse,
+            &mut self.specular,
+        ]
+    }
+}
+
+/// Color for visualizing an ID, which is random but the same for equal IDs.
+pub(crate) fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::black();
+    }
+    let bits = hash(&[id as u64]);
+    let channel = |shift: u32| 0.1 + 0.9 * ((bits >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
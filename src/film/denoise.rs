@@ -0,0 +1,191 @@
+use itertools::iproduct;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    color::Color,
+    math::{dot, Vec3},
+};
+
+use super::{Aovs, Film};
+
+/// Settings for the edge-avoiding à-trous wavelet denoiser (Dammertz et al., 2010).
+///
+/// Every iteration blurs the image with a 5x5 B-spline kernel whose taps are spread twice as far
+/// apart as in the previous iteration, so that large areas are smoothed with few taps. Each tap
+/// is weighted by how similar it is to the center pixel in color, normal, albedo and depth, which
+/// keeps edges and texture details sharp. Textures are further protected by filtering the
+/// illumination only, i.e. the color divided by the albedo.
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    /// Number of passes, each with twice the spacing between taps of the previous one. Passes
+    /// whose spacing would reach the size of the image are skipped.
+    pub iterations: usize,
+    /// Color difference (in gamma corrected values) at which taps lose most of their weight.
+    /// Larger values smooth more. Halved in every iteration, since the image gets smoother. The
+    /// default is meant for previews with few samples per pixel, whose noise easily exceeds 1.
+    pub color_sigma: f64,
+    /// Exponent for the cosine between normals. Larger values preserve geometric edges better.
+    pub normal_power: f64,
+    pub albedo_sigma: f64,
+    /// Relative depth difference at which taps lose most of their weight.
+    pub depth_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 4.0,
+            normal_power: 64.0,
+            albedo_sigma: 0.1,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+    /// Scales the strength of the color edge stopping. The default strength is 1.
+    pub fn with_strength(strength: f64) -> Self {
+        let default = Self::default();
+        Self {
+            color_sigma: default.color_sigma * strength,
+            ..default
+        }
+    }
+
+    /// Returns a denoised copy of the film. Films without AOVs are denoised using colors only,
+    /// which blurs textures and edges considerably more.
+    pub fn denoise(&self, film: &Film) -> Film {
+        let (width, height) = (film.width, film.height);
+        let guide = film.aovs.as_deref();
+
+        // Small albedos would amplify noise when demodulating, so they are clamped (and the same
+        // clamped albedo is multiplied back in afterwards).
+        let albedo = |index: usize| -> [f64; 3] {
+            match guide {
+                Some(aovs) => aovs[index].albedo.components().map(|a| a.max(0.01)),
+                None => [1.0; 3],
+            }
+        };
+
+        let mut illumination = (0..width * height)
+            .map(|index| {
+                let ([r, g, b], [ar, ag, ab]) = (film.pixels[index].components(), albedo(index));
+                Color::new(r / ar, g / ag, b / ab)
+            })
+            .collect::<Vec<_>>();
+
+        for iteration in 0..self.iterations {
+            // Further iterations would only sample pixels outside of the image
+            let step = 1_usize << iteration;
+            if step >= width.max(height) {
+                break;
+            }
+            let color_sigma = self.color_sigma / step as f64;
+            illumination = (0..height)
+                .into_par_iter()
+                .flat_map_iter(|y| {
+                    let illumination = &illumination;
+                    (0..width).map(move |x| {
+                        self.filter_pixel(
+                            illumination,
+                            guide,
+                            (x, y),
+                            (width, height),
+                            step,
+                            color_sigma,
+                        )
+                    })
+                })
+                .collect();
+        }
+
+        let pixels = illumination
+            .iter()
+            .enumerate()
+            .map(|(index, illumination)| {
+                let ([r, g, b], [ar, ag, ab]) = (illumination.components(), albedo(index));
+                Color::new(r * ar, g * ag, b * ab)
+            })
+            .collect();
+
+        Film {
+            pixels,
+            ..film.clone()
+        }
+    }
+
+    fn filter_pixel(
+        &self,
+        illumination: &[Color],
+        guide: Option<&[Aovs]>,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+        step: usize,
+        color_sigma: f64,
+    ) -> Color {
+        let center_index = y * width + x;
+        let center = gamma(illumination[center_index]);
+
+        let mut sum = Color::black();
+        let mut weight_sum = 0.0;
+        for (ky, kx) in iproduct!(0..5, 0..5) {
+            let qx = x as isize + (kx as isize - 2) * step as isize;
+            let qy = y as isize + (ky as isize - 2) * step as isize;
+            if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                continue;
+            }
+            let index = qy as usize * width + qx as usize;
+
+            let color_distance = (gamma(illumination[index]) - center).length_squared();
+            let mut weight = Self::KERNEL[kx]
+                * Self::KERNEL[ky]
+                * f64::exp(-color_distance / (color_sigma * color_sigma).max(1e-12));
+            if let Some(aovs) = guide {
+                weight *= self.guide_weight(&aovs[center_index], &aovs[index]);
+            }
+
+            sum = sum + weight * illumination[index];
+            weight_sum += weight;
+        }
+
+        if weight_sum > 0.0 {
+            (1.0 / weight_sum) * sum
+        } else {
+            illumination[center_index]
+        }
+    }
+
+    fn guide_weight(&self, center: &Aovs, tap: &Aovs) -> f64 {
+        if center.is_hit() != tap.is_hit() {
+            return 0.0;
+        }
+        if !center.is_hit() {
+            return 1.0;
+        }
+
+        let normal_weight = dot(&center.normal, &tap.normal)
+            .max(0.0)
+            .powf(self.normal_power);
+
+        let albedo_distance = (vec3(center.albedo) - vec3(tap.albedo)).length_squared();
+        let albedo_weight = f64::exp(-albedo_distance / (self.albedo_sigma * self.albedo_sigma));
+
+        let relative_depth = (center.depth - tap.depth).abs() / center.depth.max(1e-8);
+        let depth_weight = f64::exp(-relative_depth / self.depth_sigma);
+
+        normal_weight * albedo_weight * depth_weight
+    }
+}
+
+fn vec3(color: Color) -> Vec3 {
+    let [r, g, b] = color.components();
+    Vec3::new(r, g, b)
+}
+
+fn gamma(color: Color) -> Vec3 {
+    let [r, g, b] = color.components().map(|c| c.max(0.0).sqrt());
+    Vec3::new(r, g, b)
+}
@@ -1,7 +1,9 @@
 use crate::{
-    material::Material, math::{dot, Aabb, Axis, Interval, Point3, Ray, Vec3}, texture::TextureCoords
+    color::Wavelengths, material::Material, math::{dot, Aabb, Axis, Interval, Keyframes, Matrix3, Point3, Ray, Vec3}, texture::{TextureCoords, TextureDerivatives}
 };
 
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use enum_dispatch::enum_dispatch;
 
 mod sphere;
@@ -10,6 +12,9 @@ pub use sphere::*;
 mod quad;
 pub use quad::*;
 
+mod triangle;
+pub use triangle::*;
+
 mod hittable_list;
 pub use hittable_list::*;
 
@@ -25,14 +30,63 @@ pub use rotate::*;
 mod constant_medium;
 pub use constant_medium::*;
 
+mod world;
+pub use world::*;
+
+mod light_bvh;
+pub use light_bvh::*;
+
+/// Identifies a primitive, e.g. for the object ID pass. Every primitive gets a new ID when it is
+/// created, and keeps it when it is cloned or instanced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectId(u32);
+
+impl ObjectId {
+    pub(crate) fn unique() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
     pub material: &'a Material,
+    pub object_id: ObjectId,
     pub t: f64,
     pub front_face: bool,
     pub texture_coords: TextureCoords,
+    /// Wavelengths of the light along the ray that hit, see [`Wavelengths`]
+    pub wavelengths: Wavelengths,
+    /// How the point and normal change across the pixel, if the ray that hit had differentials.
+    /// The texture coordinates then have derivatives as well.
+    pub differentials: Option<SurfaceDifferentials>,
+}
+
+/// Partial derivatives of the hit point and of the normal (facing the ray) with respect to the x
+/// and y of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceDifferentials {
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub dndx: Vec3,
+    pub dndy: Vec3,
+}
+
+impl SurfaceDifferentials {
+    pub fn transformed(&self, m: Matrix3) -> Self {
+        Self {
+            dpdx: m * self.dpdx,
+            dpdy: m * self.dpdy,
+            dndx: m * self.dndx,
+            dndy: m * self.dndy,
+        }
+    }
 }
 
 impl<'a> HitRecord<'a> {
@@ -42,6 +96,7 @@ impl<'a> HitRecord<'a> {
         ray: &Ray,
         outward_normal: Vec3,
         material: &'a Material,
+        object_id: ObjectId,
         texture_coords: TextureCoords,
     ) -> Self {
         let front_face = dot(ray.direction(), &outward_normal) < 0.0;
@@ -55,10 +110,97 @@ impl<'a> HitRecord<'a> {
             t,
             front_face,
             material,
+            object_id,
             texture_coords,
+            wavelengths: ray.wavelengths(),
+            differentials: None,
         }
     }
 
+    /// Adds the differentials of the hit of ray `r`, given the partial derivatives of the surface
+    /// point and of its outward normal with respect to the texture coordinates. The offset rays
+    /// are intersected with the tangent plane at the hit (Igehy, 1999).
+    pub fn with_differentials(
+        mut self,
+        r: &Ray,
+        dpdu: Vec3,
+        dpdv: Vec3,
+        dndu: Vec3,
+        dndv: Vec3,
+    ) -> Self {
+        let Some(rd) = r.differentials() else {
+            return self;
+        };
+        let n = self.normal;
+        let d = dot(&n, self.p.as_vec3());
+        let offset_hit = |origin: &Point3, direction: &Vec3| {
+            let denominator = dot(&n, direction);
+            if denominator.abs() < 1e-12 {
+                return None;
+            }
+            let t = (d - dot(&n, origin.as_vec3())) / denominator;
+            Some(*origin + t * *direction - self.p)
+        };
+        let (Some(dpdx), Some(dpdy)) = (
+            offset_hit(&rd.rx_origin, &rd.rx_direction),
+            offset_hit(&rd.ry_origin, &rd.ry_direction),
+        ) else {
+            return self;
+        };
+
+        // Least squares solution of dp = du * dpdu + dv * dpdv
+        let (a, b, c) = (dot(&dpdu, &dpdu), dot(&dpdu, &dpdv), dot(&dpdv, &dpdv));
+        let determinant = a * c - b * b;
+        if determinant.abs() < 1e-20 {
+            return self;
+        }
+        let solve = |dp: &Vec3| {
+            let (pu, pv) = (dot(&dpdu, dp), dot(&dpdv, dp));
+            (
+                (c * pu - b * pv) / determinant,
+                (a * pv - b * pu) / determinant,
+            )
+        };
+        let ((du_dx, dv_dx), (du_dy, dv_dy)) = (solve(&dpdx), solve(&dpdy));
+        self.texture_coords.derivatives = Some(TextureDerivatives {
+            du_dx,
+            dv_dx,
+            du_dy,
+            dv_dy,
+        });
+
+        let side = if self.front_face { 1.0 } else { -1.0 };
+        self.differentials = Some(SurfaceDifferentials {
+            dpdx,
+            dpdy,
+            dndx: side * (du_dx * dndu + dv_dx * dndv),
+            dndy: side * (du_dy * dndu + dv_dy * dndv),
+        });
+        self
+    }
+
+    /// Hit record for point `p` on a surface with the given outward normal, as if hit from the
+    /// outside, for points that were sampled rather than hit by a ray.
+    pub fn on_surface(
+        p: Point3,
+        outward_normal: Vec3,
+        time: f64,
+        material: &'a Material,
+        object_id: ObjectId,
+        texture_coords: TextureCoords,
+    ) -> Self {
+        let ray = Ray::new(p + outward_normal, -outward_normal, time);
+        Self::new(
+            0.0,
+            p,
+            &ray,
+            outward_normal,
+            material,
+            object_id,
+            texture_coords,
+        )
+    }
+
     pub fn offset(mut self, offset: Vec3) -> Self {
         self.p += offset;
         self
@@ -72,8 +214,11 @@ pub enum Hittable {
     List(HittableList),
     BvhNode(BvhNode),
     Quad(Quad),
+    Triangle(Triangle),
     Translate(Translate),
     Rotate(Rotate),
+    AnimatedTranslate(AnimatedTranslate),
+    AnimatedRotate(AnimatedRotate),
     ConstantMedium(ConstantMedium),
 }
 
@@ -81,6 +226,46 @@ pub enum Hittable {
 pub trait Hit: Sync {
     fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>>;
     fn bounding_box(&self) -> &Aabb;
+
+    /// All parts of the object with an emissive material, including their transformations.
+    fn emitters(&self) -> Vec<Hittable> {
+        Vec::new()
+    }
+
+    /// Directions of the outward normals of the object's surface, which bound the directions
+    /// into which it emits light.
+    fn normal_bounds(&self) -> DirectionCone {
+        DirectionCone::all()
+    }
+
+    /// Whether the surface encloses a volume, so that light it emits towards the inside never
+    /// leaves it.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Probability density, with respect to solid angle, of `sample_direction` choosing the
+    /// direction of `r` when called with its origin and time.
+    fn pdf_value(&self, _r: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Samples a direction from `origin` towards a point on the object, at the given time.
+    fn sample_direction(&self, _origin: &Point3, _time: f64, _u: (f64, f64)) -> Vec3 {
+        Vec3::new(1, 0, 0)
+    }
+
+    /// Samples a point on the surface of the object uniformly by area, at the given time. Returns
+    /// a hit record for the point, with the outward normal, and the density with respect to area.
+    fn sample_surface(&self, _time: f64, _u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        None
+    }
+
+    /// Density, with respect to area, of `sample_surface` choosing the point where `r` hits the
+    /// object within `ray_bounds`.
+    fn surface_pdf(&self, _r: &Ray, _ray_bounds: &Interval) -> f64 {
+        0.0
+    }
 }
 
 pub trait Instance {
@@ -88,6 +273,8 @@ pub trait Instance {
     fn rotate_x(self, angle_degrees: f64) -> Hittable;
     fn rotate_y(self, angle_degrees: f64) -> Hittable;
     fn rotate_z(self, angle_degrees: f64) -> Hittable;
+    fn translate_keyframed(self, offsets: Keyframes<Vec3>) -> Hittable;
+    fn rotate_keyframed(self, angles_degrees: Keyframes<f64>, axis: Axis) -> Hittable;
 }
 
 impl<H: Into<Hittable>> Instance for H {
@@ -106,4 +293,12 @@ impl<H: Into<Hittable>> Instance for H {
     fn rotate_z(self, angle_degrees: f64) -> Hittable {
         Rotate::new(self.into(), angle_degrees, Axis::Z).into()
     }
+
+    fn translate_keyframed(self, offsets: Keyframes<Vec3>) -> Hittable {
+        AnimatedTranslate::new(self.into(), offsets).into()
+    }
+
+    fn rotate_keyframed(self, angles_degrees: Keyframes<f64>, axis: Axis) -> Hittable {
+        AnimatedRotate::new(self.into(), angles_degrees, axis).into()
+    }
 }
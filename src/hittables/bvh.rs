@@ -9,6 +9,8 @@ pub struct BvhNode {
     left: Box<Hittable>,
     right: Box<Hittable>,
     bbox: Aabb,
+    /// Nodes for a single object hold it on both sides
+    single_object: bool,
 }
 
 impl BvhNode {
@@ -16,6 +18,7 @@ impl BvhNode {
         let bbox = Aabb::merge(objects.iter().map(|o| o.bounding_box()));
 
         let axis = bbox.longest_axis();
+        let single_object = objects.len() == 1;
 
         let (left, right) = match objects.len() {
             1 => (
@@ -46,7 +49,12 @@ impl BvhNode {
             }
         };
 
-        Self { left, right, bbox }
+        Self {
+            left,
+            right,
+            bbox,
+            single_object,
+        }
     }
 
     fn box_compare(a: &Hittable, b: &Hittable, axis: Axis) -> bool {
@@ -80,4 +88,12 @@ impl Hit for BvhNode {
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        let mut emitters = self.left.emitters();
+        if !self.single_object {
+            emitters.extend(self.right.emitters());
+        }
+        emitters
+    }
 }
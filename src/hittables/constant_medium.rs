@@ -1,18 +1,18 @@
-use rand::{thread_rng, Rng};
-
 use crate::{
     material::{Isotropic, Material},
     math::{Aabb, Interval, Ray, Vec3},
+    sampler::hash,
     texture::{Texture, TextureCoords},
 };
 
-use super::{Hit, HitRecord, Hittable};
+use super::{Hit, HitRecord, Hittable, ObjectId};
 
 #[derive(Debug, Clone)]
 pub struct ConstantMedium {
     boundary: Box<Hittable>,
     neg_inv_density: f64,
     phase_function: Material,
+    id: ObjectId,
 }
 
 impl ConstantMedium {
@@ -21,10 +21,27 @@ impl ConstantMedium {
             boundary: Box::new(boundary.into()),
             neg_inv_density: -1.0 / density,
             phase_function: Isotropic::new(texture.into()).into(),
+            id: ObjectId::unique(),
         }
     }
 }
 
+/// Uniform random number in (0,1] derived from the ray. `Hit::hit` has no access to the sampler,
+/// so the scattering distance is made deterministic by hashing the ray instead.
+fn random_from_ray(r: &Ray) -> f64 {
+    let (origin, direction) = (r.origin(), r.direction());
+    let bits = hash(&[
+        origin.x().to_bits(),
+        origin.y().to_bits(),
+        origin.z().to_bits(),
+        direction.x.to_bits(),
+        direction.y.to_bits(),
+        direction.z.to_bits(),
+        r.time().to_bits(),
+    ]);
+    ((bits >> 11) + 1) as f64 / (1u64 << 53) as f64
+}
+
 impl Hit for ConstantMedium {
     fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
         let mut entry_hit = self.boundary.hit(r, &Interval::universe())?;
@@ -43,7 +60,7 @@ impl Hit for ConstantMedium {
 
         let ray_length = r.direction().length();
         let distance_inside_boundary = (exit_hit.t - entry_hit.t) * ray_length;
-        let hit_distance = self.neg_inv_density * thread_rng().gen::<f64>().ln();
+        let hit_distance = self.neg_inv_density * random_from_ray(r).ln();
 
         if hit_distance > distance_inside_boundary {
             return None; // Ray passes through the medium
@@ -56,6 +73,7 @@ impl Hit for ConstantMedium {
             r,
             Vec3::new(1, 0, 0), // arbitrary
             &self.phase_function,
+            self.id,
             TextureCoords::default(),
         )
         .into()
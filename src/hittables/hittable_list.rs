@@ -1,6 +1,6 @@
 use itertools::Itertools;
 
-use crate::math::{self, Aabb, Interval};
+use crate::math::{self, Aabb, Interval, Point3, Ray, Vec3};
 
 use super::{Hit, HitRecord, Hittable};
 
@@ -19,7 +19,7 @@ impl HittableList {
 }
 
 impl Hit for HittableList {
-    fn hit(&self, r: &math::Ray, ray_bounds: &Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &math::Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
         self.objects
             .iter()
             .flat_map(|o| o.hit(r, ray_bounds))
@@ -29,6 +29,57 @@ impl Hit for HittableList {
     fn bounding_box(&self) -> &math::Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        self.objects.iter().flat_map(Hit::emitters).collect()
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        let sum = self.objects.iter().map(|o| o.pdf_value(r)).sum::<f64>();
+        sum / self.objects.len().max(1) as f64
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, (u1, u2): (f64, f64)) -> Vec3 {
+        // Pick an object uniformly, then reuse the rest of u1 for sampling it
+        let n = self.objects.len();
+        if n == 0 {
+            return Vec3::new(1, 0, 0);
+        }
+        let index = ((u1 * n as f64) as usize).min(n - 1);
+        let u1 = u1 * n as f64 - index as f64;
+        self.objects[index].sample_direction(origin, time, (u1, u2))
+    }
+
+    fn sample_surface(&self, time: f64, (u1, u2): (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let n = self.objects.len();
+        if n == 0 {
+            return None;
+        }
+        let index = ((u1 * n as f64) as usize).min(n - 1);
+        let u1 = u1 * n as f64 - index as f64;
+        self.objects[index]
+            .sample_surface(time, (u1, u2))
+            .map(|(hit_record, pdf)| (hit_record, pdf / n as f64))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        let sum = self
+            .objects
+            .iter()
+            .map(|o| o.surface_pdf(r, ray_bounds))
+            .sum::<f64>();
+        sum / self.objects.len().max(1) as f64
+    }
+}
+
+impl HittableList {
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
 }
 
 impl<IntoHittable: Into<Hittable>> FromIterator<IntoHittable> for HittableList {
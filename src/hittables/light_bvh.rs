@@ -0,0 +1,459 @@
+use std::f64::consts::PI;
+
+use itertools::iproduct;
+
+use crate::{
+    material::{Material, ScatterAndEmit},
+    math::{cross, dot, Aabb, Interval, Matrix3, Point3, Ray, Vec3},
+    sampler::ONE_MINUS_EPSILON,
+};
+
+use super::{Hit, Hittable};
+
+/// Set of directions within an angle of the unit vector `w`, given by its cosine.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionCone {
+    w: Vec3,
+    cos_theta: f64,
+}
+
+impl DirectionCone {
+    pub fn all() -> Self {
+        Self {
+            w: Vec3::new(0, 0, 1),
+            cos_theta: -1.0,
+        }
+    }
+
+    /// The single direction `w`.
+    pub fn around(w: Vec3) -> Self {
+        Self {
+            w: w.normalized(),
+            cos_theta: 1.0,
+        }
+    }
+
+    pub fn rotate(&self, rotation: Matrix3) -> Self {
+        Self {
+            w: rotation * self.w,
+            ..*self
+        }
+    }
+
+    /// Smallest cone that contains both cones.
+    fn union(&self, other: &Self) -> Self {
+        let theta_a = self.cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_b = other.cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_d = dot(&self.w, &other.w).clamp(-1.0, 1.0).acos();
+        if (theta_d + theta_b).min(PI) <= theta_a {
+            return *self;
+        }
+        if (theta_d + theta_a).min(PI) <= theta_b {
+            return *other;
+        }
+
+        // Rotate the axis of this cone towards the other one, so that the new cone just reaches
+        // the far sides of both
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        if theta_o >= PI {
+            return Self::all();
+        }
+        let axis = cross(&self.w, &other.w);
+        if axis.length_squared() == 0.0 {
+            return Self::all();
+        }
+        let (k, theta_r) = (axis.normalized(), theta_o - theta_a);
+        let w = theta_r.cos() * self.w
+            + theta_r.sin() * cross(&k, &self.w)
+            + (dot(&k, &self.w) * (1.0 - theta_r.cos())) * k;
+        Self {
+            w: w.normalized(),
+            cos_theta: theta_o.cos(),
+        }
+    }
+}
+
+/// Bounds of the light that one or more lights emit: where they are, how much power they emit,
+/// and into which directions (Conty Estevez and Kulla, 2018).
+#[derive(Debug, Clone)]
+struct LightBounds {
+    bbox: Aabb,
+    /// Emitted power, as luminance
+    phi: f64,
+    normals: DirectionCone,
+    /// Cosine of the largest angle to its normal at which a point emits light
+    cos_theta_e: f64,
+    /// Whether the lights emit from both sides of their surfaces
+    two_sided: bool,
+}
+
+impl LightBounds {
+    /// Bounds of a light, or `None` if it doesn't emit light.
+    fn new(light: &Hittable) -> Option<Self> {
+        let (hit_record, _) = light.sample_surface(0.0, (0.5, 0.5))?;
+        let profile = hit_record.material.emission_profile()?;
+        let phi = emitted_power(light, None);
+        (phi > 0.0).then(|| Self {
+            bbox: light.bounding_box().clone(),
+            phi,
+            normals: light.normal_bounds(),
+            cos_theta_e: profile.cos_theta_max(),
+            two_sided: profile.two_sided,
+        })
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            bbox: Aabb::merge([&self.bbox, &other.bbox]),
+            phi: self.phi + other.phi,
+            normals: self.normals.union(&other.normals),
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    /// Upper bound of the light that could arrive at `p` from the lights, relative to other
+    /// bounds. With the normal of the surface at `p`, light from below the surface is excluded.
+    fn importance(&self, p: &Point3, normal: Option<&Vec3>) -> f64 {
+        let center = self.bbox.center();
+        let to_p = *p - center;
+        let radius = self.bbox.diagonal().length() / 2.0;
+        let distance_squared = to_p.length_squared().max(radius);
+        let wi = if to_p.length_squared() > 0.0 {
+            to_p.normalized()
+        } else {
+            self.normals.w
+        };
+
+        // Smallest angle between a normal of the lights and the direction towards p, from
+        // anywhere within the bounds
+        let mut cos_theta_w = dot(&self.normals.w, &wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let cos_theta_b = if to_p.length_squared() < radius * radius {
+            -1.0
+        } else {
+            (1.0 - radius * radius / to_p.length_squared())
+                .max(0.0)
+                .sqrt()
+        };
+        let (cos_theta_x, sin_theta_x) = cos_sin_sub_clamped(cos_theta_w, self.normals.cos_theta);
+        let cos_theta_p = cos_sub_clamped(cos_theta_x, sin_theta_x, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.phi * cos_theta_p / distance_squared;
+        if let Some(normal) = normal {
+            let cos_theta_i = dot(&wi, normal).abs();
+            let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+            importance *= cos_sub_clamped(cos_theta_i, sin_theta_i, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+}
+
+/// Number of points per dimension at which the emission of an object is evaluated to estimate its
+/// power
+const POWER_SAMPLES: usize = 4;
+/// Number of polar angles at which the radiance of a point is integrated, if it doesn't only
+/// depend on the angle to the normal
+const DIRECTION_SAMPLES: usize = 32;
+
+/// Power that an object emits, as luminance, estimated from the radiance at points sampled on
+/// its surface. With `material`, as if the object had that material instead of its own.
+pub(crate) fn emitted_power(object: &impl Hit, material: Option<&Material>) -> f64 {
+    let n = POWER_SAMPLES;
+    let mut power = 0.0;
+    for (i, j) in iproduct!(0..n, 0..n) {
+        let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+        let Some((hit_record, pdf)) = object.sample_surface(0.0, u) else {
+            continue;
+        };
+        let material = material.unwrap_or(hit_record.material);
+        let Some(profile) = material.emission_profile() else {
+            continue;
+        };
+        // Light emitted to the inside of closed objects doesn't count
+        let exitance = if profile.ies.is_some() {
+            integrate_sphere(|direction| {
+                let cos_theta = dot(&hit_record.normal, direction);
+                if object.is_closed() && cos_theta <= 0.0 {
+                    return 0.0;
+                }
+                let radiance = material.emit(&hit_record, direction).luminance();
+                radiance.max(0.0) * cos_theta.abs()
+            })
+        } else {
+            let radiance = material.emit(&hit_record, &hit_record.normal).luminance();
+            let sides = if profile.two_sided && object.is_closed() {
+                0.5
+            } else {
+                1.0
+            };
+            sides * profile.projected_solid_angle() * radiance.max(0.0)
+        };
+        if pdf > 0.0 {
+            power += exitance / pdf;
+        }
+    }
+    power / (n * n) as f64
+}
+
+/// Integral of `f` over all unit vectors, with the midpoint rule in the cosine of the polar
+/// angle and the azimuth.
+fn integrate_sphere(f: impl Fn(&Vec3) -> f64) -> f64 {
+    let (m, n) = (DIRECTION_SAMPLES, 2 * DIRECTION_SAMPLES);
+    let mut sum = 0.0;
+    for (i, j) in iproduct!(0..m, 0..n) {
+        let cos_theta = 1.0 - 2.0 * (i as f64 + 0.5) / m as f64;
+        let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+        let sin_theta = sin_from_cos(cos_theta);
+        sum += f(&Vec3::new(
+            sin_theta * phi.cos(),
+            cos_theta,
+            sin_theta * phi.sin(),
+        ));
+    }
+    4.0 * PI * sum / (m * n) as f64
+}
+
+/// Cosine and sine of the angle of `cos_a` minus the angle of `cos_b`, or of 0 if that is
+/// negative.
+fn cos_sin_sub_clamped(cos_a: f64, cos_b: f64) -> (f64, f64) {
+    if cos_a > cos_b {
+        return (1.0, 0.0);
+    }
+    let (sin_a, sin_b) = (sin_from_cos(cos_a), sin_from_cos(cos_b));
+    (cos_a * cos_b + sin_a * sin_b, sin_a * cos_b - cos_a * sin_b)
+}
+
+fn cos_sub_clamped(cos_a: f64, sin_a: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_from_cos(cos_b)
+}
+
+fn sin_from_cos(cos: f64) -> f64 {
+    (1.0 - cos * cos).max(0.0).sqrt()
+}
+
+#[derive(Debug, Clone)]
+enum LightBvhNode {
+    Leaf {
+        bounds: LightBounds,
+        light: usize,
+    },
+    /// The first child directly follows its parent
+    Interior {
+        bounds: LightBounds,
+        second_child: usize,
+    },
+}
+
+impl LightBvhNode {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            Self::Leaf { bounds, .. } | Self::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the lights, which chooses lights by their importance to a
+/// point. Every node bounds the power of the lights below it and the directions they emit into,
+/// and sampling descends the tree choosing children proportional to how much light they could
+/// send to the point. That way, of hundreds of small lights, the ones near the point are chosen
+/// more often, and lights that face away from it aren't chosen at all.
+#[derive(Debug, Clone, Default)]
+pub struct LightBvh {
+    lights: Vec<Hittable>,
+    nodes: Vec<LightBvhNode>,
+    /// For every light, the children to descend into from the root to reach its leaf, as one bit
+    /// per level from the least significant one, set for second children
+    trails: Vec<u64>,
+}
+
+impl LightBvh {
+    /// Builds the hierarchy over the lights that emit any light.
+    pub fn new(lights: Vec<Hittable>) -> Self {
+        let (lights, mut bounds): (Vec<_>, Vec<_>) = lights
+            .into_iter()
+            .filter_map(|light| {
+                let bounds = LightBounds::new(&light)?;
+                Some((light, bounds))
+            })
+            .enumerate()
+            .map(|(index, (light, bounds))| (light, (index, bounds)))
+            .unzip();
+
+        let mut bvh = Self {
+            trails: vec![0; lights.len()],
+            lights,
+            nodes: Vec::new(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(&mut bounds, 0, 0);
+        }
+        bvh
+    }
+
+    /// Appends the subtree over the given lights, which is reached by `trail` at `depth`.
+    fn build(
+        &mut self,
+        lights: &mut [(usize, LightBounds)],
+        trail: u64,
+        depth: u32,
+    ) -> LightBounds {
+        if let [(light, bounds)] = lights {
+            self.trails[*light] = trail;
+            self.nodes.push(LightBvhNode::Leaf {
+                bounds: bounds.clone(),
+                light: *light,
+            });
+            return bounds.clone();
+        }
+
+        // Split at the median of the centers along the longest axis
+        let centers: Aabb = lights
+            .iter()
+            .map(|(_, bounds)| bounds.bbox.center())
+            .collect();
+        let axis = centers.longest_axis() as usize;
+        lights.sort_unstable_by(|(_, lhs), (_, rhs)| {
+            lhs.bbox.center()[axis].total_cmp(&rhs.bbox.center()[axis])
+        });
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+
+        let index = self.nodes.len();
+        self.nodes.push(LightBvhNode::Interior {
+            bounds: first[0].1.clone(),
+            second_child: 0,
+        });
+        let first_bounds = self.build(first, trail, depth + 1);
+        let second_child = self.nodes.len();
+        let second_bounds = self.build(second, trail | (1 << depth), depth + 1);
+
+        let bounds = first_bounds.union(&second_bounds);
+        self.nodes[index] = LightBvhNode::Interior {
+            bounds: bounds.clone(),
+            second_child,
+        };
+        bounds
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Samples a direction from `origin` towards a point on one of the lights, chosen by its
+    /// importance to `origin`, at the given time. `normal` is the surface normal at `origin`, if
+    /// it is on a surface. Returns `None` if no light can illuminate `origin`.
+    pub fn sample_direction(
+        &self,
+        origin: &Point3,
+        normal: Option<&Vec3>,
+        time: f64,
+        (u1, u2): (f64, f64),
+    ) -> Option<Vec3> {
+        if self.root_importance(origin, normal) <= 0.0 {
+            return None;
+        }
+
+        // Choose a child at every level, then reuse the rest of u1 for the next level
+        let mut index = 0;
+        let mut u1 = u1;
+        loop {
+            match &self.nodes[index] {
+                LightBvhNode::Leaf { light, .. } => {
+                    return Some(self.lights[*light].sample_direction(origin, time, (u1, u2)));
+                }
+                LightBvhNode::Interior { second_child, .. } => {
+                    let p_first =
+                        self.first_child_probability(index, *second_child, origin, normal)?;
+                    if u1 < p_first {
+                        index += 1;
+                        u1 = (u1 / p_first).min(ONE_MINUS_EPSILON);
+                    } else {
+                        index = *second_child;
+                        u1 = ((u1 - p_first) / (1.0 - p_first)).min(ONE_MINUS_EPSILON);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Probability density, with respect to solid angle, of `sample_direction` choosing the
+    /// direction of `r` when called with its origin and time, and the same `normal`.
+    pub fn pdf_value(&self, r: &Ray, normal: Option<&Vec3>) -> f64 {
+        if self.is_empty() || self.root_importance(r.origin(), normal) <= 0.0 {
+            return 0.0;
+        }
+
+        // Only lights whose bounds the ray passes through can have been sampled
+        let ray_bounds = Interval::from(0.001..=f64::INFINITY);
+        let mut pdf = 0.0;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds().bbox.hit(r, &ray_bounds) {
+                continue;
+            }
+            match node {
+                LightBvhNode::Leaf { light, .. } => {
+                    let light_pdf = self.lights[*light].pdf_value(r);
+                    if light_pdf > 0.0 {
+                        pdf += self.pmf(*light, r.origin(), normal) * light_pdf;
+                    }
+                }
+                LightBvhNode::Interior { second_child, .. } => {
+                    stack.extend([index + 1, *second_child]);
+                }
+            }
+        }
+        pdf
+    }
+
+    /// Probability of `sample_direction` choosing `light`.
+    fn pmf(&self, light: usize, origin: &Point3, normal: Option<&Vec3>) -> f64 {
+        let mut trail = self.trails[light];
+        let mut index = 0;
+        let mut pmf = 1.0;
+        while let LightBvhNode::Interior { second_child, .. } = &self.nodes[index] {
+            let Some(p_first) = self.first_child_probability(index, *second_child, origin, normal)
+            else {
+                return 0.0;
+            };
+            if trail & 1 == 0 {
+                index += 1;
+                pmf *= p_first;
+            } else {
+                index = *second_child;
+                pmf *= 1.0 - p_first;
+            }
+            trail >>= 1;
+        }
+        pmf
+    }
+
+    fn root_importance(&self, origin: &Point3, normal: Option<&Vec3>) -> f64 {
+        self.nodes
+            .first()
+            .map_or(0.0, |root| root.bounds().importance(origin, normal))
+    }
+
+    /// Probability of descending from interior node `index` into its first child, or `None` if
+    /// neither child can illuminate `origin`.
+    fn first_child_probability(
+        &self,
+        index: usize,
+        second_child: usize,
+        origin: &Point3,
+        normal: Option<&Vec3>,
+    ) -> Option<f64> {
+        let first = self.nodes[index + 1].bounds().importance(origin, normal);
+        let second = self.nodes[second_child].bounds().importance(origin, normal);
+        (first + second > 0.0).then(|| first / (first + second))
+    }
+}
@@ -1,10 +1,10 @@
 use crate::{
-    material::Material,
+    material::{Material, ScatterAndEmit},
     math::{cross, dot, Aabb, Interval, Point3, Ray, Vec3},
     texture::TextureCoords,
 };
 
-use super::{Hit, HitRecord, HittableList};
+use super::{DirectionCone, Hit, HitRecord, Hittable, HittableList, ObjectId};
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone)]
@@ -14,9 +14,11 @@ pub struct Quad {
     v: Vec3,
     w: Vec3,
     material: Material,
+    id: ObjectId,
     bbox: Aabb,
     normal: Vec3,
     D: f64, // rhs of the quad's plane equation (Ax+By+Cy=D)
+    area: f64,
 }
 
 impl Quad {
@@ -35,10 +37,21 @@ impl Quad {
             v,
             w: n / dot(&n, &n),
             material: material.into(),
+            id: ObjectId::unique(),
             bbox: Aabb::merge([bbox_diagonal1, bbox_diagonal2]),
             normal,
             D,
+            area: n.length(),
         }
+        .with_fitted_material()
+    }
+
+    /// See [`Material::fitted_to`]
+    fn with_fitted_material(mut self) -> Self {
+        if let Some(material) = self.material.fitted_to(&self) {
+            self.material = material;
+        }
+        self
     }
 
     fn is_interior(a: f64, b: f64) -> bool {
@@ -53,7 +66,9 @@ impl Quad {
         let dy = Vec3::new(0, max.y() - min.y(), 0);
         let dz = Vec3::new(0, 0, max.z() - min.z());
 
+        // All sides of the box are one object
         let material = material.into();
+        let id = ObjectId::unique();
         [
             Quad::new(Point3::new(min.x(), min.y(), max.z()),  dx,  dy, material.clone()), // front
             Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz,  dy, material.clone()), // right
@@ -61,7 +76,7 @@ impl Quad {
             Quad::new(Point3::new(min.x(), min.y(), min.z()),  dz,  dy, material.clone()), // left
             Quad::new(Point3::new(min.x(), max.y(), max.z()),  dx, -dz, material.clone()), // top
             Quad::new(Point3::new(min.x(), min.y(), min.z()),  dx,  dz, material.clone()), // bottom
-        ].into_iter().collect()
+        ].into_iter().map(|side| Quad { id, ..side }).collect()
     }
 }
 
@@ -85,17 +100,68 @@ impl Hit for Quad {
         let alpha = dot(&self.w, &cross(&planar_hit_point_vector, &self.v));
         let beta = dot(&self.w, &cross(&self.u, &planar_hit_point_vector));
 
-        Self::is_interior(alpha, beta).then_some(HitRecord::new(
-            t,
-            intersection,
-            r,
-            self.normal,
-            &self.material,
-            TextureCoords { u: alpha, v: beta },
-        ))
+        Self::is_interior(alpha, beta).then(|| {
+            HitRecord::new(
+                t,
+                intersection,
+                r,
+                self.normal,
+                &self.material,
+                self.id,
+                TextureCoords::new(alpha, beta),
+            )
+            .with_differentials(r, self.u, self.v, Vec3::zero(), Vec3::zero())
+        })
     }
 
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        if self.material.is_emissive() {
+            vec![self.clone().into()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn normal_bounds(&self) -> DirectionCone {
+        DirectionCone::around(self.normal)
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        // Points are sampled uniformly by area, so convert from area to solid angle
+        let Some(hit_record) = self.hit(r, &(0.001..=f64::INFINITY).into()) else {
+            return 0.0;
+        };
+        let distance_squared = hit_record.t.powi(2) * r.direction().length_squared();
+        let cosine = dot(r.direction(), &self.normal).abs() / r.direction().length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn sample_direction(&self, origin: &Point3, _time: f64, (u1, u2): (f64, f64)) -> Vec3 {
+        let p = self.Q + (u1 * self.u) + (u2 * self.v);
+        p - *origin
+    }
+
+    fn sample_surface(&self, time: f64, (u1, u2): (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let p = self.Q + (u1 * self.u) + (u2 * self.v);
+        let hit_record = HitRecord::on_surface(
+            p,
+            self.normal,
+            time,
+            &self.material,
+            self.id,
+            TextureCoords::new(u1, u2),
+        );
+        Some((hit_record, 1.0 / self.area))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        match self.hit(r, ray_bounds) {
+            Some(_) => 1.0 / self.area,
+            None => 0.0,
+        }
+    }
 }
@@ -1,8 +1,8 @@
 use itertools::iproduct;
 
-use crate::math::{Aabb, Axis, Interval, Matrix3, Point3, Ray};
+use crate::math::{Aabb, Axis, Interval, Keyframes, Matrix3, Point3, Ray, RayDifferentials, Vec3};
 
-use super::{Hit, HitRecord, Hittable};
+use super::{DirectionCone, Hit, HitRecord, Hittable};
 
 #[derive(Debug, Clone)]
 pub struct Rotate {
@@ -12,21 +12,27 @@ pub struct Rotate {
     bbox: Aabb,
 }
 
+fn corners(bbox: &Aabb) -> impl Iterator<Item = Point3> {
+    iproduct!(
+        [bbox.x.min(), bbox.x.max()],
+        [bbox.y.min(), bbox.y.max()],
+        [bbox.z.min(), bbox.z.max()]
+    )
+    .map(|(x, y, z)| Point3::new(x, y, z))
+}
+
 impl Rotate {
     pub fn new(object: impl Into<Hittable>, angle_degrees: f64, axis: Axis) -> Self {
-        let object: Hittable = object.into();
+        Self::from_matrices(
+            object.into(),
+            Matrix3::rotate(-angle_degrees, axis),
+            Matrix3::rotate(angle_degrees, axis),
+        )
+    }
 
-        let to_object_space = Matrix3::rotate(-angle_degrees, axis);
-        let to_world_space = Matrix3::rotate(angle_degrees, axis);
-
-        let bbox = object.bounding_box().clone();
-        let corners = iproduct!(
-            [bbox.x.min(), bbox.x.max()],
-            [bbox.y.min(), bbox.y.max()],
-            [bbox.z.min(), bbox.z.max()]
-        );
-        let bbox = corners
-            .map(|(x, y, z)| to_world_space * Point3::new(x, y, z))
+    fn from_matrices(object: Hittable, to_object_space: Matrix3, to_world_space: Matrix3) -> Self {
+        let bbox = corners(object.bounding_box())
+            .map(|p| to_world_space * p)
             .collect();
 
         Self {
@@ -38,24 +44,218 @@ impl Rotate {
     }
 }
 
+fn rotate_ray(r: &Ray, to_object_space: Matrix3) -> Ray {
+    let differentials = r.differentials().map(|d| RayDifferentials {
+        rx_origin: to_object_space * d.rx_origin,
+        rx_direction: to_object_space * d.rx_direction,
+        ry_origin: to_object_space * d.ry_origin,
+        ry_direction: to_object_space * d.ry_direction,
+    });
+    Ray::new(
+        to_object_space * *r.origin(),
+        to_object_space * *r.direction(),
+        r.time(),
+    )
+    .with_wavelengths(r.wavelengths())
+    .with_differentials(differentials)
+}
+
+fn rotate_hit<'a>(
+    object: &'a Hittable,
+    to_object_space: Matrix3,
+    to_world_space: Matrix3,
+    r: &Ray,
+    ray_bounds: &Interval,
+) -> Option<HitRecord<'a>> {
+    object
+        .hit(&rotate_ray(r, to_object_space), ray_bounds)
+        .map(|mut hit_record| {
+            hit_record.p = to_world_space * hit_record.p;
+            hit_record.normal = to_world_space * hit_record.normal;
+            hit_record.differentials = hit_record
+                .differentials
+                .map(|d| d.transformed(to_world_space));
+            hit_record
+        })
+}
+
 impl Hit for Rotate {
     fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
-        let rotated_ray = Ray::new(
-            self.to_object_space * *r.origin(),
-            self.to_object_space * *r.direction(),
-            r.time(),
-        );
+        rotate_hit(
+            &self.object,
+            self.to_object_space,
+            self.to_world_space,
+            r,
+            ray_bounds,
+        )
+    }
 
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+
+    fn emitters(&self) -> Vec<Hittable> {
         self.object
-            .hit(&rotated_ray, ray_bounds)
-            .map(|mut hit_record| {
-                hit_record.p = self.to_world_space * hit_record.p;
-                hit_record.normal = self.to_world_space * hit_record.normal;
-                hit_record
+            .emitters()
+            .into_iter()
+            .map(|emitter| {
+                Rotate::from_matrices(emitter, self.to_object_space, self.to_world_space).into()
             })
+            .collect()
+    }
+
+    fn normal_bounds(&self) -> DirectionCone {
+        self.object.normal_bounds().rotate(self.to_world_space)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        self.object.pdf_value(&rotate_ray(r, self.to_object_space))
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        let direction = self
+            .object
+            .sample_direction(&(self.to_object_space * *origin), time, u);
+        self.to_world_space * direction
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let to_world_space = self.to_world_space;
+        self.object
+            .sample_surface(time, u)
+            .map(|(mut hit_record, pdf)| {
+                hit_record.p = to_world_space * hit_record.p;
+                hit_record.normal = to_world_space * hit_record.normal;
+                (hit_record, pdf)
+            })
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        self.object
+            .surface_pdf(&rotate_ray(r, self.to_object_space), ray_bounds)
+    }
+}
+
+/// Rotation about one axis with an angle that changes over time, evaluated at the time of each
+/// ray.
+#[derive(Debug, Clone)]
+pub struct AnimatedRotate {
+    object: Box<Hittable>,
+    angles_degrees: Keyframes<f64>,
+    axis: Axis,
+    bbox: Aabb,
+}
+
+impl AnimatedRotate {
+    // Step size for sampling the swept volume of the bounding box corners
+    const BBOX_STEP_DEGREES: f64 = 1.0;
+
+    pub fn new(object: impl Into<Hittable>, angles_degrees: Keyframes<f64>, axis: Axis) -> Self {
+        let object: Hittable = object.into();
+
+        // Between the smallest and the largest possible angle, every corner of the bounding box
+        // moves along a circular arc. Sample these arcs densely, then pad by the maximum distance
+        // between an arc and its chord.
+        let (min_angle, max_angle) = angles_degrees
+            .bounding_values()
+            .into_iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), a| {
+                (min.min(a), max.max(a))
+            });
+        let steps = ((max_angle - min_angle) / Self::BBOX_STEP_DEGREES)
+            .ceil()
+            .max(1.0) as usize;
+        let step = (max_angle - min_angle) / steps as f64;
+
+        let object_corners: Vec<_> = corners(object.bounding_box()).collect();
+        let swept: Aabb = (0..=steps)
+            .map(|i| Matrix3::rotate(min_angle + i as f64 * step, axis))
+            .flat_map(|m| object_corners.iter().map(move |p| m * *p))
+            .collect();
+
+        let max_radius = object_corners
+            .iter()
+            .map(|p| p.as_vec3().length())
+            .fold(0.0, f64::max);
+        let padding = max_radius * (1.0 - f64::cos((step / 2.0).to_radians()));
+        let bbox = Aabb {
+            x: swept.x.expand(2.0 * padding),
+            y: swept.y.expand(2.0 * padding),
+            z: swept.z.expand(2.0 * padding),
+        };
+
+        Self {
+            object: Box::new(object),
+            angles_degrees,
+            axis,
+            bbox,
+        }
+    }
+}
+
+impl Hit for AnimatedRotate {
+    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
+        let angle_degrees = self.angles_degrees.at(r.time());
+        rotate_hit(
+            &self.object,
+            Matrix3::rotate(-angle_degrees, self.axis),
+            Matrix3::rotate(angle_degrees, self.axis),
+            r,
+            ray_bounds,
+        )
     }
 
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|emitter| {
+                AnimatedRotate::new(emitter, self.angles_degrees.clone(), self.axis).into()
+            })
+            .collect()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        let angle_degrees = self.angles_degrees.at(r.time());
+        let to_object_space = Matrix3::rotate(-angle_degrees, self.axis);
+        self.object.pdf_value(&rotate_ray(r, to_object_space))
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        let angle_degrees = self.angles_degrees.at(time);
+        let to_object_space = Matrix3::rotate(-angle_degrees, self.axis);
+        let direction = self
+            .object
+            .sample_direction(&(to_object_space * *origin), time, u);
+        Matrix3::rotate(angle_degrees, self.axis) * direction
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let to_world_space = Matrix3::rotate(self.angles_degrees.at(time), self.axis);
+        self.object
+            .sample_surface(time, u)
+            .map(|(mut hit_record, pdf)| {
+                hit_record.p = to_world_space * hit_record.p;
+                hit_record.normal = to_world_space * hit_record.normal;
+                (hit_record, pdf)
+            })
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        let to_object_space = Matrix3::rotate(-self.angles_degrees.at(r.time()), self.axis);
+        self.object
+            .surface_pdf(&rotate_ray(r, to_object_space), ray_bounds)
+    }
 }
@@ -1,9 +1,9 @@
 use std::f64::consts::PI;
 
 use crate::{
-    hittables::{Hit, HitRecord},
-    material::Material,
-    math::{dot, Aabb, Interval, Point3, Ray, Vec3},
+    hittables::{Hit, HitRecord, Hittable, ObjectId},
+    material::{Material, ScatterAndEmit},
+    math::{dot, Aabb, Interval, Onb, Point3, Ray, Vec3},
 };
 
 use super::TextureCoords;
@@ -13,6 +13,7 @@ pub struct Sphere {
     center1: Point3,
     radius: f64,
     material: Material,
+    id: ObjectId,
     is_moving: bool,
     center_vec: Vec3,
     bbox: Aabb,
@@ -25,10 +26,12 @@ impl Sphere {
             center1: center,
             radius,
             material: material.into(),
+            id: ObjectId::unique(),
             is_moving: false,
             center_vec: Vec3::zero(),
             bbox: Aabb::from_points(center - rvec, center + rvec),
         }
+        .with_fitted_material()
     }
 
     pub fn moving(
@@ -44,10 +47,20 @@ impl Sphere {
             center1,
             radius,
             material: material.into(),
+            id: ObjectId::unique(),
             is_moving: true,
             center_vec: center2 - center1,
             bbox: Aabb::merge([bbox1, bbox2]),
         }
+        .with_fitted_material()
+    }
+
+    /// See [`Material::fitted_to`]
+    fn with_fitted_material(mut self) -> Self {
+        if let Some(material) = self.material.fitted_to(&self) {
+            self.material = material;
+        }
+        self
     }
 
     fn center_at_time(&self, time: f64) -> Point3 {
@@ -58,6 +71,18 @@ impl Sphere {
         }
     }
 
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    /// Cosine of the half angle of the cone of directions from `origin` that hit the sphere, or
+    /// `None` if `origin` is inside the sphere.
+    fn cos_theta_max(&self, origin: &Point3, time: f64) -> Option<f64> {
+        let distance_squared = (self.center_at_time(time) - *origin).length_squared();
+        let sin_theta_max_squared = self.radius.powi(2) / distance_squared;
+        (sin_theta_max_squared < 1.0).then(|| f64::sqrt(1.0 - sin_theta_max_squared))
+    }
+
     fn texture_coords(&self, p: &Point3) -> TextureCoords {
         // p: a given point on the sphere of radius one, centered at the origin.
         // u: returned value [0,1] of angle around the Y axis from X=-1.
@@ -69,15 +94,22 @@ impl Sphere {
         let theta = f64::acos(-p.y());
         let phi = f64::atan2(-p.z(), p.x()) + PI;
 
-        TextureCoords {
-            u: phi / (2.0 * PI),
-            v: theta / PI,
-        }
+        TextureCoords::new(phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Partial derivatives of the point with outward normal `n` with respect to the texture
+    /// coordinates of [`Self::texture_coords`].
+    fn surface_derivatives(&self, n: &Vec3) -> (Vec3, Vec3) {
+        let dpdu = (2.0 * PI * self.radius) * Vec3::new(n.z, 0.0, -n.x);
+        // The meridians meet at the poles, where any direction along them will do
+        let s = n.x.hypot(n.z).max(1e-6);
+        let dpdv = (PI * self.radius) * Vec3::new(-n.x * n.y / s, s, -n.y * n.z / s);
+        (dpdu, dpdv)
     }
 }
 
 impl Hit for Sphere {
-    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
         let center = self.center_at_time(r.time());
         let oc = center - *r.origin();
         let a = r.direction().length_squared();
@@ -101,17 +133,78 @@ impl Hit for Sphere {
         let p = r.at(root);
 
         let outward_normal = (p - center) / self.radius;
-        Some(HitRecord::new(
+        let hit_record = HitRecord::new(
             root,
             p,
             r,
             outward_normal,
             &self.material,
+            self.id,
             self.texture_coords(&outward_normal.into()),
-        ))
+        );
+        if r.differentials().is_none() {
+            return Some(hit_record);
+        }
+        let (dpdu, dpdv) = self.surface_derivatives(&outward_normal);
+        Some(hit_record.with_differentials(r, dpdu, dpdv, dpdu / self.radius, dpdv / self.radius))
     }
 
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        if self.material.is_emissive() {
+            vec![self.clone().into()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        // Directions are sampled uniformly from the cone of directions that hit the sphere, or
+        // from all directions when the origin is inside
+        let Some(cos_theta_max) = self.cos_theta_max(r.origin(), r.time()) else {
+            return 1.0 / (4.0 * PI);
+        };
+        if self.hit(r, &(0.001..=f64::INFINITY).into()).is_none() {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin, time) else {
+            return Vec3::unit_vector_from_sample(u);
+        };
+        let (u1, u2) = u;
+        let z = 1.0 + u2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * u1;
+        let r = f64::sqrt((1.0 - z * z).max(0.0));
+        Onb::new(&(self.center_at_time(time) - *origin)).transform(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let outward_normal = Vec3::unit_vector_from_sample(u);
+        let hit_record = HitRecord::on_surface(
+            self.center_at_time(time) + self.radius * outward_normal,
+            outward_normal,
+            time,
+            &self.material,
+            self.id,
+            self.texture_coords(&outward_normal.into()),
+        );
+        Some((hit_record, 1.0 / self.area()))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        match self.hit(r, ray_bounds) {
+            Some(_) => 1.0 / self.area(),
+            None => 0.0,
+        }
+    }
 }
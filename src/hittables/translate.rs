@@ -1,6 +1,6 @@
-use crate::math::{Aabb, Interval, Ray, Vec3};
+use crate::math::{Aabb, Interval, Keyframes, Point3, Ray, Vec3};
 
-use super::{Hit, HitRecord, Hittable};
+use super::{DirectionCone, Hit, HitRecord, Hittable};
 
 #[derive(Debug, Clone)]
 pub struct Translate {
@@ -33,4 +33,116 @@ impl Hit for Translate {
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|emitter| Translate::new(emitter, self.offset).into())
+            .collect()
+    }
+
+    fn normal_bounds(&self) -> DirectionCone {
+        self.object.normal_bounds()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        self.object.pdf_value(&r.offset(self.offset))
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        self.object
+            .sample_direction(&(*origin - self.offset), time, u)
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        self.object
+            .sample_surface(time, u)
+            .map(|(hit_record, pdf)| (hit_record.offset(self.offset), pdf))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        self.object.surface_pdf(&r.offset(self.offset), ray_bounds)
+    }
+}
+
+/// Translation that changes over time, evaluated at the time of each ray.
+#[derive(Debug, Clone)]
+pub struct AnimatedTranslate {
+    object: Box<Hittable>,
+    offsets: Keyframes<Vec3>,
+    bbox: Aabb,
+}
+
+impl AnimatedTranslate {
+    pub fn new(object: impl Into<Hittable>, offsets: Keyframes<Vec3>) -> Self {
+        let object = object.into();
+        let bbox = Aabb::merge(
+            offsets
+                .bounding_values()
+                .into_iter()
+                .map(|offset| object.bounding_box().clone() + offset),
+        );
+        Self {
+            object: Box::new(object),
+            offsets,
+            bbox,
+        }
+    }
+}
+
+impl Hit for AnimatedTranslate {
+    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
+        let offset = self.offsets.at(r.time());
+        let offset_ray = r.offset(offset);
+
+        self.object
+            .hit(&offset_ray, ray_bounds)
+            .map(|hit_record| hit_record.offset(offset))
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|emitter| AnimatedTranslate::new(emitter, self.offsets.clone()).into())
+            .collect()
+    }
+
+    fn normal_bounds(&self) -> DirectionCone {
+        self.object.normal_bounds()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        self.object.pdf_value(&r.offset(self.offsets.at(r.time())))
+    }
+
+    fn sample_direction(&self, origin: &Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        let offset = self.offsets.at(time);
+        self.object.sample_direction(&(*origin - offset), time, u)
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let offset = self.offsets.at(time);
+        self.object
+            .sample_surface(time, u)
+            .map(|(hit_record, pdf)| (hit_record.offset(offset), pdf))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        self.object
+            .surface_pdf(&r.offset(self.offsets.at(r.time())), ray_bounds)
+    }
 }
@@ -0,0 +1,157 @@
+use crate::{
+    material::{Material, ScatterAndEmit},
+    math::{cross, dot, Aabb, Interval, Point3, Ray, Vec3},
+    texture::TextureCoords,
+};
+
+use super::{DirectionCone, Hit, HitRecord, Hittable, ObjectId};
+
+/// Triangle with corners `Q`, `Q + u` and `Q + v`. Like [`super::Quad`], but with the points
+/// whose barycentric coordinates along `u` and `v` sum to at most one.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    Q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    material: Material,
+    id: ObjectId,
+    bbox: Aabb,
+    normal: Vec3,
+    D: f64, // rhs of the triangle's plane equation (Ax+By+Cy=D)
+    area: f64,
+}
+
+impl Triangle {
+    /// Triangle with the given corners, whose front side is the one from which they appear
+    /// counterclockwise.
+    #[allow(non_snake_case)]
+    pub fn new(a: Point3, b: Point3, c: Point3, material: impl Into<Material>) -> Self {
+        let (u, v) = (b - a, c - a);
+        let n = cross(&u, &v);
+        let normal = n.normalized();
+        let D = dot(&normal, a.as_vec3());
+
+        Self {
+            Q: a,
+            u,
+            v,
+            w: n / dot(&n, &n),
+            material: material.into(),
+            id: ObjectId::unique(),
+            bbox: [a, b, c].into_iter().collect(),
+            normal,
+            D,
+            area: n.length() / 2.0,
+        }
+        .with_fitted_material()
+    }
+
+    /// See [`Material::fitted_to`]
+    fn with_fitted_material(mut self) -> Self {
+        if let Some(material) = self.material.fitted_to(&self) {
+            self.material = material;
+        }
+        self
+    }
+
+    fn is_interior(a: f64, b: f64) -> bool {
+        a >= 0.0 && b >= 0.0 && a + b <= 1.0
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
+        let denominator = dot(&self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.D - dot(&self.normal, r.origin().as_vec3())) / denominator;
+        if !ray_bounds.contains(t) {
+            return None;
+        }
+
+        let intersection = r.at(t);
+
+        let planar_hit_point_vector = intersection - self.Q;
+        let alpha = dot(&self.w, &cross(&planar_hit_point_vector, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hit_point_vector));
+
+        Self::is_interior(alpha, beta).then(|| {
+            HitRecord::new(
+                t,
+                intersection,
+                r,
+                self.normal,
+                &self.material,
+                self.id,
+                TextureCoords::new(alpha, beta),
+            )
+            .with_differentials(r, self.u, self.v, Vec3::zero(), Vec3::zero())
+        })
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+
+    fn emitters(&self) -> Vec<Hittable> {
+        if self.material.is_emissive() {
+            vec![self.clone().into()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn normal_bounds(&self) -> DirectionCone {
+        DirectionCone::around(self.normal)
+    }
+
+    fn pdf_value(&self, r: &Ray) -> f64 {
+        // Points are sampled uniformly by area, so convert from area to solid angle
+        let Some(hit_record) = self.hit(r, &(0.001..=f64::INFINITY).into()) else {
+            return 0.0;
+        };
+        let distance_squared = hit_record.t.powi(2) * r.direction().length_squared();
+        let cosine = dot(r.direction(), &self.normal).abs() / r.direction().length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn sample_direction(&self, origin: &Point3, _time: f64, u: (f64, f64)) -> Vec3 {
+        let (alpha, beta) = uniform_barycentrics(u);
+        let p = self.Q + (alpha * self.u) + (beta * self.v);
+        p - *origin
+    }
+
+    fn sample_surface(&self, time: f64, u: (f64, f64)) -> Option<(HitRecord<'_>, f64)> {
+        let (alpha, beta) = uniform_barycentrics(u);
+        let p = self.Q + (alpha * self.u) + (beta * self.v);
+        let hit_record = HitRecord::on_surface(
+            p,
+            self.normal,
+            time,
+            &self.material,
+            self.id,
+            TextureCoords::new(alpha, beta),
+        );
+        Some((hit_record, 1.0 / self.area))
+    }
+
+    fn surface_pdf(&self, r: &Ray, ray_bounds: &Interval) -> f64 {
+        match self.hit(r, ray_bounds) {
+            Some(_) => 1.0 / self.area,
+            None => 0.0,
+        }
+    }
+}
+
+/// Maps a uniform sample of the unit square to barycentric coordinates that are uniformly
+/// distributed over the triangle.
+fn uniform_barycentrics((u1, u2): (f64, f64)) -> (f64, f64) {
+    let su = u1.sqrt();
+    (su * (1.0 - u2), su * u2)
+}
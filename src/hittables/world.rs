@@ -0,0 +1,45 @@
+use crate::math::{Aabb, Interval, Ray};
+
+use super::{Hit, HitRecord, Hittable, HittableList, LightBvh};
+
+/// The objects of a scene, together with the list of its light sources. Integrators that sample
+/// light sources directly use the lights, which are collected from the objects' emissive parts,
+/// so scenes don't have to declare them separately. Lights are sampled for points in the scene
+/// by their [`LightBvh`], and uniformly for paths that start at them.
+#[derive(Debug, Clone)]
+pub struct World {
+    objects: Hittable,
+    lights: HittableList,
+    light_bvh: LightBvh,
+}
+
+impl World {
+    pub fn new(objects: impl Into<Hittable>) -> Self {
+        let objects = objects.into();
+        let emitters = objects.emitters();
+        let light_bvh = LightBvh::new(emitters.clone());
+        Self {
+            objects,
+            lights: emitters.into_iter().collect(),
+            light_bvh,
+        }
+    }
+
+    pub fn lights(&self) -> &HittableList {
+        &self.lights
+    }
+
+    pub fn light_bvh(&self) -> &LightBvh {
+        &self.light_bvh
+    }
+}
+
+impl Hit for World {
+    fn hit(&self, r: &Ray, ray_bounds: &Interval) -> Option<HitRecord<'_>> {
+        self.objects.hit(r, ray_bounds)
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        self.objects.bounding_box()
+    }
+}
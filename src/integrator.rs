@@ -0,0 +1,299 @@
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    camera::{Camera, Splats},
+    color::Color,
+    film::Aovs,
+    hittables::{Hit, HitRecord, World},
+    material::{Lobe, ScatterAndEmit},
+    math::{Point3, Ray, Vec3},
+    sampler::{GenerateSamples, Sampler},
+};
+
+mod path;
+pub use path::*;
+
+mod direct;
+pub use direct::*;
+
+mod whitted;
+pub use whitted::*;
+
+mod ambient_occlusion;
+pub use ambient_occlusion::*;
+
+mod normals;
+pub use normals::*;
+
+mod bdpt;
+pub use bdpt::*;
+
+mod photon_mapping;
+pub use photon_mapping::*;
+
+mod light_tracing;
+pub use light_tracing::*;
+
+mod metropolis;
+pub use metropolis::*;
+
+/// Algorithm that computes the light arriving at the camera along a ray.
+#[derive(Debug, Clone)]
+#[enum_dispatch(Integrate)]
+pub enum Integrator {
+    PathTracer(PathTracer),
+    DirectLighting(DirectLighting),
+    Whitted(Whitted),
+    AmbientOcclusion(AmbientOcclusion),
+    Normals(Normals),
+    BidirectionalPathTracer(BidirectionalPathTracer),
+    PhotonMapper(PhotonMapper),
+    LightTracer(LightTracer),
+    MetropolisLightTransport(MetropolisLightTransport),
+}
+
+/// Kind of integrator to render with, see [`Integrator::new`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntegratorType {
+    #[default]
+    PathTracer,
+    DirectLighting,
+    Whitted,
+    /// Occlusion is tested up to `max_distance`, or a tenth of the scene's diagonal if `None`.
+    AmbientOcclusion {
+        max_distance: Option<f64>,
+    },
+    Normals,
+    BidirectionalPathTracer,
+    /// `photons_per_pass` photons are traced for every sample index and looked up within
+    /// `radius`, or 5 pixels at the distance the camera looks at if `None`. With `progressive`,
+    /// the radius shrinks from pass to pass.
+    PhotonMapping {
+        photons_per_pass: usize,
+        radius: Option<f64>,
+        progressive: bool,
+    },
+    LightTracer,
+    /// Every pass traces `bootstrap_samples` independent paths to start `chains` Markov chains
+    /// from, whose mutations are large steps with `large_step_probability`.
+    MetropolisLightTransport {
+        bootstrap_samples: usize,
+        chains: usize,
+        large_step_probability: f64,
+    },
+}
+
+impl Integrator {
+    /// Creates an integrator from the settings of a camera. Integrators ignore the settings that
+    /// don't apply to them.
+    pub fn new(
+        integrator_type: IntegratorType,
+        max_depth: i32,
+        russian_roulette_depth: Option<u32>,
+        background: Color,
+    ) -> Self {
+        let max_depth = max_depth.max(0) as u32;
+        match integrator_type {
+            IntegratorType::PathTracer => {
+                PathTracer::new(max_depth, russian_roulette_depth, background).into()
+            }
+            IntegratorType::DirectLighting => DirectLighting::new(background).into(),
+            IntegratorType::Whitted => Whitted::new(max_depth, background).into(),
+            IntegratorType::AmbientOcclusion { max_distance } => {
+                AmbientOcclusion::new(max_distance).into()
+            }
+            IntegratorType::Normals => Normals.into(),
+            IntegratorType::BidirectionalPathTracer => {
+                BidirectionalPathTracer::new(max_depth, russian_roulette_depth, background).into()
+            }
+            IntegratorType::PhotonMapping {
+                photons_per_pass,
+                radius,
+                progressive,
+            } => PhotonMapper::new(
+                max_depth,
+                russian_roulette_depth,
+                photons_per_pass,
+                radius,
+                progressive,
+                background,
+            )
+            .into(),
+            IntegratorType::LightTracer => {
+                LightTracer::new(max_depth, russian_roulette_depth, background).into()
+            }
+            IntegratorType::MetropolisLightTransport {
+                bootstrap_samples,
+                chains,
+                large_step_probability,
+            } => MetropolisLightTransport::new(
+                max_depth,
+                russian_roulette_depth,
+                bootstrap_samples,
+                chains,
+                large_step_probability,
+                background,
+            )
+            .into(),
+        }
+    }
+}
+
+/// What integrators render: the world, seen by the camera. Light that integrators find for other
+/// pixels than the one they are sampling goes to the splats.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderContext<'a> {
+    pub world: &'a World,
+    pub camera: &'a Camera,
+    pub splats: &'a Splats,
+    /// Photons traced for the current sample index, see [`Integrate::trace_photons`]
+    pub photons: Option<&'a PhotonMap>,
+}
+
+#[enum_dispatch]
+pub trait Integrate: Sync {
+    /// Returns the light arriving along the camera ray `r`. If `aovs` is given, it is filled with
+    /// the AOVs of the sample. Light splatted to other pixels is not part of the AOVs.
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color;
+
+    /// Whether the samples of all pixels share work, see [`Integrate::trace_photons`] and
+    /// [`Integrate::splat_pass`]. Cameras render such integrators one sample index at a time.
+    fn renders_by_sample_index(&self) -> bool {
+        false
+    }
+
+    /// Traces the photons that the samples with index `sample_index` of all pixels look up. They
+    /// are passed to the samples in the render context.
+    fn trace_photons(&self, _context: &RenderContext, _sample_index: usize) -> Option<PhotonMap> {
+        None
+    }
+
+    /// Splats light on behalf of the `samples` samples with index `sample_index`, one of each
+    /// pixel that is still sampled, before these are taken.
+    fn splat_pass(&self, _context: &RenderContext, _sample_index: usize, _samples: usize) {}
+}
+
+/// Ray bounds for tracing rays that leave a surface, excluding hits on the surface itself.
+fn ray_bounds() -> crate::math::Interval {
+    (0.001..=f64::INFINITY).into()
+}
+
+/// Whether nothing blocks the line between two points.
+fn unoccluded(context: &RenderContext, from: &Point3, to: &Point3, time: f64) -> bool {
+    let ray = Ray::new(*from, *to - *from, time);
+    context.world.hit(&ray, &(0.001..=0.999).into()).is_none()
+}
+
+/// AOVs of the first surface hit by camera ray `r`, without any light passes.
+fn surface_aovs(r: &Ray, hit_record: &HitRecord) -> Aovs {
+    let material = hit_record.material;
+    Aovs {
+        albedo: material.albedo(hit_record),
+        normal: hit_record.normal,
+        depth: hit_record.t * r.direction().length(),
+        position: *hit_record.p.as_vec3(),
+        material_id: material.id().value(),
+        object_id: hit_record.object_id.value(),
+        ..Aovs::default()
+    }
+}
+
+/// Adds light that reached the camera after `bounces` scattering events, the first of which was
+/// of kind `first_lobe`, to the light passes.
+fn record_light(aovs: &mut Aovs, bounces: u32, first_lobe: Option<Lobe>, light: Color) {
+    match bounces {
+        0 => aovs.emission = aovs.emission + light,
+        1 => aovs.direct = aovs.direct + light,
+        _ => aovs.indirect = aovs.indirect + light,
+    }
+    match first_lobe {
+        Some(Lobe::Diffuse) => aovs.diffuse = aovs.diffuse + light,
+        Some(Lobe::Specular) => aovs.specular = aovs.specular + light,
+        None => {}
+    }
+}
+
+/// Estimates the light scattered along `-ray_in` at `hit_record` that arrives directly from the
+/// world's lights, by sampling a direction towards one of them.
+///
+/// With `mis`, the estimate is weighted by the power heuristic, so that it can be combined with
+/// the light found by sampling the material (see [`bsdf_sample_weight`]).
+fn sample_light(
+    world: &World,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    sampler: &mut Sampler,
+    mis: bool,
+) -> Color {
+    let u = sampler.get_2d();
+    let lights = world.light_bvh();
+    let material = hit_record.material;
+    let normal = surface_normal(hit_record);
+    let Some(direction) = lights.sample_direction(&hit_record.p, normal, ray_in.time(), u) else {
+        return Color::black();
+    };
+    let ray =
+        Ray::new(hit_record.p, direction, ray_in.time()).with_wavelengths(hit_record.wavelengths);
+    let light_pdf = lights.pdf_value(&ray, normal);
+    let scattering = material.scattering(ray_in, hit_record, &direction);
+    if light_pdf <= 0.0 || scattering.is_black() {
+        return Color::black();
+    }
+
+    let Some(light_hit) = world.hit(&ray, &ray_bounds()) else {
+        return Color::black();
+    };
+    let weight = if mis {
+        power_heuristic(
+            light_pdf,
+            material.scattering_pdf(ray_in, hit_record, &direction),
+        )
+    } else {
+        1.0
+    };
+    (weight / light_pdf) * (scattering * light_hit.material.emit(&light_hit, &-direction))
+}
+
+/// MIS weight for light that was found by following `scattered`, which `ray_in` scattered into at
+/// `hit_record`, when the same light is also estimated by [`sample_light`].
+fn bsdf_sample_weight(
+    world: &World,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    scattered: &Ray,
+    lobe: Lobe,
+) -> f64 {
+    match lobe {
+        // Light sampling can't find directions of specular scattering
+        Lobe::Specular => 1.0,
+        Lobe::Diffuse => power_heuristic(
+            hit_record
+                .material
+                .scattering_pdf(ray_in, hit_record, scattered.direction()),
+            world
+                .light_bvh()
+                .pdf_value(scattered, surface_normal(hit_record)),
+        ),
+    }
+}
+
+/// Normal of the surface at `hit_record`, which lights are chosen by their importance to, or
+/// `None` inside volumes.
+fn surface_normal<'a>(hit_record: &'a HitRecord) -> Option<&'a Vec3> {
+    (!hit_record.material.is_volumetric()).then_some(&hit_record.normal)
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
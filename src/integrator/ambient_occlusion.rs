@@ -0,0 +1,64 @@
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    math::{Ray, Vec3},
+    sampler::{GenerateSamples, Sampler},
+};
+
+use super::{ray_bounds, record_light, surface_aovs, Integrate, RenderContext};
+
+/// Shades surfaces by the fraction of their hemisphere that isn't blocked by nearby geometry,
+/// weighted by the cosine to the normal. Ignores materials and lights, and puts its whole output
+/// into the emission AOV.
+#[derive(Debug, Clone)]
+pub struct AmbientOcclusion {
+    max_distance: Option<f64>,
+}
+
+impl AmbientOcclusion {
+    /// Geometry further away than `max_distance` doesn't occlude. Defaults to a tenth of the
+    /// diagonal of the scene's bounding box.
+    pub fn new(max_distance: Option<f64>) -> Self {
+        Self { max_distance }
+    }
+}
+
+impl Integrate for AmbientOcclusion {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let world = context.world;
+        let Some(hit_record) = world.hit(r, &ray_bounds()) else {
+            if let Some(aovs) = aovs {
+                *aovs = Aovs::missed(Color::black());
+            }
+            return Color::black();
+        };
+
+        let max_distance = self.max_distance.unwrap_or_else(|| {
+            let bbox = world.bounding_box();
+            0.1 * Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length()
+        });
+        let direction = Vec3::cosine_weighted_from_sample(&hit_record.normal, sampler.get_2d());
+        let occlusion_ray = Ray::new(hit_record.p, direction, r.time());
+        let occluded = world
+            .hit(&occlusion_ray, &(0.001..=max_distance).into())
+            .is_some();
+        let color = if occluded {
+            Color::black()
+        } else {
+            r.wavelengths().illuminant(Color::white())
+        };
+
+        if let Some(aovs) = aovs {
+            *aovs = surface_aovs(r, &hit_record);
+            record_light(aovs, 0, None, color);
+        }
+        color
+    }
+}
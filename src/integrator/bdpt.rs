@@ -0,0 +1,584 @@
+use std::f64::consts::PI;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    film::Aovs,
+    hittables::{Hit, HitRecord},
+    material::{Lobe, ScatterAndEmit},
+    math::{dot, Point3, Ray, Vec3},
+    sampler::{GenerateSamples, Sampler},
+};
+
+use super::{ray_bounds, record_light, surface_aovs, unoccluded, Integrate, RenderContext};
+
+/// Bidirectional path tracer (Veach, 1997). For every camera sample, it traces a path from the
+/// camera and one from a point on a light source, and connects every vertex of the one with
+/// every vertex of the other. Each connection is a different strategy for sampling light paths,
+/// and multiple importance sampling with the power heuristic weights every strategy by how
+/// likely it is to find the path compared to the others.
+///
+/// This finds paths that the path tracer rarely samples, like caustics: light focused by
+/// dielectrics or metals onto diffuse surfaces. Paths that connect directly to the camera can
+/// end up in any pixel and are splatted to the film.
+#[derive(Debug, Clone)]
+pub struct BidirectionalPathTracer {
+    max_depth: u32,
+    russian_roulette_depth: Option<u32>,
+    background: Color,
+}
+
+impl BidirectionalPathTracer {
+    /// Like [`super::PathTracer::new`]: light paths have at most `max_depth` bounces, and both
+    /// subpaths are terminated by Russian roulette after `russian_roulette_depth` bounces.
+    pub fn new(max_depth: u32, russian_roulette_depth: Option<u32>, background: Color) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth,
+            background,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Camera,
+    /// Point sampled on a light source, which starts a light subpath. Its light is emitted
+    /// towards the next vertex, like other vertices scatter it.
+    Light,
+    Surface,
+}
+
+/// Vertex of a camera or light subpath.
+#[derive(Debug, Clone)]
+struct Vertex<'a> {
+    kind: VertexKind,
+    p: Point3,
+    /// Outward normal of lights, and the normal facing `ray_in` of surfaces
+    normal: Vec3,
+    hit_record: Option<HitRecord<'a>>,
+    /// Ray along which the subpath arrived at a surface
+    ray_in: Option<Ray>,
+    /// Product of the throughput of the subpath up to this vertex and the inverse of its density
+    beta: Color,
+    /// Kind of the scattering at the vertex
+    lobe: Option<Lobe>,
+    /// Whether the vertex scatters into discrete directions, so it can't be connected to
+    delta: bool,
+    /// Density, with respect to area, of sampling the vertex when tracing the subpath
+    pdf_fwd: f64,
+    /// Density, with respect to area, of sampling the vertex when tracing the subpath in the
+    /// opposite direction
+    pdf_rev: f64,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(p: Point3, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            p,
+            normal: Vec3::zero(),
+            hit_record: None,
+            ray_in: None,
+            beta,
+            lobe: None,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(hit_record: HitRecord<'a>, beta: Color, pdf: f64) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            p: hit_record.p,
+            normal: hit_record.normal,
+            hit_record: Some(hit_record),
+            ray_in: None,
+            beta,
+            lobe: None,
+            delta: false,
+            pdf_fwd: pdf,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(hit_record: HitRecord<'a>, ray_in: &Ray, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            p: hit_record.p,
+            normal: hit_record.normal,
+            hit_record: Some(hit_record),
+            ray_in: Some(ray_in.clone()),
+            beta,
+            lobe: None,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    /// Whether the density of the vertex with respect to area includes the cosine at the vertex.
+    /// Points in participating media and on the lens have no surface.
+    fn is_on_surface(&self) -> bool {
+        match (self.kind, &self.hit_record) {
+            (VertexKind::Camera, _) => false,
+            (_, Some(hit_record)) => !hit_record.material.is_volumetric(),
+            (_, None) => false,
+        }
+    }
+
+    /// Whether the subpath only carries the hero wavelength, see
+    /// [`crate::color::Wavelengths::is_hero_only`].
+    fn is_hero_only(&self) -> bool {
+        self.hit_record
+            .as_ref()
+            .is_some_and(|hit_record| hit_record.wavelengths.is_hero_only())
+    }
+
+    fn is_connectible(&self) -> bool {
+        !self.delta
+    }
+
+    /// Light emitted at a surface vertex back along the ray the subpath arrived on.
+    fn emit(&self) -> Color {
+        match (&self.hit_record, &self.ray_in) {
+            (Some(hit_record), Some(ray_in)) => {
+                hit_record.material.emit(hit_record, &-*ray_in.direction())
+            }
+            _ => Color::black(),
+        }
+    }
+
+    /// Light scattered at the vertex towards `next`, including the cosine at the vertex.
+    fn f_cos(&self, next: &Vertex) -> Color {
+        self.f_cos_towards(&(next.p - self.p))
+    }
+
+    fn f_cos_towards(&self, direction: &Vec3) -> Color {
+        match (self.kind, &self.hit_record, &self.ray_in) {
+            (VertexKind::Light, Some(hit_record), _) => {
+                let cos_theta = dot(&self.normal, &direction.normalized()).abs();
+                cos_theta * hit_record.material.emit(hit_record, direction)
+            }
+            (VertexKind::Surface, Some(hit_record), Some(ray_in)) => hit_record
+                .material
+                .scattering(ray_in, hit_record, direction),
+            _ => Color::black(),
+        }
+    }
+
+    /// Converts a density with respect to solid angle at this vertex into a density with respect
+    /// to area at `next`.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = w.length_squared();
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / distance_squared;
+        if next.is_on_surface() {
+            pdf *= dot(&next.normal, &w).abs() / distance_squared.sqrt();
+        }
+        pdf
+    }
+
+    /// Density, with respect to area at `next`, of sampling `next` from this vertex, when the
+    /// subpath arrived from `prev`.
+    fn pdf(&self, camera: &Camera, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = next.p - self.p;
+        let pdf = match self.kind {
+            VertexKind::Light => return self.pdf_light(next),
+            VertexKind::Camera => camera.importance_pdf(&direction).1,
+            VertexKind::Surface => {
+                let (Some(hit_record), Some(prev)) = (&self.hit_record, prev) else {
+                    return 0.0;
+                };
+                let ray_in = Ray::new(prev.p, self.p - prev.p, 0.0);
+                hit_record
+                    .material
+                    .scattering_pdf(&ray_in, hit_record, &direction)
+            }
+        };
+        self.convert_density(pdf, next)
+    }
+
+    /// Density, with respect to area at `next`, of emitting light from this point of a light
+    /// source towards `next`. Directions are sampled on both sides of lights.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.p - self.p).normalized();
+        let pdf_direction = dot(&self.normal, &direction).abs() / (2.0 * PI);
+        self.convert_density(pdf_direction, next)
+    }
+
+    /// Density, with respect to area, of choosing this point on a light source to start a light
+    /// subpath. `prev` is the vertex from which the point was found.
+    fn pdf_light_origin(&self, context: &RenderContext, prev: &Vertex) -> f64 {
+        let time = self.ray_in.as_ref().map_or(0.0, Ray::time);
+        let ray = Ray::new(prev.p, self.p - prev.p, time);
+        context
+            .world
+            .lights()
+            .surface_pdf(&ray, &(0.999..=1.001).into())
+    }
+}
+
+impl BidirectionalPathTracer {
+    /// Traces the subpath starting at `r` and appends its vertices to `path`, until it has
+    /// `max_vertices` vertices. `pdf` is the density, with respect to solid angle, of `r`.
+    /// Returns the throughput of the subpath if it left the scene.
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk<'a>(
+        &self,
+        context: &RenderContext<'a>,
+        r: &Ray,
+        sampler: &mut Sampler,
+        mut beta: Color,
+        pdf: f64,
+        max_vertices: usize,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Option<Color> {
+        let mut ray = r.clone();
+        let mut pdf_fwd = pdf;
+        let mut bounces = 0;
+        while path.len() < max_vertices {
+            let Some(hit_record) = context.world.hit(&ray, &ray_bounds()) else {
+                return Some(beta);
+            };
+            let prev = path.last().unwrap();
+            let mut vertex = Vertex::surface(hit_record, &ray, beta);
+            vertex.pdf_fwd = prev.convert_density(pdf_fwd, &vertex);
+            path.push(vertex);
+            if path.len() >= max_vertices {
+                break;
+            }
+
+            let vertex = path.last().unwrap();
+            let hit_record = vertex.hit_record.as_ref().unwrap();
+            let material = hit_record.material;
+            let scattered = material.scatter(&ray, hit_record, sampler)?;
+            let direction = *scattered.ray.direction();
+            let pdf_rev = match scattered.lobe {
+                Lobe::Specular => {
+                    pdf_fwd = 0.0;
+                    0.0
+                }
+                Lobe::Diffuse => {
+                    pdf_fwd = material.scattering_pdf(&ray, hit_record, &direction);
+                    // Density of scattering back towards the previous vertex, for light
+                    // arriving along the scattered ray
+                    let reversed = Ray::new(vertex.p + direction, -direction, ray.time());
+                    material.scattering_pdf(&reversed, hit_record, &-*ray.direction())
+                }
+            };
+            beta = beta * scattered.attenuation;
+
+            let n = path.len();
+            let (prev, vertex) = path.split_at_mut(n - 1);
+            let (prev, vertex) = (prev.last_mut().unwrap(), &mut vertex[0]);
+            vertex.delta = scattered.lobe == Lobe::Specular;
+            vertex.lobe = Some(scattered.lobe);
+            prev.pdf_rev = vertex.convert_density(pdf_rev, prev);
+            ray = scattered.ray;
+
+            bounces += 1;
+            if self
+                .russian_roulette_depth
+                .is_some_and(|min_depth| bounces >= min_depth)
+            {
+                let [r, g, b] = beta.components();
+                let survival_probability = r.max(g).max(b).min(1.0);
+                if sampler.get_1d() >= survival_probability {
+                    break;
+                }
+                beta = (1.0 / survival_probability) * beta;
+            }
+        }
+        None
+    }
+
+    /// Traces a subpath from the camera along `r`. Returns the subpath, and its throughput if it
+    /// left the scene.
+    fn camera_subpath<'a>(
+        &self,
+        context: &RenderContext<'a>,
+        r: &Ray,
+        sampler: &mut Sampler,
+    ) -> (Vec<Vertex<'a>>, Option<Color>) {
+        let mut path = vec![Vertex::camera(*r.origin(), Color::white())];
+        let (_, pdf_direction) = context.camera.importance_pdf(r.direction());
+        let escaped = self.random_walk(
+            context,
+            r,
+            sampler,
+            Color::white(),
+            pdf_direction,
+            self.max_depth as usize + 1,
+            &mut path,
+        );
+        (path, escaped)
+    }
+
+    /// Traces a subpath from a point sampled on one of the lights, at the time and wavelengths
+    /// of camera ray `r`.
+    fn light_subpath<'a>(
+        &self,
+        context: &RenderContext<'a>,
+        r: &Ray,
+        sampler: &mut Sampler,
+    ) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        let (time, wavelengths) = (r.time(), r.wavelengths());
+        let (u_position, (u1, u2)) = (sampler.get_2d(), sampler.get_2d());
+        let Some((mut hit_record, pdf_position)) =
+            context.world.lights().sample_surface(time, u_position)
+        else {
+            return path;
+        };
+        hit_record.wavelengths = wavelengths;
+        if pdf_position <= 0.0 {
+            return path;
+        }
+
+        let light = Vertex::light(
+            hit_record,
+            (1.0 / pdf_position) * Color::white(),
+            pdf_position,
+        );
+
+        // Lights can emit to both sides, so pick a side and sample a cosine distributed
+        // direction
+        let (side, u1) = if u1 < 0.5 {
+            (light.normal, 2.0 * u1)
+        } else {
+            (-light.normal, 2.0 * u1 - 1.0)
+        };
+        let direction = Vec3::cosine_weighted_from_sample(&side, (u1, u2));
+        let pdf_direction = dot(&light.normal, &direction).abs() / (2.0 * PI);
+        let beta = (1.0 / pdf_direction) * (light.f_cos_towards(&direction) * light.beta);
+        let ray = Ray::new(light.p, direction, time).with_wavelengths(wavelengths);
+        path.push(light);
+        if beta.is_black() || pdf_direction <= 0.0 {
+            return path;
+        }
+
+        self.random_walk(
+            context,
+            &ray,
+            sampler,
+            beta,
+            pdf_direction,
+            self.max_depth as usize,
+            &mut path,
+        );
+        path
+    }
+
+    /// Light of the path made of the first `s` vertices of the light subpath and the first `t`
+    /// vertices of the camera subpath, weighted by MIS. Paths that connect to the camera are
+    /// returned with their raster position, at which they have to be splatted.
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        context: &RenderContext,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+        sampler: &mut Sampler,
+    ) -> Option<(Color, Option<(f64, f64)>)> {
+        let mut sampled_camera = None;
+        let mut raster = None;
+        let light = if s == 0 {
+            // The camera subpath hit a light
+            let pt = &camera_path[t - 1];
+            if pt.kind != VertexKind::Surface {
+                return None;
+            }
+            pt.beta * pt.emit()
+        } else if t == 1 {
+            // Connect the light subpath to a point on the lens
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() {
+                return None;
+            }
+            let sample = context.camera.sample_importance(&qs.p, sampler.get_2d())?;
+            if sample.importance <= 0.0 || sample.pdf <= 0.0 {
+                return None;
+            }
+            let camera = Vertex::camera(
+                sample.lens_point,
+                (sample.importance / sample.pdf) * Color::white(),
+            );
+            let light = qs.beta * qs.f_cos(&camera) * camera.beta;
+            if light.is_black() || !unoccluded(context, &qs.p, &camera.p, time) {
+                return None;
+            }
+            raster = Some(sample.raster);
+            sampled_camera = Some(camera);
+            light
+        } else {
+            let (qs, pt) = (&light_path[s - 1], &camera_path[t - 1]);
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return None;
+            }
+            let distance_squared = (pt.p - qs.p).length_squared();
+            let mut light =
+                (1.0 / distance_squared) * (qs.beta * qs.f_cos(pt) * pt.f_cos(qs) * pt.beta);
+            if qs.is_hero_only() && pt.is_hero_only() {
+                // Both subpaths moved the light of the secondary wavelengths to the hero
+                light = (1.0 / 3.0) * light;
+            }
+            if light.is_black() || !unoccluded(context, &qs.p, &pt.p, time) {
+                return None;
+            }
+            light
+        };
+        if light.is_black() {
+            return None;
+        }
+
+        let weight = mis_weight(
+            context,
+            light_path,
+            camera_path,
+            sampled_camera.as_ref(),
+            s,
+            t,
+        );
+        Some((weight * light, raster))
+    }
+}
+
+impl Integrate for BidirectionalPathTracer {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        mut aovs: Option<&mut Aovs>,
+    ) -> Color {
+        if self.max_depth == 0 {
+            return Color::black();
+        }
+
+        let (camera_path, escaped) = self.camera_subpath(context, r, sampler);
+        if let Some(aovs) = aovs.as_deref_mut() {
+            *aovs = match camera_path.get(1).and_then(|v| v.hit_record.as_ref()) {
+                Some(hit_record) => surface_aovs(r, hit_record),
+                None => Aovs::missed(self.background),
+            };
+        }
+        // Kind of the first bounce of paths whose camera subpath has t vertices
+        let first_lobe = |t: usize, bounces: usize| match (bounces, t) {
+            (0, _) => None,
+            (_, 2) => Some(Lobe::Diffuse),
+            _ => camera_path[1].lobe,
+        };
+
+        // The background can't be sampled from the lights, so only the camera subpath finds it
+        let mut color = Color::black();
+        if let Some(beta) = escaped {
+            let light = beta * r.wavelengths().illuminant(self.background);
+            color = color + light;
+            if let Some(aovs) = aovs.as_deref_mut() {
+                let bounces = camera_path.len() - 1;
+                let first_lobe = camera_path.get(1).and_then(|v| v.lobe);
+                record_light(aovs, bounces as u32, first_lobe, light);
+            }
+        }
+
+        let light_path = self.light_subpath(context, r, sampler);
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                if s + t < 2 || s + t - 1 > self.max_depth as usize {
+                    continue;
+                }
+                let Some((light, raster)) =
+                    self.connect(context, &light_path, &camera_path, s, t, r.time(), sampler)
+                else {
+                    continue;
+                };
+                match raster {
+                    Some(raster) => context.splats.add(raster, r.wavelengths().to_rgb(light)),
+                    None => {
+                        color = color + light;
+                        if let Some(aovs) = aovs.as_deref_mut() {
+                            let bounces = s + t - 2;
+                            record_light(aovs, bounces as u32, first_lobe(t, bounces), light);
+                        }
+                    }
+                }
+            }
+        }
+        color
+    }
+}
+
+/// Weight of the strategy with `s` light and `t` camera vertices by the power heuristic, over all
+/// strategies that could have sampled the same path (Veach, 1997, section 10.2). The densities of
+/// the other strategies follow from the ratios of the reverse and forward densities of the
+/// vertices. `sampled_camera` is the lens point the light subpath was connected to if `t` is 1.
+fn mis_weight(
+    context: &RenderContext,
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    sampled_camera: Option<&Vertex>,
+    s: usize,
+    t: usize,
+) -> f64 {
+    // Forward and reverse densities and whether the vertex is specular, for the vertices of the
+    // path, of which the ones at the connection change
+    let densities = |v: &Vertex| (v.pdf_fwd, v.pdf_rev, v.delta);
+    let mut camera_densities = camera_path[..t].iter().map(densities).collect::<Vec<_>>();
+    let mut light_densities = light_path[..s].iter().map(densities).collect::<Vec<_>>();
+
+    let camera = context.camera;
+    let pt = sampled_camera.unwrap_or(&camera_path[t - 1]);
+    let pt_minus = (t > 1).then(|| &camera_path[t - 2]);
+    let qs = (s > 0).then(|| &light_path[s - 1]);
+    let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+
+    // The vertices at the connection are connected deterministically, not sampled by scattering
+    camera_densities[t - 1].2 = false;
+    camera_densities[t - 1].1 = match qs {
+        Some(qs) => qs.pdf(camera, qs_minus, pt),
+        None => pt.pdf_light_origin(context, pt_minus.unwrap()),
+    };
+    if let Some(pt_minus) = pt_minus {
+        camera_densities[t - 2].1 = match qs {
+            Some(qs) => pt.pdf(camera, Some(qs), pt_minus),
+            None => pt.pdf_light(pt_minus),
+        };
+    }
+    if let Some(qs) = qs {
+        light_densities[s - 1].2 = false;
+        light_densities[s - 1].1 = pt.pdf(camera, pt_minus, qs);
+        if let Some(qs_minus) = qs_minus {
+            light_densities[s - 2].1 = qs.pdf(camera, Some(pt), qs_minus);
+        }
+    }
+
+    // Specular vertices have density 0, which cancels out as long as they aren't connected to
+    let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+    let mut sum = 0.0;
+    let mut ratio = 1.0;
+    for i in (1..t).rev() {
+        let (pdf_fwd, pdf_rev, delta) = camera_densities[i];
+        ratio *= remap(pdf_rev) / remap(pdf_fwd);
+        if !delta && !camera_densities[i - 1].2 {
+            sum += ratio * ratio;
+        }
+    }
+    ratio = 1.0;
+    for i in (0..s).rev() {
+        let (pdf_fwd, pdf_rev, delta) = light_densities[i];
+        ratio *= remap(pdf_rev) / remap(pdf_fwd);
+        let prev_delta = i > 0 && light_densities[i - 1].2;
+        if !delta && !prev_delta {
+            sum += ratio * ratio;
+        }
+    }
+    1.0 / (1.0 + sum)
+}
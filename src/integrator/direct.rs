@@ -0,0 +1,80 @@
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    material::{Lobe, ScatterAndEmit},
+    math::Ray,
+    sampler::Sampler,
+};
+
+use super::{
+    bsdf_sample_weight, ray_bounds, record_light, sample_light, surface_aovs, Integrate,
+    RenderContext,
+};
+
+/// Computes only the light that reaches the camera after at most one bounce.
+///
+/// At the first surface hit, the light arriving directly from light sources is estimated both by
+/// sampling the lights and by sampling the material, and the two estimates are combined with
+/// multiple importance sampling. This finds small lights much faster than path tracing.
+#[derive(Debug, Clone)]
+pub struct DirectLighting {
+    background: Color,
+}
+
+impl DirectLighting {
+    pub fn new(background: Color) -> Self {
+        Self { background }
+    }
+}
+
+impl Integrate for DirectLighting {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let world = context.world;
+        let background = r.wavelengths().illuminant(self.background);
+        let Some(hit_record) = world.hit(r, &ray_bounds()) else {
+            if let Some(aovs) = aovs {
+                *aovs = Aovs::missed(self.background);
+                record_light(aovs, 0, None, background);
+            }
+            return background;
+        };
+        let material = hit_record.material;
+        let emitted = material.emit(&hit_record, &-*r.direction());
+
+        let mut reflected = Color::black();
+        let mut lobe = None;
+        if let Some(scattered) = material.scatter(r, &hit_record, sampler) {
+            lobe = Some(scattered.lobe);
+            if scattered.lobe == Lobe::Diffuse {
+                reflected = sample_light(world, r, &hit_record, sampler, true);
+            }
+
+            let incoming = match world.hit(&scattered.ray, &ray_bounds()) {
+                Some(next_hit) => {
+                    let weight =
+                        bsdf_sample_weight(world, r, &hit_record, &scattered.ray, scattered.lobe);
+                    weight
+                        * next_hit
+                            .material
+                            .emit(&next_hit, &-*scattered.ray.direction())
+                }
+                None => background,
+            };
+            reflected = reflected + scattered.attenuation * incoming;
+        }
+
+        if let Some(aovs) = aovs {
+            *aovs = surface_aovs(r, &hit_record);
+            record_light(aovs, 0, None, emitted);
+            record_light(aovs, 1, lobe, reflected);
+        }
+        emitted + reflected
+    }
+}
@@ -0,0 +1,163 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    material::{Lobe, ScatterAndEmit},
+    math::{dot, Point3, Ray, Vec3},
+    sampler::{GenerateSamples, Sampler},
+};
+
+use super::{ray_bounds, record_light, surface_aovs, unoccluded, Integrate, RenderContext};
+
+/// Light tracer, also called particle tracer: for every camera sample, it traces a path from a
+/// point sampled on one of the lights, and connects every vertex of the path to a point on the
+/// lens. The connections can end up in any pixel and are splatted to the film.
+///
+/// It's the adjoint of the path tracer: caustics on diffuse surfaces are easy to find, but light
+/// reaching the camera through specular surfaces can't be found at all. Since paths only start at
+/// lights, the background is only seen directly.
+#[derive(Debug, Clone)]
+pub struct LightTracer {
+    max_depth: u32,
+    russian_roulette_depth: Option<u32>,
+    background: Color,
+}
+
+impl LightTracer {
+    /// Like [`super::PathTracer::new`]: light paths have at most `max_depth` bounces and are
+    /// terminated by Russian roulette after `russian_roulette_depth` bounces.
+    pub fn new(max_depth: u32, russian_roulette_depth: Option<u32>, background: Color) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth,
+            background,
+        }
+    }
+
+    /// Traces a path from a point sampled on one of the lights, at the time and wavelengths of
+    /// camera ray `r`, and splats the light that every vertex sends to the camera.
+    fn trace_light_path(&self, context: &RenderContext, r: &Ray, sampler: &mut Sampler) {
+        let (time, wavelengths) = (r.time(), r.wavelengths());
+        let (u_position, (u1, u2)) = (sampler.get_2d(), sampler.get_2d());
+        let Some((mut light, pdf_position)) =
+            context.world.lights().sample_surface(time, u_position)
+        else {
+            return;
+        };
+        light.wavelengths = wavelengths;
+        if pdf_position <= 0.0 {
+            return;
+        }
+
+        let emitted = |direction: &Vec3| light.material.emit(&light, direction);
+        connect_to_camera(context, &light.p, r, sampler, |direction| {
+            let cos_theta = dot(&light.normal, &direction.normalized()).abs();
+            (cos_theta / pdf_position) * emitted(direction)
+        });
+        if self.max_depth <= 1 {
+            return;
+        }
+
+        // Directions are sampled cosine distributed on both sides, so the cosine cancels out
+        // with their density
+        let (side, u1) = if u1 < 0.5 {
+            (light.normal, 2.0 * u1)
+        } else {
+            (-light.normal, 2.0 * u1 - 1.0)
+        };
+        let direction = Vec3::cosine_weighted_from_sample(&side, (u1, u2));
+        let mut beta = (2.0 * PI / pdf_position) * emitted(&direction);
+        if beta.is_black() {
+            return;
+        }
+        let mut ray = Ray::new(light.p, direction, time).with_wavelengths(wavelengths);
+
+        for bounces in 1..self.max_depth {
+            let Some(hit_record) = context.world.hit(&ray, &ray_bounds()) else {
+                return;
+            };
+            let material = hit_record.material;
+            let Some(scattered) = material.scatter(&ray, &hit_record, sampler) else {
+                return;
+            };
+            if scattered.lobe == Lobe::Diffuse {
+                connect_to_camera(context, &hit_record.p, &ray, sampler, |direction| {
+                    beta * material.scattering(&ray, &hit_record, direction)
+                });
+            }
+
+            beta = beta * scattered.attenuation;
+            ray = scattered.ray;
+            if self
+                .russian_roulette_depth
+                .is_some_and(|min_depth| bounces >= min_depth)
+            {
+                let [r, g, b] = beta.components();
+                let survival_probability = r.max(g).max(b).min(1.0);
+                if sampler.get_1d() >= survival_probability {
+                    return;
+                }
+                beta = (1.0 / survival_probability) * beta;
+            }
+        }
+    }
+}
+
+impl Integrate for LightTracer {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color {
+        if self.max_depth == 0 {
+            return Color::black();
+        }
+
+        // All light leaving surfaces towards the camera is splatted, so the camera ray only
+        // finds the background
+        let hit_record = context.world.hit(r, &ray_bounds());
+        let color = match hit_record {
+            Some(_) => Color::black(),
+            None => r.wavelengths().illuminant(self.background),
+        };
+        if let Some(aovs) = aovs {
+            *aovs = match &hit_record {
+                Some(hit_record) => surface_aovs(r, hit_record),
+                None => Aovs::missed(self.background),
+            };
+            record_light(aovs, 0, None, color);
+        }
+
+        self.trace_light_path(context, r, sampler);
+        color
+    }
+}
+
+/// Splats the light that leaves `p` towards a point sampled on the lens, if it isn't occluded.
+/// `light` returns the light leaving `p` in a direction, divided by the density of the path up
+/// to `p`. The path has the time and wavelengths of `ray`.
+fn connect_to_camera(
+    context: &RenderContext,
+    p: &Point3,
+    ray: &Ray,
+    sampler: &mut Sampler,
+    light: impl FnOnce(&Vec3) -> Color,
+) {
+    let Some(sample) = context.camera.sample_importance(p, sampler.get_2d()) else {
+        return;
+    };
+    if sample.importance <= 0.0 || sample.pdf <= 0.0 {
+        return;
+    }
+    let light = (sample.importance / sample.pdf) * light(&(sample.lens_point - *p));
+    if light.is_black() || !unoccluded(context, p, &sample.lens_point, ray.time()) {
+        return;
+    }
+    context
+        .splats
+        .add(sample.raster, ray.wavelengths().to_rgb(light));
+}
@@ -0,0 +1,201 @@
+use rand::Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    math::Ray,
+    sampler::{hash, GenerateSamples, MetropolisSampler, Pcg32, Sampler},
+};
+
+use super::{ray_bounds, surface_aovs, Integrate, PathTracer, RenderContext};
+
+/// Metropolis light transport in primary sample space (Kelemen et al., 2002), on top of the path
+/// tracer. Markov chains mutate the random numbers that the path tracer consumes, including the
+/// ones that place the camera ray on the image, so that they visit paths in proportion to their
+/// luminance. Once a chain found a path that carries a lot of light, e.g. through a gap into a
+/// room, small steps explore the similar paths around it, which the path tracer on its own would
+/// rarely find. Large steps start over at random, so that no part of the image is left out.
+///
+/// All light is splatted by the chains. Every sample index is a pass of one mutation per pixel,
+/// whose chains start at paths of a bootstrap phase of independent paths. The chains only know
+/// how much brighter paths are than others, so the splats are normalized by the mean luminance
+/// of the bootstrap paths.
+#[derive(Debug, Clone)]
+pub struct MetropolisLightTransport {
+    path_tracer: PathTracer,
+    bootstrap_samples: usize,
+    chains: usize,
+    large_step_probability: f64,
+    background: Color,
+}
+
+impl MetropolisLightTransport {
+    /// Standard deviation of the small steps, in primary sample space
+    const SIGMA: f64 = 0.01;
+
+    /// `max_depth`, `russian_roulette_depth` and `background` are passed on to the path tracer.
+    /// Every pass traces `bootstrap_samples` independent paths, and distributes its mutations
+    /// over `chains` chains. Mutations are large steps with `large_step_probability`.
+    pub fn new(
+        max_depth: u32,
+        russian_roulette_depth: Option<u32>,
+        bootstrap_samples: usize,
+        chains: usize,
+        large_step_probability: f64,
+        background: Color,
+    ) -> Self {
+        Self {
+            path_tracer: PathTracer::new(max_depth, russian_roulette_depth, background),
+            bootstrap_samples,
+            chains,
+            large_step_probability,
+            background,
+        }
+    }
+
+    /// Light of the path that the current state of the chain describes, and the raster
+    /// coordinates at which it reaches the camera. Reconstruction filters don't apply to the
+    /// splats, so pixels are box filtered.
+    fn path(&self, context: &RenderContext, sampler: &mut Sampler) -> (Color, (f64, f64)) {
+        sampler.start_pixel_sample((0, 0), 0);
+        let (width, height) = context.camera.image_size();
+        let (u, v) = sampler.get_2d();
+        let raster = (u * width as f64, v * height as f64);
+        let ray = context.camera.ray_through(raster, sampler);
+        let light = self.path_tracer.radiance(&ray, context, sampler, None);
+        (ray.wavelengths().to_rgb(light), raster)
+    }
+
+    /// Runs chain `index` of the pass with the given seed for `mutations` mutations, starting
+    /// from a bootstrap path chosen with probability proportional to its luminance, given by
+    /// `cdf`.
+    fn run_chain(
+        &self,
+        context: &RenderContext,
+        seed: u64,
+        index: usize,
+        mutations: usize,
+        cdf: &[f64],
+        normalization: f64,
+    ) {
+        let mut rng = Pcg32::new(hash(&[seed, index as u64]), 1);
+        let total = cdf.last().copied().unwrap_or(0.0);
+        let u = rng.gen::<f64>() * total;
+        let start = cdf.partition_point(|c| *c <= u).min(cdf.len() - 1);
+
+        // Regenerate the bootstrap path, and continue with random numbers of this chain, since
+        // several chains may start from the same path
+        let mut sampler = self.bootstrap_sampler(seed, start);
+        let (mut light, mut raster) = self.path(context, &mut sampler);
+        let mut contribution = importance(&light);
+        chain(&mut sampler).reseed(hash(&[seed, index as u64, 1]));
+
+        for _ in 0..mutations {
+            chain(&mut sampler).start_iteration();
+            let (proposed_light, proposed_raster) = self.path(context, &mut sampler);
+            let proposed_contribution = importance(&proposed_light);
+            let accept = if contribution > 0.0 {
+                (proposed_contribution / contribution).min(1.0)
+            } else {
+                1.0
+            };
+
+            // Splatting both states, weighted by the probability of moving to the other, has the
+            // same expected value as splatting the next state, with less noise
+            if accept > 0.0 {
+                let weight = normalization * accept / proposed_contribution;
+                context.splats.add(proposed_raster, weight * proposed_light);
+            }
+            if accept < 1.0 {
+                let weight = normalization * (1.0 - accept) / contribution;
+                context.splats.add(raster, weight * light);
+            }
+
+            if rng.gen::<f64>() < accept {
+                chain(&mut sampler).accept();
+                (light, raster, contribution) =
+                    (proposed_light, proposed_raster, proposed_contribution);
+            } else {
+                chain(&mut sampler).reject();
+            }
+        }
+    }
+
+    fn bootstrap_sampler(&self, seed: u64, index: usize) -> Sampler {
+        MetropolisSampler::new(
+            hash(&[seed, index as u64, 0]),
+            Self::SIGMA,
+            self.large_step_probability,
+        )
+        .into()
+    }
+}
+
+impl Integrate for MetropolisLightTransport {
+    /// All light is splatted by [`Integrate::splat_pass`], so the samples of the pixels only
+    /// provide the AOVs.
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        _sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color {
+        if let Some(aovs) = aovs {
+            *aovs = match context.world.hit(r, &ray_bounds()) {
+                Some(hit_record) => surface_aovs(r, &hit_record),
+                None => Aovs::missed(self.background),
+            };
+        }
+        Color::black()
+    }
+
+    fn renders_by_sample_index(&self) -> bool {
+        true
+    }
+
+    fn splat_pass(&self, context: &RenderContext, sample_index: usize, samples: usize) {
+        let seed = hash(&[context.camera.seed(), sample_index as u64]);
+        let luminances = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|index| {
+                let mut sampler = self.bootstrap_sampler(seed, index);
+                importance(&self.path(context, &mut sampler).0)
+            })
+            .collect::<Vec<_>>();
+        let cdf = luminances
+            .iter()
+            .scan(0.0, |sum, luminance| {
+                *sum += luminance;
+                Some(*sum)
+            })
+            .collect::<Vec<_>>();
+        let total = cdf.last().copied().unwrap_or(0.0);
+        if total <= 0.0 || samples == 0 {
+            return;
+        }
+        let normalization = total / self.bootstrap_samples as f64;
+
+        let chains = self.chains.clamp(1, samples);
+        (0..chains).into_par_iter().for_each(|index| {
+            let mutations = samples / chains + usize::from(index < samples % chains);
+            self.run_chain(context, seed, index, mutations, &cdf, normalization);
+        });
+    }
+}
+
+/// Function that the chains sample paths proportionally to. Light at single wavelengths of
+/// spectral mode can be out of the RGB gamut, with negative luminance.
+fn importance(light: &Color) -> f64 {
+    light.luminance().abs()
+}
+
+/// The sampler of a chain, which the path tracer consumes as a [`Sampler`].
+fn chain(sampler: &mut Sampler) -> &mut MetropolisSampler {
+    match sampler {
+        Sampler::Metropolis(sampler) => sampler,
+        _ => unreachable!("chains are sampled by metropolis samplers"),
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{color::Color, film::Aovs, hittables::Hit, math::Ray, sampler::Sampler};
+
+use super::{ray_bounds, record_light, surface_aovs, Integrate, RenderContext};
+
+/// Debug view that shows the normals of the surfaces hit by camera rays, mapped from [-1,1] to
+/// [0,1]. Puts its whole output into the emission AOV.
+#[derive(Debug, Clone)]
+pub struct Normals;
+
+impl Integrate for Normals {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        _sampler: &mut Sampler,
+        aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let Some(hit_record) = context.world.hit(r, &ray_bounds()) else {
+            if let Some(aovs) = aovs {
+                *aovs = Aovs::missed(Color::black());
+            }
+            return Color::black();
+        };
+
+        let n = hit_record.normal;
+        // Shown as if the surface emitted the color, so that it stays the same in spectral mode
+        let color = r.wavelengths().illuminant(Color::new(
+            0.5 * (n.x + 1.0),
+            0.5 * (n.y + 1.0),
+            0.5 * (n.z + 1.0),
+        ));
+        if let Some(aovs) = aovs {
+            *aovs = surface_aovs(r, &hit_record);
+            record_light(aovs, 0, None, color);
+        }
+        color
+    }
+}
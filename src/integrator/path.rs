@@ -0,0 +1,93 @@
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    material::ScatterAndEmit,
+    math::Ray,
+    sampler::{GenerateSamples, Sampler},
+};
+
+use super::{ray_bounds, record_light, surface_aovs, Integrate, RenderContext};
+
+/// Unidirectional path tracer that follows the scattered rays of the materials, and only finds
+/// light when a path happens to hit a light source.
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    max_depth: u32,
+    russian_roulette_depth: Option<u32>,
+    background: Color,
+}
+
+impl PathTracer {
+    /// Paths are terminated by Russian roulette after `russian_roulette_depth` bounces, if
+    /// given, and always after `max_depth` bounces.
+    pub fn new(max_depth: u32, russian_roulette_depth: Option<u32>, background: Color) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth,
+            background,
+        }
+    }
+}
+
+impl Integrate for PathTracer {
+    /// The path ends when it leaves the scene, is absorbed, reaches `max_depth` bounces, or is
+    /// terminated by Russian roulette. Surviving paths have their throughput divided by the
+    /// survival probability, so the termination doesn't darken the image.
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        mut aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let world = context.world;
+        let mut color = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = r.clone();
+        let mut first_lobe = None;
+
+        for depth in 0..self.max_depth {
+            let hit_record = world.hit(&ray, &ray_bounds());
+            let light = match &hit_record {
+                Some(hit_record) => hit_record.material.emit(hit_record, &-*ray.direction()),
+                None => ray.wavelengths().illuminant(self.background),
+            };
+            let contribution = throughput * light;
+            color = color + contribution;
+
+            if let Some(aovs) = aovs.as_deref_mut() {
+                match (depth, &hit_record) {
+                    (0, Some(hit_record)) => *aovs = surface_aovs(&ray, hit_record),
+                    (0, None) => *aovs = Aovs::missed(self.background),
+                    _ => {}
+                }
+                record_light(aovs, depth, first_lobe, contribution);
+            }
+
+            let Some(hit_record) = hit_record else {
+                break;
+            };
+            let Some(scattered) = hit_record.material.scatter(&ray, &hit_record, sampler) else {
+                break;
+            };
+            throughput = throughput * scattered.attenuation;
+            first_lobe.get_or_insert(scattered.lobe);
+            ray = scattered.ray;
+
+            if self
+                .russian_roulette_depth
+                .is_some_and(|min_depth| depth + 1 >= min_depth)
+            {
+                let [r, g, b] = throughput.components();
+                let survival_probability = r.max(g).max(b).min(1.0);
+                if sampler.get_1d() >= survival_probability {
+                    break;
+                }
+                throughput = (1.0 / survival_probability) * throughput;
+            }
+        }
+
+        color
+    }
+}
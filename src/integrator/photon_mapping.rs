@@ -0,0 +1,329 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::{Hit, HitRecord},
+    material::{Lobe, ScatterAndEmit},
+    math::{dot, Point3, Ray, Vec3},
+    sampler::{hash, GenerateSamples, IndependentSampler, Sampler},
+};
+
+use super::{
+    bsdf_sample_weight, ray_bounds, record_light, sample_light, surface_aovs, Integrate,
+    RenderContext,
+};
+
+/// Photon mapping (Jensen, 1996): photons are traced from the lights and stored where they hit
+/// diffuse surfaces. Camera paths follow specular bounces and scattering in media up to the first
+/// diffuse surface, where the direct light is estimated like [`super::DirectLighting`] does, and
+/// the indirect light from the density of the photons around the point. Since photons only start
+/// at lights, the background only contributes directly.
+///
+/// Every sample index gets its own photon map, shared by the samples with that index of all
+/// pixels. Averaging the samples of a pixel thus averages over many photon maps, which is as good
+/// as one huge map. The density estimate blurs the light over the lookup radius, so with a fixed
+/// radius the image converges to a slightly blurred result. The progressive variant (Knaus and
+/// Zwicker, 2011) shrinks the radius with every sample index, so that it converges to the correct
+/// image, at the cost of more noise.
+#[derive(Debug, Clone)]
+pub struct PhotonMapper {
+    max_depth: u32,
+    russian_roulette_depth: Option<u32>,
+    photons_per_pass: usize,
+    radius: Option<f64>,
+    progressive: bool,
+    background: Color,
+}
+
+/// Light carried by a photon that arrived at a point. Photons are shared by the samples of all
+/// pixels, which sample different wavelengths in spectral mode, so they are traced in RGB.
+#[derive(Debug, Clone)]
+struct Photon {
+    p: Point3,
+    /// Unit vector along which the photon arrived
+    direction: Vec3,
+    power: Color,
+}
+
+/// Photons of one pass, in a hash grid with cells as large as the lookup radius, so that a lookup
+/// only has to search the 27 cells around its point.
+#[derive(Debug)]
+pub struct PhotonMap {
+    cells: HashMap<[i64; 3], Vec<Photon>>,
+    radius: f64,
+    /// Number of photons emitted from the lights, including those that were never stored
+    emitted: usize,
+}
+
+impl PhotonMap {
+    fn new(photons: Vec<Photon>, radius: f64, emitted: usize) -> Self {
+        let mut cells = HashMap::<_, Vec<_>>::new();
+        for photon in photons {
+            cells
+                .entry(Self::cell(&photon.p, radius))
+                .or_default()
+                .push(photon);
+        }
+        Self {
+            cells,
+            radius,
+            emitted,
+        }
+    }
+
+    fn cell(p: &Point3, radius: f64) -> [i64; 3] {
+        let p = p.as_vec3();
+        [p.x, p.y, p.z].map(|x| (x / radius).floor() as i64)
+    }
+
+    /// Light scattered along `-ray_in` at `hit_record`, estimated from the photons within the
+    /// lookup radius.
+    fn estimate(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let material = hit_record.material;
+        let [x, y, z] = Self::cell(&hit_record.p, self.radius);
+
+        let mut sum = Color::black();
+        for (dx, dy, dz) in itertools::iproduct!(-1..=1, -1..=1, -1..=1) {
+            let Some(photons) = self.cells.get(&[x + dx, y + dy, z + dz]) else {
+                continue;
+            };
+            for photon in photons {
+                if (photon.p - hit_record.p).length_squared() > self.radius * self.radius {
+                    continue;
+                }
+                // The scattering includes the cosine, which the photon's power already accounts
+                // for
+                let incoming = -photon.direction;
+                let cos_theta = dot(&hit_record.normal, &incoming);
+                if cos_theta <= 0.0 {
+                    continue;
+                }
+                let f = material.scattering(ray_in, hit_record, &incoming);
+                let power = hit_record.wavelengths.illuminant(photon.power);
+                sum = sum + (1.0 / cos_theta) * (f * power);
+            }
+        }
+
+        // Divide by the area the photons were gathered from
+        let area = PI * self.radius * self.radius;
+        (1.0 / (area * self.emitted.max(1) as f64)) * sum
+    }
+}
+
+impl PhotonMapper {
+    /// Exponent of the radius reduction of the progressive variant, between 0 and 1. Smaller
+    /// values shrink the radius faster.
+    const ALPHA: f64 = 2.0 / 3.0;
+
+    /// Default lookup radius, in pixels at the distance the camera looks at. The scene's bounds
+    /// are no good measure, since they are dominated by huge objects like ground spheres.
+    const DEFAULT_RADIUS_PIXELS: f64 = 5.0;
+
+    /// `photons_per_pass` photons are traced for every sample index, and looked up within
+    /// `radius` of the shading point, or [`Self::DEFAULT_RADIUS_PIXELS`] pixels if `None`. With
+    /// `progressive`, the radius is only the initial one.
+    pub fn new(
+        max_depth: u32,
+        russian_roulette_depth: Option<u32>,
+        photons_per_pass: usize,
+        radius: Option<f64>,
+        progressive: bool,
+        background: Color,
+    ) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth,
+            photons_per_pass,
+            radius,
+            progressive,
+            background,
+        }
+    }
+
+    /// Lookup radius for the samples with index `sample_index`.
+    fn radius(&self, context: &RenderContext, sample_index: usize) -> f64 {
+        let initial = self
+            .radius
+            .unwrap_or_else(|| Self::DEFAULT_RADIUS_PIXELS * context.camera.pixel_footprint());
+        if !self.progressive {
+            return initial;
+        }
+
+        // r_{i+1}² = r_i² (i + α) / (i + 1)
+        let squared_ratio = (1..=sample_index)
+            .map(|i| (i as f64 + Self::ALPHA) / (i as f64 + 1.0))
+            .product::<f64>();
+        initial * squared_ratio.sqrt()
+    }
+
+    /// Traces photon `index` of a pass and returns the photons it left behind. Only photons that
+    /// bounced at least once are stored, since the direct light is sampled from the lights.
+    fn trace_photon(&self, context: &RenderContext, seed: u64, index: usize) -> Vec<Photon> {
+        let mut sampler: Sampler = IndependentSampler::new(seed).into();
+        sampler.start_pixel_sample((index, 0), 0);
+        let mut photons = Vec::new();
+
+        let time = context.camera.sample_time(sampler.get_1d());
+        let (u_position, (u1, u2)) = (sampler.get_2d(), sampler.get_2d());
+        let Some((light, pdf_position)) = context.world.lights().sample_surface(time, u_position)
+        else {
+            return photons;
+        };
+        if pdf_position <= 0.0 {
+            return photons;
+        }
+
+        // Directions are sampled cosine distributed on both sides, so the cosine cancels out
+        // with their density
+        let (side, u1) = if u1 < 0.5 {
+            (light.normal, 2.0 * u1)
+        } else {
+            (-light.normal, 2.0 * u1 - 1.0)
+        };
+        let direction = Vec3::cosine_weighted_from_sample(&side, (u1, u2));
+        let power = (2.0 * PI / pdf_position) * light.material.emit(&light, &direction);
+        let mut ray = Ray::new(light.p, direction, time);
+        let mut throughput = Color::white();
+
+        for depth in 0..self.max_depth {
+            let Some(hit_record) = context.world.hit(&ray, &ray_bounds()) else {
+                break;
+            };
+            let Some(scattered) = hit_record.material.scatter(&ray, &hit_record, &mut sampler)
+            else {
+                break;
+            };
+            if depth > 0 && is_diffuse_surface(&hit_record, scattered.lobe) {
+                photons.push(Photon {
+                    p: hit_record.p,
+                    direction: ray.direction().normalized(),
+                    power: throughput * power,
+                });
+            }
+
+            throughput = throughput * scattered.attenuation;
+            ray = scattered.ray;
+            if self
+                .russian_roulette_depth
+                .is_some_and(|min_depth| depth + 1 >= min_depth)
+            {
+                let [r, g, b] = throughput.components();
+                let survival_probability = r.max(g).max(b).min(1.0);
+                if sampler.get_1d() >= survival_probability {
+                    break;
+                }
+                throughput = (1.0 / survival_probability) * throughput;
+            }
+        }
+        photons
+    }
+}
+
+impl Integrate for PhotonMapper {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        mut aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let world = context.world;
+        let mut color = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = r.clone();
+        let mut first_lobe = None;
+
+        for depth in 0..self.max_depth {
+            let hit_record = world.hit(&ray, &ray_bounds());
+            let light = match &hit_record {
+                Some(hit_record) => hit_record.material.emit(hit_record, &-*ray.direction()),
+                None => ray.wavelengths().illuminant(self.background),
+            };
+            let contribution = throughput * light;
+            color = color + contribution;
+
+            if let Some(aovs) = aovs.as_deref_mut() {
+                match (depth, &hit_record) {
+                    (0, Some(hit_record)) => *aovs = surface_aovs(&ray, hit_record),
+                    (0, None) => *aovs = Aovs::missed(self.background),
+                    _ => {}
+                }
+                record_light(aovs, depth, first_lobe, contribution);
+            }
+
+            let Some(hit_record) = hit_record else {
+                break;
+            };
+            let Some(scattered) = hit_record.material.scatter(&ray, &hit_record, sampler) else {
+                break;
+            };
+            first_lobe.get_or_insert(scattered.lobe);
+            if !is_diffuse_surface(&hit_record, scattered.lobe) {
+                throughput = throughput * scattered.attenuation;
+                ray = scattered.ray;
+                continue;
+            }
+
+            // Photons are only stored at diffuse surfaces, so the path is followed until it
+            // reaches one
+            if depth + 1 >= self.max_depth {
+                break;
+            }
+            let incoming = match world.hit(&scattered.ray, &ray_bounds()) {
+                Some(next_hit) => {
+                    let weight = bsdf_sample_weight(
+                        world,
+                        &ray,
+                        &hit_record,
+                        &scattered.ray,
+                        scattered.lobe,
+                    );
+                    weight
+                        * next_hit
+                            .material
+                            .emit(&next_hit, &-*scattered.ray.direction())
+                }
+                None => ray.wavelengths().illuminant(self.background),
+            };
+            let direct = throughput
+                * (sample_light(world, &ray, &hit_record, sampler, true)
+                    + scattered.attenuation * incoming);
+            let indirect = match context.photons {
+                Some(photons) => throughput * photons.estimate(&ray, &hit_record),
+                None => Color::black(),
+            };
+            color = color + direct + indirect;
+            if let Some(aovs) = aovs.as_deref_mut() {
+                record_light(aovs, depth + 1, first_lobe, direct);
+                record_light(aovs, depth + 2, first_lobe, indirect);
+            }
+            break;
+        }
+
+        color
+    }
+
+    fn renders_by_sample_index(&self) -> bool {
+        true
+    }
+
+    fn trace_photons(&self, context: &RenderContext, sample_index: usize) -> Option<PhotonMap> {
+        let radius = self.radius(context, sample_index);
+        let seed = hash(&[context.camera.seed(), sample_index as u64]);
+        let photons = (0..self.photons_per_pass)
+            .into_par_iter()
+            .flat_map_iter(|index| self.trace_photon(context, seed, index))
+            .collect();
+        Some(PhotonMap::new(photons, radius, self.photons_per_pass))
+    }
+}
+
+/// Whether photons are stored and looked up at the hit. Neither is possible at specular surfaces,
+/// and the density of photons scattered in a medium would depend on the medium's density, which
+/// hit records don't carry.
+fn is_diffuse_surface(hit_record: &HitRecord, lobe: Lobe) -> bool {
+    lobe == Lobe::Diffuse && !hit_record.material.is_volumetric()
+}
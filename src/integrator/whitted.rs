@@ -0,0 +1,89 @@
+use crate::{
+    color::Color,
+    film::Aovs,
+    hittables::Hit,
+    material::{Lobe, ScatterAndEmit},
+    math::Ray,
+    sampler::Sampler,
+};
+
+use super::{ray_bounds, record_light, sample_light, surface_aovs, Integrate, RenderContext};
+
+/// Whitted-style ray tracer: specular surfaces reflect and refract rays recursively, and diffuse
+/// surfaces only receive light directly from light sources, through shadow rays. There is no
+/// indirect diffuse light, so images converge quickly but look flat.
+#[derive(Debug, Clone)]
+pub struct Whitted {
+    max_depth: u32,
+    background: Color,
+}
+
+impl Whitted {
+    pub fn new(max_depth: u32, background: Color) -> Self {
+        Self {
+            max_depth,
+            background,
+        }
+    }
+}
+
+impl Integrate for Whitted {
+    fn radiance(
+        &self,
+        r: &Ray,
+        context: &RenderContext,
+        sampler: &mut Sampler,
+        mut aovs: Option<&mut Aovs>,
+    ) -> Color {
+        let world = context.world;
+        let mut color = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = r.clone();
+        let mut first_lobe = None;
+
+        for depth in 0..self.max_depth {
+            let Some(hit_record) = world.hit(&ray, &ray_bounds()) else {
+                let background = throughput * ray.wavelengths().illuminant(self.background);
+                color = color + background;
+                if let Some(aovs) = aovs.as_deref_mut() {
+                    if depth == 0 {
+                        *aovs = Aovs::missed(self.background);
+                    }
+                    record_light(aovs, depth, first_lobe, background);
+                }
+                break;
+            };
+
+            let emitted = throughput * hit_record.material.emit(&hit_record, &-*ray.direction());
+            color = color + emitted;
+            if let Some(aovs) = aovs.as_deref_mut() {
+                if depth == 0 {
+                    *aovs = surface_aovs(&ray, &hit_record);
+                }
+                record_light(aovs, depth, first_lobe, emitted);
+            }
+
+            let Some(scattered) = hit_record.material.scatter(&ray, &hit_record, sampler) else {
+                break;
+            };
+            first_lobe.get_or_insert(scattered.lobe);
+            match scattered.lobe {
+                Lobe::Diffuse => {
+                    let direct =
+                        throughput * sample_light(world, &ray, &hit_record, sampler, false);
+                    color = color + direct;
+                    if let Some(aovs) = aovs.as_deref_mut() {
+                        record_light(aovs, depth + 1, first_lobe, direct);
+                    }
+                    break;
+                }
+                Lobe::Specular => {
+                    throughput = throughput * scattered.attenuation;
+                    ray = scattered.ray;
+                }
+            }
+        }
+
+        color
+    }
+}
@@ -1,6 +1,9 @@
 pub mod camera;
 pub mod color;
+pub mod film;
 pub mod hittables;
+pub mod integrator;
 pub mod material;
 pub mod math;
+pub mod sampler;
 pub mod texture;
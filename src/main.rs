@@ -1,25 +1,325 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::Result;
 use clap::Parser;
 use itertools::{iproduct, Itertools};
-use rand::{thread_rng, Rng};
+use rand::{Rng, SeedableRng};
 use weekend_raytracer::{
+    camera::{
+        AdaptiveSampling, Aperture, CameraAnimation, CameraParamsBuilder, Checkpoint, Filter,
+        FrameTiming, PhysicalCamera, Progressive, ShutterCurve,
+    },
     color::Color,
-    hittables::{BvhNode, ConstantMedium, Hittable, Instance, Quad, Sphere},
-    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
-    math::{Point3, Vec3},
-    texture::{CheckerTexture, Image, Noise},
+    film::{Denoiser, Pass},
+    hittables::{BvhNode, ConstantMedium, Hittable, Instance, Quad, Sphere, World},
+    integrator::IntegratorType,
+    material::{Dielectric, DiffuseLight, IesProfile, Lambertian, LightPower, Material, Metal},
+    math::{Axis, Interpolation, Keyframes, Point3, Vec3},
+    sampler::{Pcg32, SamplerType},
+    texture::{
+        Cellular, CellularFeature, CheckerTexture, ColorRamp, DistanceMetric, Fbm, Image, Marble,
+        Noise, Octaves, Procedural, RidgedMultifractal, TextureFilter, Wood, WrapMode,
+    },
     {camera::Camera, hittables::HittableList},
 };
 
 #[derive(clap::Parser)]
 #[command(version, about)]
 struct Args {
-    scene: Scene,
+    #[arg(required_unless_present = "ies_plot")]
+    scene: Option<Scene>,
+
+    /// First frame of an animation to render. Frames are written to image files given by
+    /// --output-pattern instead of stdout.
+    #[arg(long, requires = "frame_end")]
+    frame_start: Option<usize>,
+    /// Last frame of an animation to render (inclusive)
+    #[arg(long, requires = "frame_start")]
+    frame_end: Option<usize>,
+    /// Frames per second of an animation
+    #[arg(long, default_value_t = 24.0)]
+    fps: f64,
+    /// Fraction of a frame during which the shutter is open, in degrees (360 = whole frame)
+    #[arg(long, default_value_t = 180.0)]
+    shutter_angle: f64,
+    /// File name pattern for animation frames, where the run of '#' characters is replaced by
+    /// the zero-padded frame number, or which gets the frame number in front of its extension if
+    /// it has none. The image format is determined by the extension.
+    #[arg(long, default_value = "frame_####.png")]
+    output_pattern: String,
+
+    /// Enable adaptive sampling: pixels only receive further samples while their estimated
+    /// noise (standard error of the displayed luminance) is above this threshold
+    #[arg(long)]
+    noise_threshold: Option<f64>,
+    /// Minimum number of samples per pixel with adaptive sampling
+    #[arg(long, default_value_t = 16)]
+    min_spp: usize,
+    /// Maximum number of samples per pixel with adaptive sampling
+    #[arg(long, default_value_t = 1024)]
+    max_spp: usize,
+    /// Write an image showing the number of samples taken per pixel to this path
+    #[arg(long)]
+    sample_heatmap: Option<PathBuf>,
+
+    /// Algorithm that computes the light arriving at the camera
+    #[arg(long, value_enum, default_value_t = IntegratorArg::Path)]
+    integrator: IntegratorArg,
+    /// Distance up to which geometry occludes with the ambient occlusion integrator. Defaults to
+    /// a tenth of the diagonal of the scene's bounding box.
+    #[arg(long)]
+    ao_distance: Option<f64>,
+    /// Number of photons traced per sample per pixel with the photon mapping integrators
+    #[arg(long, default_value_t = 100_000)]
+    photons: usize,
+    /// Radius within which photons are looked up, initially for progressive photon mapping.
+    /// Defaults to 5 pixels at the distance the camera looks at.
+    #[arg(long)]
+    photon_radius: Option<f64>,
+    /// Number of independent paths traced per sample per pixel to normalize and start the Markov
+    /// chains of Metropolis light transport
+    #[arg(long, default_value_t = 100_000)]
+    bootstrap_samples: usize,
+    /// Number of Markov chains per sample per pixel with Metropolis light transport
+    #[arg(long, default_value_t = 100)]
+    chains: usize,
+    /// Probability of mutations of Metropolis light transport to start over at a random path
+    #[arg(long, default_value_t = 0.3)]
+    large_step_probability: f64,
+
+    /// Sampler used to generate pixel positions, lens positions, times and scattering directions
+    #[arg(long, value_enum, default_value_t = SamplerArg::Stratified)]
+    sampler: SamplerArg,
+    /// Seed for the random numbers used to build the scene and to render it. Rendering the same
+    /// scene with the same seed always produces the same image.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Reconstruction filter that weights samples around each pixel
+    #[arg(long, value_enum, default_value_t = FilterArg::Box)]
+    filter: FilterArg,
+    /// Radius of the reconstruction filter in pixels. Defaults to 0.5 for box, 1 for tent,
+    /// 1.5 for gaussian, 2 for mitchell and 3 for lanczos.
+    #[arg(long, value_parser = parse_filter_radius)]
+    filter_radius: Option<f64>,
+
+    /// Edge length in pixels of the square tiles that are distributed across threads
+    #[arg(long, default_value_t = 32)]
+    tile_size: usize,
+
+    /// Number of bounces after which paths are terminated by Russian roulette, with a
+    /// probability based on how much light they can still carry
+    #[arg(long, default_value_t = 3)]
+    russian_roulette_depth: u32,
+    /// Trace every path until it is absorbed or reaches the maximum depth of the scene
+    #[arg(long)]
+    no_russian_roulette: bool,
+    /// Render spectrally at sampled wavelengths instead of in RGB, which makes dispersive
+    /// materials split light into its colors
+    #[arg(long)]
+    spectral: bool,
+
+    /// Override the number of samples per pixel of the scene
+    #[arg(long)]
+    samples_per_pixel: Option<usize>,
+    /// Render progressively in passes of increasing sample count, and periodically save the
+    /// state to this file so that the render can be resumed with --resume
+    #[arg(long, conflicts_with_all = ["frame_start", "noise_threshold"])]
+    checkpoint: Option<PathBuf>,
+    /// Minimum number of seconds between two checkpoints
+    #[arg(long, default_value_t = 60)]
+    checkpoint_interval: u64,
+    /// Continue a progressive render from a checkpoint, e.g. with a higher --samples-per-pixel.
+    /// The seed is taken from the checkpoint, and the scene, sampler, filter, integrator,
+    /// spectral rendering and exposure need to be the same as when it was written. Unless
+    /// --checkpoint is given, the checkpoint is updated in place.
+    #[arg(long, conflicts_with_all = ["frame_start", "noise_threshold", "seed"])]
+    resume: Option<PathBuf>,
+    /// Render progressively and stop before this many seconds have been spent rendering. Unless
+    /// --samples-per-pixel is given, the sample count of the scene is not a limit.
+    #[arg(
+        long,
+        conflicts_with_all = ["frame_start", "noise_threshold"],
+        value_parser = parse_time_budget
+    )]
+    time_budget: Option<Duration>,
+    /// Render progressively and stop once the estimated RMSE of the image (in gamma corrected
+    /// luminance) is at most this. Unless --samples-per-pixel is given, the sample count of the
+    /// scene is not a limit.
+    #[arg(long, conflicts_with_all = ["frame_start", "noise_threshold"])]
+    noise_target: Option<f64>,
+
+    /// Render AOVs (albedo, normal, depth, position, material and object IDs, and light passes)
+    /// and write each of them as a PNG image into this directory
+    #[arg(long, conflicts_with = "frame_start")]
+    aov_dir: Option<PathBuf>,
+    /// Write the image as a 32-bit float OpenEXR file, including the AOVs as layers
+    #[arg(long, conflicts_with = "frame_start")]
+    exr: Option<PathBuf>,
+
+    /// Denoise the image after rendering, guided by the albedo, normal and depth AOVs
+    #[arg(long, conflicts_with = "frame_start")]
+    denoise: bool,
+    /// Strength of the denoiser: larger values smooth more, at the cost of detail
+    #[arg(long, default_value_t = 1.0, requires = "denoise")]
+    denoise_strength: f64,
+    /// Number of denoiser iterations. Each one doubles the size of the filter
+    #[arg(long, default_value_t = 5, requires = "denoise")]
+    denoise_iterations: usize,
+
+    /// Instead of rendering a scene, plot the light distribution of an IES file as a polar
+    /// diagram
+    #[arg(long)]
+    ies_plot: Option<PathBuf>,
+}
+
+/// Width and height of the image of --ies-plot
+const IES_PLOT_SIZE: usize = 512;
+
+/// Sample count used as the limit when rendering to a time budget or noise target
+const UNLIMITED_SAMPLES_PER_PIXEL: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum FilterArg {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterArg {
+    fn filter(self, radius: Option<f64>) -> Filter {
+        match self {
+            Self::Box => Filter::box_filter(radius.unwrap_or(0.5)),
+            Self::Tent => Filter::tent(radius.unwrap_or(1.0)),
+            Self::Gaussian => Filter::gaussian(radius.unwrap_or(1.5)),
+            Self::Mitchell => Filter::mitchell(radius.unwrap_or(2.0)),
+            Self::Lanczos => Filter::lanczos(radius.unwrap_or(3.0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum SamplerArg {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl From<SamplerArg> for SamplerType {
+    fn from(sampler: SamplerArg) -> Self {
+        match sampler {
+            SamplerArg::Independent => Self::Independent,
+            SamplerArg::Stratified => Self::Stratified,
+            SamplerArg::Halton => Self::Halton,
+            SamplerArg::Sobol => Self::Sobol,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum IntegratorArg {
+    /// Path tracing with all light paths
+    Path,
+    /// Only light that reaches the camera after at most one bounce
+    Direct,
+    /// Recursive specular reflection and refraction, and shadow rays at diffuse surfaces
+    Whitted,
+    /// Ambient occlusion
+    Ao,
+    /// Surface normals
+    Normals,
+    /// Bidirectional path tracing, for caustics and other paths that are hard to find from the
+    /// camera
+    Bdpt,
+    /// Photon mapping with a fixed lookup radius, for caustics
+    Photon,
+    /// Progressive photon mapping, whose lookup radius shrinks so that it converges to the correct
+    /// image
+    Sppm,
+    /// Light tracing, which connects paths from the lights to the camera
+    Light,
+    /// Metropolis light transport, for light that only few paths find
+    Mlt,
+}
+
+impl IntegratorArg {
+    fn integrator(&self, args: &Args) -> IntegratorType {
+        let photon_mapping = |progressive| IntegratorType::PhotonMapping {
+            photons_per_pass: args.photons,
+            radius: args.photon_radius,
+            progressive,
+        };
+        match self {
+            Self::Path => IntegratorType::PathTracer,
+            Self::Direct => IntegratorType::DirectLighting,
+            Self::Whitted => IntegratorType::Whitted,
+            Self::Ao => IntegratorType::AmbientOcclusion {
+                max_distance: args.ao_distance,
+            },
+            Self::Normals => IntegratorType::Normals,
+            Self::Bdpt => IntegratorType::BidirectionalPathTracer,
+            Self::Photon => photon_mapping(false),
+            Self::Sppm => photon_mapping(true),
+            Self::Light => IntegratorType::LightTracer,
+            Self::Mlt => IntegratorType::MetropolisLightTransport {
+                bootstrap_samples: args.bootstrap_samples,
+                chains: args.chains,
+                large_step_probability: args.large_step_probability,
+            },
+        }
+    }
+}
+
+/// Parses --time-budget, which must be a positive number of seconds.
+fn parse_time_budget(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|e| format!("{e}"))?;
+    if seconds <= 0.0 {
+        return Err("must be a positive number of seconds".to_string());
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{e}"))
+}
+
+/// Parses --filter-radius, which must be a positive number of pixels, up to [`Filter::MAX_RADIUS`].
+fn parse_filter_radius(radius: &str) -> Result<f64, String> {
+    let radius: f64 = radius.parse().map_err(|e| format!("{e}"))?;
+    if !(radius > 0.0 && radius <= Filter::MAX_RADIUS) {
+        return Err(format!(
+            "must be a positive number of pixels, up to {}",
+            Filter::MAX_RADIUS
+        ));
+    }
+    Ok(radius)
+}
+
+fn frame_path(pattern: &str, frame: usize) -> PathBuf {
+    match pattern.find('#') {
+        Some(start) => {
+            let width = pattern[start..].chars().take_while(|c| *c == '#').count();
+            let (prefix, suffix) = (&pattern[..start], &pattern[start + width..]);
+            PathBuf::from(format!("{prefix}{frame:0width$}{suffix}"))
+        }
+        None => {
+            // The extension determines the image format, so it has to stay at the end
+            let path = Path::new(pattern);
+            let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+            file_name.push(frame.to_string());
+            if let Some(extension) = path.extension() {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            path.with_file_name(file_name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[allow(clippy::enum_variant_names)]
 enum Scene {
     BouncingSpheres,
     CheckeredSpheres,
@@ -30,10 +330,17 @@ enum Scene {
     CornellBox,
     CornellSmoke,
     FinalScene,
+    Bokeh,
+    MotionBlur,
+    Turntable,
+    ManyLights,
+    IesLights,
+    ProceduralTextures,
+    TexturedFloor,
 }
 
 impl Scene {
-    fn create(&self) -> Result<(Camera, Hittable)> {
+    fn create(&self, rng: &mut impl Rng) -> Result<(CameraParamsBuilder, Hittable)> {
         let mut world = HittableList::default();
         let camera = match self {
             Self::BouncingSpheres => {
@@ -47,28 +354,27 @@ impl Scene {
 
                 for a in -11..11 {
                     for b in -11..11 {
-                        let chose_mat = thread_rng().gen_range(0.0..1.0);
+                        let chose_mat = rng.gen_range(0.0..1.0);
                         let center = Point3::new(
-                            a as f64 + 0.9 * thread_rng().gen_range(0.0..1.0),
+                            a as f64 + 0.9 * rng.gen_range(0.0..1.0),
                             0.2,
-                            b as f64 + 0.9 * thread_rng().gen_range(0.0..1.0),
+                            b as f64 + 0.9 * rng.gen_range(0.0..1.0),
                         );
 
                         if (center - Point3::new(4, 0.2, 0)).length() > 0.9 {
-                            let center2 =
-                                center + Vec3::new(0, thread_rng().gen_range(0.0..0.5), 0);
+                            let center2 = center + Vec3::new(0, rng.gen_range(0.0..0.5), 0);
                             world.push(Sphere::moving(
                                 center,
                                 center2,
                                 0.2,
                                 if chose_mat < 0.8 {
                                     let albedo = Color::from(
-                                        Vec3::random(0.0..1.0) * Vec3::random(0.0..1.0),
+                                        Vec3::random(rng, 0.0..1.0) * Vec3::random(rng, 0.0..1.0),
                                     );
                                     Material::from(Lambertian::new(albedo))
                                 } else if chose_mat < 0.95 {
-                                    let albedo = Color::from(Vec3::random(0.5..1.0));
-                                    let fuzz = thread_rng().gen_range(0.0..0.5);
+                                    let albedo = Color::from(Vec3::random(rng, 0.5..1.0));
+                                    let fuzz = rng.gen_range(0.0..0.5);
                                     Material::from(Metal::new(albedo, fuzz))
                                 } else {
                                     Material::from(Dielectric::new(1.5))
@@ -149,8 +455,50 @@ impl Scene {
                     .v_up(Vec3::new(0, 1, 0))
                     .defocus_angle(Some(0.6))
             }
+            Self::TexturedFloor => {
+                // Strips of a floor that recedes to the horizon, tiled with the same image, from
+                // left to right with every filter
+                let earth = Image::new(Path::new("res/earthmap.jpg"))?.with_wrap(WrapMode::Repeat);
+                let filters = [
+                    TextureFilter::Nearest,
+                    TextureFilter::Bilinear,
+                    TextureFilter::Trilinear,
+                    TextureFilter::Ewa,
+                ];
+                for (i, filter) in filters.into_iter().enumerate() {
+                    world.push(Quad::new(
+                        Point3::new(-8.0 + 4.0 * i as f64, 0.0, 2.0),
+                        Vec3::new(4, 0, 0),
+                        Vec3::new(0, 0, -200),
+                        Lambertian::new(earth.clone().with_filter(filter).with_tiling(2.0, 200.0)),
+                    ));
+                }
+                // Tiles that are flipped at every other edge, above the horizon
+                world.push(Quad::new(
+                    Point3::new(-2.0, 1.7, -6.0),
+                    Vec3::new(4, 0, 0),
+                    Vec3::new(0, 1, 0),
+                    Lambertian::new(
+                        earth
+                            .with_wrap(WrapMode::Mirror)
+                            .with_filter(TextureFilter::Ewa)
+                            .with_tiling(2.0, 2.0),
+                    ),
+                ));
+
+                Camera::builder()
+                    .background(Color::new(0.70, 0.80, 1.00))
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(600)
+                    .samples_per_pixel(16)
+                    .max_depth(10)
+                    .vfov_degrees(50.0)
+                    .look_from(Point3::new(0.0, 1.5, 3.0))
+                    .look_at(Point3::new(0.0, 0.5, -10.0))
+                    .v_up(Vec3::new(0, 1, 0))
+            }
             Self::PerlinSpheres => {
-                let perlin_text = Noise::new(4.0);
+                let perlin_text = Noise::new(4.0, rng);
                 world.push(Sphere::stationary(
                     Point3::new(0, -1000, 0),
                     1000.0,
@@ -223,7 +571,7 @@ impl Scene {
                     .v_up(Vec3::new(0, 1, 0))
             }
             Self::SimpleLight => {
-                let pertext = Lambertian::new(Noise::new(4.0));
+                let pertext = Lambertian::new(Noise::new(4.0, rng));
                 world.push(Sphere::stationary(
                     Point3::new(0, -1000, 0),
                     1000.0,
@@ -258,7 +606,7 @@ impl Scene {
                 let red = Lambertian::new(Color::new(0.65, 0.05, 0.05));
                 let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
                 let green = Lambertian::new(Color::new(0.12, 0.45, 0.15));
-                let light = DiffuseLight::new(Color::new(15.0, 15.0, 15.0));
+                let light = DiffuseLight::new(Color::new(15.0, 15.0, 15.0)).one_sided();
 
                 // Cornell box sides
                 world.push(Quad::new(
@@ -336,7 +684,7 @@ impl Scene {
                 let red = Lambertian::new(Color::new(0.65, 0.05, 0.05));
                 let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
                 let green = Lambertian::new(Color::new(0.12, 0.45, 0.15));
-                let light = DiffuseLight::new(Color::new(7.0, 7.0, 7.0));
+                let light = DiffuseLight::new(Color::new(7.0, 7.0, 7.0)).one_sided();
 
                 world.push(Quad::new(
                     Point3::new(555, 0, 0),
@@ -415,7 +763,7 @@ impl Scene {
                         let y0 = 0.0;
 
                         let x1 = x0 + w;
-                        let y1 = thread_rng().gen_range(1.0..101.0);
+                        let y1 = rng.gen_range(1.0..101.0);
                         let z1 = z0 + w;
                         Hittable::from(Quad::make_box(
                             Point3::new(x0, y0, z0),
@@ -478,14 +826,14 @@ impl Scene {
                 world.push(Sphere::stationary(
                     Point3::new(220, 280, 300),
                     80.0,
-                    Lambertian::new(Noise::new(0.2)),
+                    Lambertian::new(Noise::new(0.2, rng)),
                 ));
 
                 let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
                 let boxes2 = (0..1000)
                     .map(|_| {
                         Hittable::from(Sphere::stationary(
-                            Point3::from_vec3(Vec3::random(0.0..165.0)),
+                            Point3::from_vec3(Vec3::random(rng, 0.0..165.0)),
                             10.0,
                             white.clone(),
                         ))
@@ -507,20 +855,481 @@ impl Scene {
                     .look_at(Point3::new(278, 278, 0))
                     .v_up(Vec3::new(0, 1, 0))
             }
+            Self::Bokeh => {
+                // Night scene in meters: a row of small lamps far behind an in-focus sphere, shot
+                // wide open with a hexagonal aperture.
+                world.push(Sphere::stationary(
+                    Point3::new(0, -1000, 0),
+                    1000.0,
+                    Lambertian::new(Color::new(0.3, 0.3, 0.3)),
+                ));
+                world.push(Sphere::stationary(
+                    Point3::new(0, 0.1, 0),
+                    0.1,
+                    Metal::new(Color::new(0.8, 0.6, 0.2), 0.1),
+                ));
+
+                let lamp = DiffuseLight::new(Color::new(400.0, 300.0, 150.0));
+                for i in 0..10 {
+                    world.push(Sphere::stationary(
+                        Point3::new(-3.0 + 0.65 * i as f64, 0.1 + 0.1 * (i % 3) as f64, -20.0),
+                        0.03,
+                        lamp.clone(),
+                    ));
+                }
+
+                Camera::builder()
+                    .background(Color::new(0.5, 0.7, 1.5))
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(400)
+                    .samples_per_pixel(400)
+                    .max_depth(20)
+                    .look_from(Point3::new(0, 0.15, 3))
+                    .look_at(Point3::new(0, 0.1, 0))
+                    .v_up(Vec3::new(0, 1, 0))
+                    .focus_dist(3.0)
+                    .physical_camera(Some(PhysicalCamera {
+                        focal_length_mm: 135.0,
+                        f_stop: 1.4,
+                        iso: 400.0,
+                        shutter_seconds: 1.0 / 60.0,
+                        ..Default::default()
+                    }))
+                    .aperture(Aperture::polygonal(6, 15.0))
+            }
+            Self::MotionBlur => {
+                let checker =
+                    CheckerTexture::new(0.5, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+                world.push(Sphere::stationary(
+                    Point3::new(0, -1000, 0),
+                    1000.0,
+                    Lambertian::new(checker),
+                ));
+
+                // A box spinning about its vertical axis while sliding to the right
+                world.push(
+                    Hittable::from(Quad::make_box(
+                        Point3::new(-0.5, 0, -0.5),
+                        Point3::new(0.5, 1, 0.5),
+                        Lambertian::new(Color::new(0.65, 0.05, 0.05)),
+                    ))
+                    .rotate_keyframed(Keyframes::new([(0.0, 0.0), (1.0, 60.0)]), Axis::Y)
+                    .translate_keyframed(Keyframes::new([
+                        (0.0, Vec3::new(-1.5, 0, 0)),
+                        (1.0, Vec3::new(-0.5, 0, 0)),
+                    ])),
+                );
+
+                // A quad that falls down, then comes to a stop
+                world.push(
+                    Quad::new(
+                        Point3::new(0.5, 0, 0),
+                        Vec3::new(1, 0, 0),
+                        Vec3::new(0, 1, 0),
+                        Metal::new(Color::new(0.8, 0.8, 0.9), 0.2),
+                    )
+                    .translate_keyframed(Keyframes::new([
+                        (0.0, Vec3::new(0, 1.5, 0)),
+                        (0.5, Vec3::new(0, 0.2, 0)),
+                        (1.0, Vec3::new(0, 0.2, 0)),
+                    ])),
+                );
+
+                Camera::builder()
+                    .background(Color::new(0.70, 0.80, 1.00))
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(400)
+                    .samples_per_pixel(100)
+                    .max_depth(50)
+                    .vfov_degrees(40.0)
+                    .look_from(Point3::new(0, 2, 6))
+                    .look_at(Point3::new(0, 0.75, 0))
+                    .v_up(Vec3::new(0, 1, 0))
+                    .shutter_open(0.0)
+                    .shutter_close(1.0)
+                    .shutter_curve(ShutterCurve::Triangle)
+            }
+            Self::Turntable => {
+                // A spinning globe, orbited by the camera once every four seconds
+                world.push(Sphere::stationary(
+                    Point3::new(0, -1000, 0),
+                    1000.0,
+                    Lambertian::new(CheckerTexture::new(
+                        0.5,
+                        Color::new(0.2, 0.3, 0.1),
+                        Color::new(0.9, 0.9, 0.9),
+                    )),
+                ));
+                world.push(
+                    Sphere::stationary(
+                        Point3::origin(),
+                        1.0,
+                        Lambertian::new(Image::new(Path::new("res/earthmap.jpg"))?),
+                    )
+                    .rotate_keyframed(Keyframes::new([(0.0, 0.0), (4.0, 360.0)]), Axis::Y)
+                    .translate(Vec3::new(0, 1, 0)),
+                );
+                world.push(Sphere::stationary(
+                    Point3::new(2, 0.5, 0),
+                    0.5,
+                    Metal::new(Color::new(0.8, 0.8, 0.9), 0.0),
+                ));
+
+                let orbit = Keyframes::new((0..=4).map(|i| {
+                    let angle = (i as f64 * 90.0).to_radians();
+                    (
+                        i as f64,
+                        Point3::new(6.0 * angle.sin(), 2.5, 6.0 * angle.cos()),
+                    )
+                }))
+                .with_interpolation(Interpolation::CatmullRom);
+
+                Camera::builder()
+                    .background(Color::new(0.70, 0.80, 1.00))
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(400)
+                    .samples_per_pixel(64)
+                    .max_depth(50)
+                    .vfov_degrees(30.0)
+                    .look_from(Point3::new(0, 2.5, 6))
+                    .look_at(Point3::new(0, 1, 0))
+                    .v_up(Vec3::new(0, 1, 0))
+                    .animation(CameraAnimation {
+                        look_from: Some(orbit),
+                        ..Default::default()
+                    })
+            }
+            Self::ManyLights => {
+                // The boxes of the final scene, lit only by its cluster of small spheres, each of
+                // which glows in a random color
+                let ground = Lambertian::new(Color::new(0.48, 0.83, 0.53));
+                let boxes = iproduct!(0..20, 0..20)
+                    .map(|(i, j)| {
+                        let (x0, z0) = (-1000.0 + i as f64 * 100.0, -1000.0 + j as f64 * 100.0);
+                        Hittable::from(Quad::make_box(
+                            Point3::new(x0, 0.0, z0),
+                            Point3::new(x0 + 100.0, rng.gen_range(1.0..101.0), z0 + 100.0),
+                            ground.clone(),
+                        ))
+                    })
+                    .collect();
+                world.push(BvhNode::new(boxes));
+
+                world.push(Sphere::stationary(
+                    Point3::new(260, 150, 45),
+                    50.0,
+                    Dielectric::new(1.5),
+                ));
+                world.push(Sphere::stationary(
+                    Point3::new(0, 150, 145),
+                    50.0,
+                    Metal::new(Color::new(0.8, 0.8, 0.9), 0.0),
+                ));
+
+                let lamps = (0..1000)
+                    .map(|_| {
+                        let color = Color::from(Vec3::random(rng, 0.0..1.0));
+                        Hittable::from(Sphere::stationary(
+                            Point3::from_vec3(Vec3::random(rng, 0.0..165.0)),
+                            2.0,
+                            DiffuseLight::new(100.0 * color),
+                        ))
+                    })
+                    .collect_vec();
+                world.push(
+                    BvhNode::new(lamps)
+                        .rotate_y(15.0)
+                        .translate(Vec3::new(-100, 270, 395)),
+                );
+
+                Camera::builder()
+                    .aspect_ratio(1.0)
+                    .image_width(400)
+                    .samples_per_pixel(64)
+                    .max_depth(8)
+                    .vfov_degrees(40.0)
+                    .look_from(Point3::new(478, 278, -600))
+                    .look_at(Point3::new(278, 278, 0))
+                    .v_up(Vec3::new(0, 1, 0))
+            }
+            Self::IesLights => {
+                // Room in meters, whose back wall is lit by wall washers, which are small spheres
+                // emitting like point lights, and whose floor by a downlight panel in the ceiling.
+                // Both emit the flux of their luminaires. The wall washers are too small to be
+                // found by the path tracer, which doesn't sample lights.
+                let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
+                world.push(Quad::new(
+                    Point3::new(-3, 0, 2),
+                    Vec3::new(6, 0, 0),
+                    Vec3::new(0, 0, -4.5),
+                    white.clone(),
+                ));
+                world.push(Quad::new(
+                    Point3::new(-3, 0, -2.5),
+                    Vec3::new(6, 0, 0),
+                    Vec3::new(0, 3, 0),
+                    white.clone(),
+                ));
+                world.push(Quad::new(
+                    Point3::new(-3, 3, -2.5),
+                    Vec3::new(6, 0, 0),
+                    Vec3::new(0, 0, 4.5),
+                    white.clone(),
+                ));
+                for (x, color) in [
+                    (-3.0, Color::new(0.6, 0.2, 0.15)),
+                    (3.0, Color::new(0.73, 0.73, 0.73)),
+                ] {
+                    world.push(Quad::new(
+                        Point3::new(x, 0.0, 2.0),
+                        Vec3::new(0, 0, -4.5),
+                        Vec3::new(0, 3, 0),
+                        Lambertian::new(color),
+                    ));
+                }
+                world.push(Sphere::stationary(
+                    Point3::new(-0.8, 0.5, -0.8),
+                    0.5,
+                    Metal::new(Color::new(0.8, 0.8, 0.9), 0.05),
+                ));
+                world.push(Quad::make_box(
+                    Point3::new(0.4, 0.0, -1.4),
+                    Point3::new(1.2, 0.9, -0.6),
+                    white.clone(),
+                ));
+
+                // Horizontal angle 0 of the wall washers turned from +x towards the wall
+                let wall_washer = IesProfile::load(Path::new("res/ies/wall-washer.ies"))?
+                    .with_rotation(90.0, Axis::Y);
+                let lumens = wall_washer.lumens();
+                let washer_light = DiffuseLight::new(Color::new(1.0, 0.85, 0.7))
+                    .with_ies(wall_washer)
+                    .with_power(LightPower::Lumens(lumens));
+                for x in [-2.0, 0.0, 2.0] {
+                    world.push(Sphere::stationary(
+                        Point3::new(x, 2.9, -2.1),
+                        0.03,
+                        washer_light.clone(),
+                    ));
+                }
+
+                let downlight = IesProfile::load(Path::new("res/ies/downlight.ies"))?;
+                let lumens = downlight.lumens();
+                world.push(Quad::new(
+                    Point3::new(-0.2, 2.99, -0.2),
+                    Vec3::new(0.4, 0, 0),
+                    Vec3::new(0, 0, 0.4),
+                    DiffuseLight::new(Color::new(1.0, 1.0, 1.0))
+                        .one_sided()
+                        .with_ies(downlight)
+                        .with_power(LightPower::Lumens(lumens)),
+                ));
+
+                Camera::builder()
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(400)
+                    .samples_per_pixel(100)
+                    .max_depth(8)
+                    .look_from(Point3::new(0, 1.6, 1.9))
+                    .look_at(Point3::new(0, 1.3, -2.5))
+                    .v_up(Vec3::new(0, 1, 0))
+                    .focus_dist(4.0)
+                    .physical_camera(Some(PhysicalCamera {
+                        focal_length_mm: 18.0,
+                        f_stop: 4.0,
+                        iso: 400.0,
+                        shutter_seconds: 1.0 / 30.0,
+                        ..Default::default()
+                    }))
+            }
+            Self::ProceduralTextures => {
+                let stones = Cellular::new(rng).with_feature(CellularFeature::F2MinusF1);
+                let mortar = ColorRamp::new([
+                    (0.0, Color::new(0.05, 0.05, 0.05)),
+                    (0.08, Color::new(0.45, 0.42, 0.38)),
+                    (1.0, Color::new(0.6, 0.57, 0.52)),
+                ]);
+                world.push(Sphere::stationary(
+                    Point3::new(0, -1000, 0),
+                    1000.0,
+                    Lambertian::new(Procedural::new(stones, mortar).with_scale(1.5)),
+                ));
+
+                let clouds = Procedural::new(
+                    Fbm::new(rng),
+                    ColorRamp::new([
+                        (0.3, Color::new(0.1, 0.25, 0.7)),
+                        (0.7, Color::new(0.95, 0.95, 0.95)),
+                    ]),
+                )
+                .with_scale(3.0);
+                let mountains = Procedural::new(
+                    RidgedMultifractal::new(rng).with_octaves(Octaves {
+                        count: 6,
+                        lacunarity: 2.1,
+                        gain: 0.6,
+                    }),
+                    ColorRamp::new([
+                        (0.0, Color::new(0.1, 0.3, 0.1)),
+                        (0.5, Color::new(0.4, 0.3, 0.2)),
+                        (0.9, Color::new(0.9, 0.9, 0.9)),
+                    ])
+                    .with_interpolation(Interpolation::CatmullRom),
+                )
+                .with_scale(2.0);
+                let tiles = Procedural::new(
+                    Cellular::new(rng)
+                        .with_metric(DistanceMetric::Chebyshev)
+                        .with_jitter(0.5),
+                    ColorRamp::new([
+                        (0.0, Color::new(0.8, 0.6, 0.1)),
+                        (0.6, Color::new(0.5, 0.1, 0.1)),
+                    ]),
+                )
+                .with_scale(4.0);
+                let wood = Procedural::new(
+                    Wood::new(6.0, rng).with_axis(Vec3::new(0, 0.2, 1)),
+                    ColorRamp::new([
+                        (0.0, Color::new(0.55, 0.33, 0.15)),
+                        (0.7, Color::new(0.4, 0.22, 0.09)),
+                        (1.0, Color::new(0.25, 0.12, 0.05)),
+                    ]),
+                );
+                let marble = Procedural::new(
+                    Marble::new(6.0, rng)
+                        .with_axis(Vec3::new(1, 1, 0))
+                        .with_turbulence(6.0),
+                    ColorRamp::new([
+                        (0.0, Color::new(0.15, 0.2, 0.15)),
+                        (0.3, Color::new(0.8, 0.8, 0.75)),
+                        (1.0, Color::new(0.95, 0.95, 0.9)),
+                    ]),
+                );
+                for (i, texture) in [clouds, mountains, tiles, wood, marble]
+                    .into_iter()
+                    .enumerate()
+                {
+                    world.push(Sphere::stationary(
+                        Point3::new(2.5 * (i as f64 - 2.0), 1.0, 0.0),
+                        1.0,
+                        Lambertian::new(texture),
+                    ));
+                }
+
+                Camera::builder()
+                    .background(Color::new(0.70, 0.80, 1.00))
+                    .aspect_ratio(16.0 / 9.0)
+                    .image_width(600)
+                    .samples_per_pixel(100)
+                    .max_depth(50)
+                    .vfov_degrees(30.0)
+                    .look_from(Point3::new(0, 3, 13))
+                    .look_at(Point3::new(0, 1, 0))
+                    .v_up(Vec3::new(0, 1, 0))
+            }
         };
-        Ok((
-            camera.build(),
-            BvhNode::new(world.into_iter().collect()).into(),
-        ))
+        Ok((camera, BvhNode::new(world.into_iter().collect()).into()))
     }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let Some(scene) = args.scene else {
+        if let Some(path) = &args.ies_plot {
+            let plot = IesProfile::load(path)?.polar_plot(IES_PLOT_SIZE);
+            for (key, value) in plot.metadata() {
+                eprintln!("{key}: {value}");
+            }
+            plot.write_ppm(&mut std::io::stdout())?;
+        }
+        return Ok(());
+    };
 
-    let (camera, world) = args.scene.create()?;
+    let resume = args.resume.as_deref().map(Checkpoint::load).transpose()?;
+    let seed = resume.as_ref().map(Checkpoint::seed).unwrap_or(args.seed);
+
+    let (mut camera, world) = scene.create(&mut Pcg32::seed_from_u64(seed))?;
+    camera = camera
+        .integrator(args.integrator.integrator(&args))
+        .sampler(args.sampler.into())
+        .seed(seed)
+        .filter(args.filter.filter(args.filter_radius))
+        .tile_size(args.tile_size)
+        .russian_roulette_depth((!args.no_russian_roulette).then_some(args.russian_roulette_depth))
+        .spectral(args.spectral)
+        .aovs(args.aov_dir.is_some() || args.exr.is_some() || args.denoise);
+    if let Some(noise_threshold) = args.noise_threshold {
+        camera = camera.adaptive_sampling(Some(AdaptiveSampling {
+            min_samples: args.min_spp,
+            max_samples: args.max_spp,
+            noise_threshold,
+        }));
+    }
+    let has_stopping_criterion = args.time_budget.is_some() || args.noise_target.is_some();
+    match args.samples_per_pixel {
+        Some(samples_per_pixel) => camera = camera.samples_per_pixel(samples_per_pixel),
+        None if has_stopping_criterion => {
+            camera = camera.samples_per_pixel(UNLIMITED_SAMPLES_PER_PIXEL)
+        }
+        None => {}
+    }
+    let camera = camera.build();
+    let world = World::new(world);
 
-    camera.render(&world, &mut std::io::stdout())?;
+    if let (Some(frame_start), Some(frame_end)) = (args.frame_start, args.frame_end) {
+        let timing = FrameTiming {
+            fps: args.fps,
+            shutter_angle_degrees: args.shutter_angle,
+        };
+        for frame in frame_start..=frame_end {
+            let path = frame_path(&args.output_pattern, frame);
+            eprintln!("Rendering frame {frame} to {}", path.display());
+            camera
+                .at_frame(frame, &timing)
+                .render_film(&world)
+                .save(&path)?;
+        }
+    } else {
+        let checkpoint_path = args.checkpoint.as_ref().or(args.resume.as_ref());
+        let mut film = if checkpoint_path.is_some() || has_stopping_criterion {
+            let progressive = Progressive {
+                checkpoint_path: checkpoint_path.cloned(),
+                checkpoint_interval: Duration::from_secs(args.checkpoint_interval),
+                time_budget: args.time_budget,
+                noise_target: args.noise_target,
+                scene: format!("{scene:?}"),
+            };
+            let film = camera.render_progressive(&world, &progressive, resume)?;
+            for (key, value) in film.metadata() {
+                eprintln!("{key}: {value}");
+            }
+            film
+        } else {
+            camera.render_film(&world)
+        };
+        if args.denoise {
+            let denoiser = Denoiser {
+                iterations: args.denoise_iterations,
+                ..Denoiser::with_strength(args.denoise_strength)
+            };
+            film = denoiser.denoise(&film);
+        }
+        film.write_ppm(&mut std::io::stdout())?;
+        if let Some(path) = &args.sample_heatmap {
+            film.sample_heatmap().save(path)?;
+        }
+        if let Some(path) = &args.exr {
+            film.save_exr(path)?;
+        }
+        if let Some(dir) = &args.aov_dir {
+            std::fs::create_dir_all(dir)?;
+            for pass in Pass::ALL {
+                if let Some(image) = film.aov_image(pass) {
+                    image.save(&dir.join(format!("{}.png", pass.name())))?;
+                }
+            }
+        }
+    }
 
     Ok(())
 }
@@ -1,13 +1,21 @@
+use std::{
+    f64::consts::PI,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
 use enum_dispatch::enum_dispatch;
-use rand::{thread_rng, Rng};
 
 use crate::{
     color::Color,
-    hittables::HitRecord,
-    math::{dot, reflect, refract, Ray, Vec3},
+    hittables::{emitted_power, Hit, HitRecord},
+    math::{dot, reflect, refract, Ray, RayDifferentials, Vec3},
+    sampler::{GenerateSamples, Sampler},
     texture::{Texture, TextureValue},
 };
 
+mod ies;
+pub use ies::*;
+
 #[derive(Debug, Clone)]
 #[enum_dispatch(ScatterAndEmit)]
 pub enum Material {
@@ -18,67 +26,255 @@ pub enum Material {
     Isotropic(Isotropic),
 }
 
+/// Identifies a material, e.g. for the material ID pass. Every material gets a new ID when it is
+/// created, and keeps it when it is cloned, so objects sharing a material have the same ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(u32);
+
+impl MaterialId {
+    fn unique() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
 #[enum_dispatch]
 pub trait ScatterAndEmit {
-    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<ScatteredRay> {
+    fn id(&self) -> MaterialId;
+
+    /// Base color of the surface, as used e.g. for guiding denoisers.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::black()
+    }
+
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _hit_record: &HitRecord,
+        _sampler: &mut Sampler,
+    ) -> Option<ScatteredRay> {
         None
     }
 
-    fn emit(&self, _hit_record: &HitRecord) -> Color {
+    /// BSDF times the cosine of the scattering angle, for scattering `ray_in` into `direction`.
+    /// This is what allows sampling light sources directly. Materials that only scatter into
+    /// discrete directions (see [`Lobe::Specular`]) can't be evaluated and return black.
+    fn scattering(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Color {
+        Color::black()
+    }
+
+    /// Probability density, with respect to solid angle, of `scatter` choosing `direction`.
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Light emitted at `hit_record` into `direction`.
+    fn emit(&self, _hit_record: &HitRecord, _direction: &Vec3) -> Color {
         Color::black()
     }
+
+    /// Directions into which the material emits light, if it is emissive.
+    fn emission_profile(&self) -> Option<&EmissionProfile> {
+        None
+    }
+
+    /// Whether objects with this material are sampled as light sources.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Whether the material scatters inside a volume rather than at a surface, so that there is no
+    /// cosine between the surface normal and the incoming light.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Lambertian {
     texture: Texture,
+    id: MaterialId,
 }
 
 impl Lambertian {
     pub fn new(texture: impl Into<Texture>) -> Self {
         Self {
             texture: texture.into(),
+            id: MaterialId::unique(),
         }
     }
 }
 
 impl ScatterAndEmit for Lambertian {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+    fn id(&self) -> MaterialId {
+        self.id
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.value(hit_record)
+    }
+
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<ScatteredRay> {
+        let mut scatter_direction =
+            hit_record.normal + Vec3::unit_vector_from_sample(sampler.get_2d());
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
-        let attenuation = self.texture.value(&hit_record.texture_coords, hit_record.p);
-        ScatteredRay::new(hit_record, attenuation, scatter_direction, ray_in.time()).into()
+        let attenuation = hit_record
+            .wavelengths
+            .reflectance(self.texture.value(hit_record));
+        ScatteredRay::new(
+            hit_record,
+            attenuation,
+            scatter_direction,
+            ray_in.time(),
+            Lobe::Diffuse,
+        )
+        .into()
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.scattering_pdf(ray_in, hit_record, direction)
+            * hit_record
+                .wavelengths
+                .reflectance(self.texture.value(hit_record))
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        // Scattered directions are cosine distributed, so the pdf equals BSDF times cosine
+        // divided by the albedo
+        let cos_theta = dot(&hit_record.normal, &direction.normalized());
+        cos_theta.max(0.0) / PI
     }
 }
 
-#[derive(Debug, Clone, derive_more::Constructor)]
+#[derive(Debug, Clone)]
 pub struct Metal {
     albedo: Color,
     fuzz: f64,
+    id: MaterialId,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self {
+            albedo,
+            fuzz,
+            id: MaterialId::unique(),
+        }
+    }
 }
 
 impl ScatterAndEmit for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
+    fn id(&self) -> MaterialId {
+        self.id
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<ScatteredRay> {
         let reflected = reflect(ray_in.direction(), &hit_record.normal).normalized()
-            + self.fuzz * Vec3::random_unit_vector();
-        let scattered = ScatteredRay::new(hit_record, self.albedo, reflected, ray_in.time());
+            + self.fuzz * Vec3::unit_vector_from_sample(sampler.get_2d());
+        let mut scattered = ScatteredRay::new(
+            hit_record,
+            hit_record.wavelengths.reflectance(self.albedo),
+            reflected,
+            ray_in.time(),
+            Lobe::Specular,
+        );
+        if self.fuzz == 0.0 {
+            scattered.ray = scattered
+                .ray
+                .with_differentials(specular_differentials(ray_in, hit_record, &reflected, None));
+        }
         (dot(scattered.ray.direction(), &hit_record.normal) > 0.0).then_some(scattered)
     }
 }
 
-#[derive(Debug, Clone, derive_more::Constructor)]
+#[derive(Debug, Clone)]
 pub struct Dielectric {
     refraction_index: f64,
+    /// Abbe number of dispersive dielectrics, whose refraction index depends on the wavelength
+    abbe_number: Option<f64>,
+    id: MaterialId,
+}
+
+impl Dielectric {
+    pub fn new(refraction_index: f64) -> Self {
+        Self {
+            refraction_index,
+            abbe_number: None,
+            id: MaterialId::unique(),
+        }
+    }
+
+    /// Dielectric that splits light into its colors in spectral mode, with `refraction_index` at
+    /// 587.6nm and the given Abbe number, which is lower for stronger dispersion (e.g. 64 for
+    /// crown glass, 36 for flint glass). Renders like [`Dielectric::new`] in RGB mode.
+    pub fn dispersive(refraction_index: f64, abbe_number: f64) -> Self {
+        Self {
+            abbe_number: Some(abbe_number),
+            ..Self::new(refraction_index)
+        }
+    }
+
+    /// Refraction index at `lambda` in nanometers, following Cauchy's equation n = A + B/λ²,
+    /// fitted to the refraction index and the Abbe number.
+    fn refraction_index_at(&self, abbe_number: f64, lambda: f64) -> f64 {
+        // Fraunhofer d, F and C lines, in micrometers
+        let (d, f, c) = (0.5876, 0.4861, 0.6563);
+        let b = (self.refraction_index - 1.0) / (abbe_number * (1.0 / (f * f) - 1.0 / (c * c)));
+        let a = self.refraction_index - b / (d * d);
+        let lambda = lambda / 1000.0;
+        a + b / (lambda * lambda)
+    }
 }
 
 impl ScatterAndEmit for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
+    fn id(&self) -> MaterialId {
+        self.id
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::white()
+    }
+
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<ScatteredRay> {
+        // Light of different wavelengths refracts into different directions, so only the hero
+        // wavelength can follow the path
+        let mut wavelengths = hit_record.wavelengths;
+        let (refraction_index, attenuation) = match (self.abbe_number, wavelengths.hero()) {
+            (Some(abbe_number), Some(lambda)) => (
+                self.refraction_index_at(abbe_number, lambda),
+                wavelengths.terminate_secondary(),
+            ),
+            _ => (self.refraction_index, Color::white()),
+        };
         let ri = if hit_record.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
 
         let unit_direction = ray_in.direction().normalized();
@@ -87,17 +283,62 @@ impl ScatterAndEmit for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Self::reflectance(cos_theta, ri) > thread_rng().gen_range(0.0..=1.0)
-        {
-            reflect(&unit_direction, &hit_record.normal)
+        let u = sampler.get_1d();
+        let (direction, eta) = if cannot_refract || Self::reflectance(cos_theta, ri) > u {
+            (reflect(&unit_direction, &hit_record.normal), None)
         } else {
-            refract(&unit_direction, &hit_record.normal, ri)
+            (refract(&unit_direction, &hit_record.normal, ri), Some(ri))
         };
-        ScatteredRay::new(hit_record, Color::white(), direction, ray_in.time()).into()
+        let mut scattered = ScatteredRay::new(
+            hit_record,
+            attenuation,
+            direction,
+            ray_in.time(),
+            Lobe::Specular,
+        );
+        scattered.ray = scattered
+            .ray
+            .with_wavelengths(wavelengths)
+            .with_differentials(specular_differentials(ray_in, hit_record, &direction, eta));
+        Some(scattered)
     }
 }
 
+/// Differentials of the ray that leaves `hit_record` into direction `wi` by perfect specular
+/// reflection, or by refraction with the ratio `eta` of the refraction indices on the incident
+/// and the transmitted side. They follow from differentiating the law of reflection or Snell's
+/// law (Igehy, 1999). `None` if `ray_in` or the hit have no differentials.
+fn specular_differentials(
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    wi: &Vec3,
+    eta: Option<f64>,
+) -> Option<RayDifferentials> {
+    let (rd, sd) = (ray_in.differentials()?, hit_record.differentials.as_ref()?);
+    let n = hit_record.normal;
+    let wo = -ray_in.direction().normalized();
+    let wi = wi.normalized();
+    let offset_direction = |offset_direction: &Vec3, dndx: &Vec3| {
+        let dwodx = -offset_direction.normalized() - wo;
+        let d_dn_dx = dot(&dwodx, &n) + dot(&wo, dndx);
+        match eta {
+            None => wi - dwodx + 2.0 * (dot(&wo, &n) * *dndx + d_dn_dx * n),
+            Some(eta) => {
+                let cos_transmitted = dot(&wi, &n).abs();
+                let mu = eta * dot(&wo, &n) - cos_transmitted;
+                let dmudx = (eta - eta * eta * dot(&wo, &n) / cos_transmitted) * d_dn_dx;
+                wi - eta * dwodx + (mu * *dndx + dmudx * n)
+            }
+        }
+    };
+    Some(RayDifferentials {
+        rx_origin: hit_record.p + sd.dpdx,
+        rx_direction: offset_direction(&rd.rx_direction, &sd.dndx),
+        ry_origin: hit_record.p + sd.dpdy,
+        ry_direction: offset_direction(&rd.ry_direction, &sd.dndy),
+    })
+}
+
 impl Dielectric {
     fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
         // Use Schlick's approximation for reflectance.
@@ -106,58 +347,337 @@ impl Dielectric {
     }
 }
 
+/// Light source that emits the radiance given by its texture. By default, it emits from both
+/// sides of its surfaces, equally into all directions.
 #[derive(Debug, Clone)]
 pub struct DiffuseLight {
     texture: Texture,
+    id: MaterialId,
+    profile: EmissionProfile,
+    power: Option<LightPower>,
+    /// Factor of the radiance of the texture, which makes objects emit `power` once they fitted
+    /// the material to their surface (see [`Material::fitted_to`])
+    scale: f64,
 }
 
 impl DiffuseLight {
     pub fn new(texture: impl Into<Texture>) -> Self {
         Self {
             texture: texture.into(),
+            id: MaterialId::unique(),
+            profile: EmissionProfile {
+                two_sided: true,
+                closed: false,
+                spotlight: None,
+                ies: None,
+            },
+            power: None,
+            scale: 1.0,
         }
     }
+
+    /// Only emits from the front side of surfaces, into the directions of their outward normals.
+    pub fn one_sided(mut self) -> Self {
+        self.profile.two_sided = false;
+        self
+    }
+
+    /// Concentrates the emitted light around the normal, see [`Spotlight`].
+    pub fn with_spotlight(mut self, spotlight: Spotlight) -> Self {
+        self.profile.spotlight = Some(spotlight);
+        self
+    }
+
+    /// Shapes the emitted light like the luminaire of an IES file. The radiance of the texture
+    /// is the one into the direction of the luminaire's maximum intensity, and it can be set
+    /// with [`DiffuseLight::with_power`], e.g. to [`IesProfile::lumens`]. Small spheres work as
+    /// point lights with the exact distribution of the profile, and flat surfaces emit it into
+    /// the directions they emit into.
+    pub fn with_ies(mut self, profile: IesProfile) -> Self {
+        self.profile.ies = Some(Box::new(profile));
+        self
+    }
+
+    /// Scales the radiance so that every object with this material emits `power` in total,
+    /// rather than the radiance of the texture. The texture only gives the color then.
+    pub fn with_power(mut self, power: LightPower) -> Self {
+        self.power = Some(power);
+        self
+    }
 }
 
 impl ScatterAndEmit for DiffuseLight {
-    fn emit(&self, hit_record: &HitRecord) -> Color {
-        self.texture.value(&hit_record.texture_coords, hit_record.p)
+    fn id(&self) -> MaterialId {
+        self.id
+    }
+
+    fn emit(&self, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let outward_normal = if hit_record.front_face {
+            hit_record.normal
+        } else {
+            -hit_record.normal
+        };
+        let falloff = self.profile.falloff(&outward_normal, direction);
+        if falloff <= 0.0 {
+            return Color::black();
+        }
+        (falloff * self.scale)
+            * hit_record
+                .wavelengths
+                .illuminant(self.texture.value(hit_record))
+    }
+
+    fn emission_profile(&self) -> Option<&EmissionProfile> {
+        Some(&self.profile)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+impl Material {
+    /// The material adapted to `surface`, which has this material: lights with IES profiles
+    /// learn whether the surface is closed (see [`EmissionProfile::falloff`]), and lights that
+    /// are given by their power (see [`DiffuseLight::with_power`]) get the radiance that makes
+    /// the surface emit that power. `None` if the material doesn't change.
+    pub(crate) fn fitted_to(&self, surface: &impl Hit) -> Option<Self> {
+        let Self::DiffuseLight(light) = self else {
+            return None;
+        };
+        let closed = surface.is_closed();
+        let changes_shape = light.profile.ies.is_some() && light.profile.closed != closed;
+        if light.power.is_none() && !changes_shape {
+            return None;
+        }
+        let mut light = light.clone();
+        light.profile.closed = closed;
+        let Some(target) = light.power.map(|power| power.lumens()) else {
+            return Some(light.into());
+        };
+        let power = emitted_power(surface, Some(&light.clone().into()));
+        (power > 0.0).then(|| {
+            DiffuseLight {
+                scale: light.scale * target / power,
+                ..light
+            }
+            .into()
+        })
+    }
+}
+
+/// Directions into which a light emits, relative to the outward normal of its surface, and to
+/// the scene for IES profiles.
+#[derive(Debug, Clone)]
+pub struct EmissionProfile {
+    pub two_sided: bool,
+    /// Whether the emitting surface encloses a volume, like a sphere, so that it looks equally
+    /// large from every direction
+    pub closed: bool,
+    pub spotlight: Option<Spotlight>,
+    pub ies: Option<Box<IesProfile>>,
+}
+
+impl EmissionProfile {
+    /// Smallest cosine to the normal by which IES profiles are divided on flat surfaces, so that
+    /// the radiance stays bounded at grazing angles
+    const MIN_IES_COS: f64 = 0.05;
+
+    /// Factor of the radiance emitted into `direction` at a point with the given outward normal.
+    ///
+    /// The intensity of a flat surface falls off with the cosine to its normal, since its
+    /// projected area does, so IES profiles are divided by that cosine to make the intensity
+    /// follow the profile. Closed surfaces have the same projected area in every direction.
+    pub fn falloff(&self, outward_normal: &Vec3, direction: &Vec3) -> f64 {
+        let cos_theta = dot(outward_normal, direction) / direction.length();
+        let cos_theta = if self.two_sided {
+            cos_theta.abs()
+        } else {
+            cos_theta
+        };
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let spotlight = self
+            .spotlight
+            .map_or(1.0, |spotlight| spotlight.falloff(cos_theta));
+        let ies = self.ies.as_ref().map_or(1.0, |ies| {
+            let intensity = ies.relative_intensity(direction);
+            if self.closed {
+                intensity
+            } else {
+                intensity / cos_theta.max(Self::MIN_IES_COS)
+            }
+        });
+        spotlight * ies
+    }
+
+    /// Cosine of the largest angle to the normal at which light is emitted.
+    pub fn cos_theta_max(&self) -> f64 {
+        self.spotlight
+            .map_or(0.0, |spotlight| spotlight.cos_total_width)
+    }
+
+    /// Integral of the falloff times the cosine to the normal over all directions, which is the
+    /// power emitted per area and unit of radiance. IES profiles aren't taken into account, since
+    /// they don't only depend on the angle to the normal.
+    pub fn projected_solid_angle(&self) -> f64 {
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        let per_side = match self.spotlight {
+            Some(spotlight) => spotlight.projected_solid_angle(),
+            None => PI,
+        };
+        sides * per_side
+    }
+}
+
+/// Falloff of the light that a surface emits towards larger angles to its normal, like the
+/// beam of a spotlight: full radiance up to the angle at which the falloff starts, and none
+/// beyond the total width, with a smooth transition in between.
+#[derive(Debug, Clone, Copy)]
+pub struct Spotlight {
+    cos_falloff_start: f64,
+    cos_total_width: f64,
+}
+
+impl Spotlight {
+    /// Angles to the normal, at most 90°.
+    pub fn new(falloff_start_degrees: f64, total_width_degrees: f64) -> Self {
+        let total_width_degrees = total_width_degrees.clamp(0.0, 90.0);
+        let falloff_start_degrees = falloff_start_degrees.clamp(0.0, total_width_degrees);
+        Self {
+            cos_falloff_start: falloff_start_degrees.to_radians().cos(),
+            cos_total_width: total_width_degrees.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_falloff_start {
+            1.0
+        } else if cos_theta <= self.cos_total_width {
+            0.0
+        } else {
+            let t = (cos_theta - self.cos_total_width)
+                / (self.cos_falloff_start - self.cos_total_width);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+
+    /// Integral of the falloff times the cosine over the hemisphere: the cosine integrated up to
+    /// the start of the falloff, plus the smoothstep times the cosine up to the total width.
+    fn projected_solid_angle(&self) -> f64 {
+        let (start, end) = (self.cos_falloff_start, self.cos_total_width);
+        let width = start - end;
+        2.0 * PI * ((1.0 - start * start) / 2.0 + width * (end / 2.0 + 7.0 * width / 20.0))
+    }
+}
+
+/// Total power of a light, as an alternative to its radiance. Radiance is taken to be in
+/// candela per square scene unit, which is what [`crate::camera::PhysicalCamera`] expects, so
+/// powers are physical if scene units are meters.
+#[derive(Debug, Clone, Copy)]
+pub enum LightPower {
+    /// Radiant power, in watts of light at the wavelength the eye is most sensitive to
+    Watts(f64),
+    /// Luminous power
+    Lumens(f64),
+}
+
+impl LightPower {
+    /// Luminous efficacy of light at 555nm
+    const LUMENS_PER_WATT: f64 = 683.0;
+
+    fn lumens(&self) -> f64 {
+        match self {
+            Self::Watts(watts) => watts * Self::LUMENS_PER_WATT,
+            Self::Lumens(lumens) => *lumens,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Isotropic {
     texture: Texture,
+    id: MaterialId,
 }
 
 impl Isotropic {
     pub fn new(texture: impl Into<Texture>) -> Self {
         Self {
             texture: texture.into(),
+            id: MaterialId::unique(),
         }
     }
 }
 
 impl ScatterAndEmit for Isotropic {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatteredRay> {
-        ScatteredRay {
-            attenuation: self.texture.value(&hit_record.texture_coords, hit_record.p),
-            ray: Ray::new(hit_record.p, Vec3::random_unit_vector(), ray_in.time()),
-        }
+    fn id(&self) -> MaterialId {
+        self.id
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.value(hit_record)
+    }
+
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<ScatteredRay> {
+        ScatteredRay::new(
+            hit_record,
+            hit_record
+                .wavelengths
+                .reflectance(self.texture.value(hit_record)),
+            Vec3::unit_vector_from_sample(sampler.get_2d()),
+            ray_in.time(),
+            Lobe::Diffuse,
+        )
         .into()
     }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.scattering_pdf(ray_in, hit_record, direction)
+            * hit_record
+                .wavelengths
+                .reflectance(self.texture.value(hit_record))
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+/// Kind of scattering event, for splitting the image into diffuse and specular light passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse,
+    Specular,
 }
 
 pub struct ScatteredRay {
     pub attenuation: Color,
     pub ray: Ray,
+    pub lobe: Lobe,
 }
 
 impl ScatteredRay {
-    pub fn new(hit_record: &HitRecord, attenuation: Color, direction: Vec3, time: f64) -> Self {
+    pub fn new(
+        hit_record: &HitRecord,
+        attenuation: Color,
+        direction: Vec3,
+        time: f64,
+        lobe: Lobe,
+    ) -> Self {
         Self {
             attenuation,
-            ray: Ray::new(hit_record.p, direction, time),
+            ray: Ray::new(hit_record.p, direction, time).with_wavelengths(hit_record.wavelengths),
+            lobe,
         }
     }
 }
@@ -0,0 +1,483 @@
+use std::{f64::consts::PI, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use itertools::iproduct;
+
+use crate::{
+    color::Color,
+    film::Film,
+    math::{Axis, Matrix3, Vec3},
+};
+
+/// Photometric profile of a luminaire from an IES LM-63 file: the luminous intensity, in
+/// candela, that it emits into every direction.
+///
+/// Only type C photometry is supported, which is what virtually all files of architectural
+/// luminaires use. Its vertical angle is measured from the nadir of the luminaire, and the
+/// horizontal angle around it, counterclockwise seen from above. By default the luminaire points
+/// down (-y), with the horizontal angle 0° towards +x and 90° towards -z. Tilt data is ignored.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    /// In degrees, ascending
+    vertical_angles: Vec<f64>,
+    /// In degrees, ascending, after filling in the full circle if the distribution is symmetric
+    /// about the nadir
+    horizontal_angles: Vec<f64>,
+    symmetry: Symmetry,
+    /// Candela for every horizontal angle, for every vertical angle
+    candela: Vec<Vec<f64>>,
+    max_candela: f64,
+    lumens: f64,
+    to_luminaire: Matrix3,
+}
+
+/// Symmetry of a distribution, given by the range of horizontal angles of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Symmetry {
+    /// A single horizontal angle
+    Rotational,
+    /// 0° to 90°
+    Quadrants,
+    /// 0° to 180°
+    BilateralC0,
+    /// 90° to 270°
+    BilateralC90,
+    /// The full circle
+    None,
+}
+
+impl IesProfile {
+    /// Number of steps per 180° in which the candela are integrated for the luminous flux
+    const FLUX_STEPS: usize = 180;
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).with_context(|| format!("invalid IES file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        // Keyword lines up to the tilt specification, then whitespace or comma separated numbers
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .context("missing TILT line")?
+            .trim();
+        let numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<f64>()
+                    .with_context(|| format!("invalid number {token}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut numbers = numbers.into_iter();
+        let mut next = |what: &str| numbers.next().with_context(|| format!("missing {what}"));
+
+        if tilt == "INCLUDE" {
+            let _lamp_to_luminaire_geometry = next("tilt geometry")?;
+            let pairs = next("number of tilt angles")? as usize;
+            let count = pairs.checked_mul(2).context("too many tilt angles")?;
+            for _ in 0..count {
+                next("tilt angles and factors")?;
+            }
+        }
+
+        let _lamps = next("number of lamps")?;
+        let _lumens_per_lamp = next("lumens per lamp")?;
+        let multiplier = next("candela multiplier")?;
+        let vertical_count = next("number of vertical angles")? as usize;
+        let horizontal_count = next("number of horizontal angles")? as usize;
+        let photometric_type = next("photometric type")?;
+        for what in ["units type", "width", "length", "height"] {
+            next(what)?;
+        }
+        let ballast_factor = next("ballast factor")?;
+        let _future_use = next("future use")?;
+        let _input_watts = next("input watts")?;
+        if photometric_type != 1.0 {
+            bail!("only type C photometry is supported, not type {photometric_type}");
+        }
+        ensure!(
+            vertical_count >= 2 && horizontal_count >= 1,
+            "too few angles"
+        );
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next("vertical angle"))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut horizontal_angles = (0..horizontal_count)
+            .map(|_| next("horizontal angle"))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| Ok(multiplier * ballast_factor * next("candela value")?))
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let is_ascending = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        ensure!(
+            is_ascending(&vertical_angles) && is_ascending(&horizontal_angles),
+            "angles are not ascending"
+        );
+
+        let (first, last) = (
+            horizontal_angles[0],
+            horizontal_angles[horizontal_count - 1],
+        );
+        let symmetry = if horizontal_count == 1 {
+            Symmetry::Rotational
+        } else if first == 0.0 && last == 90.0 {
+            Symmetry::Quadrants
+        } else if first == 0.0 && last == 180.0 {
+            Symmetry::BilateralC0
+        } else if first == 90.0 && last == 270.0 {
+            Symmetry::BilateralC90
+        } else if first == 0.0 && last <= 360.0 {
+            Symmetry::None
+        } else {
+            bail!("unsupported range of horizontal angles from {first}° to {last}°");
+        };
+        if symmetry == Symmetry::None && last < 360.0 {
+            // Close the circle, so that angles between the last one and 360° are interpolated
+            horizontal_angles.push(360.0);
+            candela.push(candela[0].clone());
+        }
+
+        let max_candela = candela.iter().flatten().copied().fold(0.0, f64::max);
+        ensure!(max_candela > 0.0, "the luminaire doesn't emit any light");
+        let mut profile = Self {
+            vertical_angles,
+            horizontal_angles,
+            symmetry,
+            candela,
+            max_candela,
+            lumens: 0.0,
+            to_luminaire: Matrix3::identity(),
+        };
+        profile.lumens = profile.integrate_flux();
+        Ok(profile)
+    }
+
+    /// Rotates the luminaire about an axis of the scene, after any earlier rotations.
+    pub fn with_rotation(mut self, angle_degrees: f64, axis: Axis) -> Self {
+        self.to_luminaire = self.to_luminaire * Matrix3::rotate(-angle_degrees, axis);
+        self
+    }
+
+    /// Luminous flux that the luminaire emits in total, which can be passed on to
+    /// [`super::DiffuseLight::with_power`] to make a light emit as much as the luminaire.
+    pub fn lumens(&self) -> f64 {
+        self.lumens
+    }
+
+    /// Luminous intensity into `direction`, in candela.
+    pub fn candela(&self, direction: &Vec3) -> f64 {
+        let d = (self.to_luminaire * *direction).normalized();
+        let vertical = (-d.y).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = (-d.z).atan2(d.x).to_degrees().rem_euclid(360.0);
+        self.candela_at(horizontal, vertical)
+    }
+
+    /// Luminous intensity into `direction`, relative to the maximum of the distribution.
+    pub fn relative_intensity(&self, direction: &Vec3) -> f64 {
+        self.candela(direction) / self.max_candela
+    }
+
+    /// Bilinearly interpolated candela at the given angles in degrees, with the horizontal angle
+    /// in [0°, 360°). The luminaire emits nothing at vertical angles outside of the file's range.
+    fn candela_at(&self, horizontal: f64, vertical: f64) -> f64 {
+        let Some((j, t)) = lerp_position(&self.vertical_angles, vertical) else {
+            return 0.0;
+        };
+        let horizontal = match self.symmetry {
+            Symmetry::Rotational => 0.0,
+            Symmetry::Quadrants => {
+                let h = if horizontal > 180.0 {
+                    360.0 - horizontal
+                } else {
+                    horizontal
+                };
+                if h > 90.0 {
+                    180.0 - h
+                } else {
+                    h
+                }
+            }
+            Symmetry::BilateralC0 if horizontal > 180.0 => 360.0 - horizontal,
+            Symmetry::BilateralC90 if horizontal < 90.0 => 180.0 - horizontal,
+            Symmetry::BilateralC90 if horizontal > 270.0 => 540.0 - horizontal,
+            Symmetry::BilateralC0 | Symmetry::BilateralC90 | Symmetry::None => horizontal,
+        };
+        let (i, s) = lerp_position(&self.horizontal_angles, horizontal).unwrap_or((0, 0.0));
+
+        let at = |i: usize| {
+            let values = &self.candela[i];
+            let next = values.get(j + 1).copied().unwrap_or(values[j]);
+            (1.0 - t) * values[j] + t * next
+        };
+        let next = if i + 1 < self.candela.len() { i + 1 } else { i };
+        (1.0 - s) * at(i) + s * at(next)
+    }
+
+    /// Integral of the candela over all directions, with the midpoint rule.
+    fn integrate_flux(&self) -> f64 {
+        let n = Self::FLUX_STEPS;
+        let step = PI / n as f64;
+        let mut lumens = 0.0;
+        for j in 0..n {
+            let vertical = (j as f64 + 0.5) * step;
+            let solid_angle = vertical.sin() * step * step;
+            for i in 0..2 * n {
+                let horizontal = (i as f64 + 0.5) * step;
+                lumens +=
+                    solid_angle * self.candela_at(horizontal.to_degrees(), vertical.to_degrees());
+            }
+        }
+        lumens
+    }
+
+    /// Polar diagram of the distribution, the way it's shown on data sheets of luminaires: the
+    /// candela along the vertical angles, with the nadir pointing down, in the plane of the
+    /// horizontal angles 0° and 180° in red, and of 90° and 270° in blue, which overlap in dark
+    /// purple. Gray circles mark quarters of the maximum intensity, and gray lines steps of 30°.
+    /// The rotation of the profile is ignored.
+    pub fn polar_plot(&self, size: usize) -> Film {
+        const SUBSAMPLES: usize = 4;
+        let background = Color::white();
+        let grid = Color::new(0.5, 0.5, 0.5);
+        let planes = [
+            (0.0, Color::new(0.9, 0.1, 0.1)),
+            (90.0, Color::new(0.1, 0.2, 0.9)),
+        ];
+        let center = size as f64 / 2.0;
+        let radius = 0.45 * size as f64;
+        let line_width = 1.0 / radius;
+
+        let mut film = Film::new(size, size);
+        for (x, y) in iproduct!(0..size, 0..size) {
+            let mut sum = Color::black();
+            for (i, j) in iproduct!(0..SUBSAMPLES, 0..SUBSAMPLES) {
+                let dx = (x as f64 + (i as f64 + 0.5) / SUBSAMPLES as f64 - center) / radius;
+                let dy = (y as f64 + (j as f64 + 0.5) / SUBSAMPLES as f64 - center) / radius;
+                let r = dx.hypot(dy);
+                let vertical = dx.abs().atan2(dy).to_degrees();
+
+                let mut color = background;
+                for (horizontal, plane_color) in planes {
+                    let horizontal = if dx < 0.0 {
+                        horizontal + 180.0
+                    } else {
+                        horizontal
+                    };
+                    if r <= self.candela_at(horizontal, vertical) / self.max_candela {
+                        color = color * plane_color;
+                    }
+                }
+                let on_circle = (1..=4).any(|k| (r - k as f64 / 4.0).abs() < line_width);
+                let on_ray = r <= 1.0
+                    && (0..6).any(|k| {
+                        let angle = (k as f64 * 30.0).to_radians();
+                        (dx * angle.cos() - dy * angle.sin()).abs() < line_width
+                    });
+                if on_circle || on_ray {
+                    color = 0.5 * (color + grid);
+                }
+                sum = sum + color;
+            }
+            film.set_pixel(x, y, (1.0 / (SUBSAMPLES * SUBSAMPLES) as f64) * sum, 1);
+        }
+        film.set_metadata("max candela", self.max_candela);
+        film.set_metadata("lumens", self.lumens);
+        film
+    }
+}
+
+/// Index of the last angle at or below `x`, and how far `x` is towards the next one, or `None`
+/// if `x` is outside of the angles.
+fn lerp_position(angles: &[f64], x: f64) -> Option<(usize, f64)> {
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+    if x < first || x > last {
+        return None;
+    }
+    let i = angles
+        .partition_point(|a| *a <= x)
+        .saturating_sub(1)
+        .min(angles.len().saturating_sub(2));
+    let t = if i + 1 < angles.len() {
+        ((x - angles[i]) / (angles[i + 1] - angles[i])).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    Some((i, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file with the given tilt section, horizontal angles and candela per horizontal angle, at
+    /// the vertical angles 0°, 90° and 180°.
+    fn ies(tilt: &str, horizontal_angles: &[f64], candela: &[[f64; 3]]) -> String {
+        let join = |values: &[f64]| {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let candela = candela.iter().map(|c| join(c)).collect::<Vec<_>>();
+        format!(
+            "IESNA:LM-63-2002\n[TEST] profile\nTILT={tilt}\n\
+             1 1000 1 3 {} 1 1 0 0 0\n1 1 100\n0 90 180\n{}\n{}\n",
+            horizontal_angles.len(),
+            join(horizontal_angles),
+            candela.join("\n"),
+        )
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    /// Direction at the given angles of type C photometry, in the default orientation
+    fn direction(horizontal: f64, vertical: f64) -> Vec3 {
+        let (h, v) = (horizontal.to_radians(), vertical.to_radians());
+        Vec3::new(v.sin() * h.cos(), -v.cos(), -v.sin() * h.sin())
+    }
+
+    #[test]
+    fn tilt_include_is_skipped() {
+        let text = ies(
+            "INCLUDE\n1\n3\n0 45 90\n1 0.9 0.8",
+            &[0.0],
+            &[[100.0, 50.0, 0.0]],
+        );
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_close(profile.candela(&direction(0.0, 0.0)), 100.0);
+        assert_close(profile.candela(&direction(0.0, 90.0)), 50.0);
+    }
+
+    #[test]
+    fn too_many_tilt_angles() {
+        let text = ies("INCLUDE\n1\n1e300\n0\n1", &[0.0], &[[100.0, 50.0, 0.0]]);
+        assert!(IesProfile::parse(&text).is_err());
+    }
+
+    #[test]
+    fn rotational_symmetry() {
+        let profile = IesProfile::parse(&ies("NONE", &[0.0], &[[100.0, 50.0, 0.0]])).unwrap();
+        assert_eq!(profile.symmetry, Symmetry::Rotational);
+        for h in [0.0, 45.0, 135.0, 270.0] {
+            assert_close(profile.candela(&direction(h, 90.0)), 50.0);
+        }
+    }
+
+    #[test]
+    fn quadrant_symmetry() {
+        let text = ies(
+            "NONE",
+            &[0.0, 90.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0]],
+        );
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.symmetry, Symmetry::Quadrants);
+        for (h, expected) in [(0.0, 10.0), (90.0, 20.0), (180.0, 10.0), (270.0, 20.0)] {
+            assert_close(profile.candela(&direction(h, 90.0)), expected);
+        }
+        assert_close(profile.candela(&direction(135.0, 90.0)), 15.0);
+    }
+
+    #[test]
+    fn bilateral_symmetry_about_c0() {
+        let text = ies(
+            "NONE",
+            &[0.0, 90.0, 180.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0], [100.0, 30.0, 0.0]],
+        );
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.symmetry, Symmetry::BilateralC0);
+        for (h, expected) in [(0.0, 10.0), (90.0, 20.0), (180.0, 30.0), (270.0, 20.0)] {
+            assert_close(profile.candela(&direction(h, 90.0)), expected);
+        }
+        assert_close(profile.candela(&direction(315.0, 90.0)), 15.0);
+    }
+
+    #[test]
+    fn bilateral_symmetry_about_c90() {
+        let text = ies(
+            "NONE",
+            &[90.0, 180.0, 270.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0], [100.0, 30.0, 0.0]],
+        );
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.symmetry, Symmetry::BilateralC90);
+        for (h, expected) in [(90.0, 10.0), (180.0, 20.0), (270.0, 30.0), (0.0, 20.0)] {
+            assert_close(profile.candela(&direction(h, 90.0)), expected);
+        }
+        assert_close(profile.candela(&direction(45.0, 90.0)), 15.0);
+        assert_close(profile.candela(&direction(315.0, 90.0)), 25.0);
+    }
+
+    #[test]
+    fn no_symmetry_closes_the_circle() {
+        let text = ies(
+            "NONE",
+            &[0.0, 120.0, 240.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0], [100.0, 40.0, 0.0]],
+        );
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.symmetry, Symmetry::None);
+        assert_close(profile.candela(&direction(120.0, 90.0)), 20.0);
+        assert_close(profile.candela(&direction(300.0, 90.0)), 25.0);
+    }
+
+    #[test]
+    fn unsupported_horizontal_range() {
+        let text = ies(
+            "NONE",
+            &[10.0, 90.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0]],
+        );
+        assert!(IesProfile::parse(&text).is_err());
+    }
+
+    #[test]
+    fn angles_not_ascending() {
+        let text = ies(
+            "NONE",
+            &[90.0, 0.0],
+            &[[100.0, 10.0, 0.0], [100.0, 20.0, 0.0]],
+        );
+        assert!(IesProfile::parse(&text).is_err());
+        let text = ies("NONE", &[0.0], &[[100.0, 50.0, 0.0]]).replace("0 90 180", "0 180 90");
+        assert!(IesProfile::parse(&text).is_err());
+    }
+
+    #[test]
+    fn truncated_files() {
+        let text = ies(
+            "INCLUDE\n1\n3\n0 45 90\n1 0.9 0.8",
+            &[0.0],
+            &[[100.0, 50.0, 0.0]],
+        );
+        let numbers = text.find("TILT=").unwrap() + "TILT=INCLUDE".len();
+        for end in numbers..text.trim_end().len() {
+            if text[..end].ends_with(char::is_whitespace) {
+                continue;
+            }
+            // Cutting off the last digit of a number still leaves a valid file
+            if text[end..].trim().chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            assert!(
+                IesProfile::parse(&text[..end]).is_err(),
+                "accepted file truncated to {:?}",
+                &text[..end]
+            );
+        }
+        assert!(IesProfile::parse("IESNA:LM-63-2002\n").is_err());
+    }
+}
@@ -97,6 +97,16 @@ impl Aabb {
         }
     }
 
+    pub fn center(&self) -> Point3 {
+        let center = |interval: &Interval| (interval.min() + interval.max()) / 2.0;
+        Point3::new(center(&self.x), center(&self.y), center(&self.z))
+    }
+
+    /// Vector from the minimum to the maximum corner
+    pub fn diagonal(&self) -> Vec3 {
+        Vec3::new(self.x.size(), self.y.size(), self.z.size())
+    }
+
     pub fn longest_axis(&self) -> Axis {
         if self.x.size() > self.y.size() {
             if self.x.size() > self.z.size() {
@@ -0,0 +1,137 @@
+use super::{Point3, Vec3};
+
+/// Types that can be linearly interpolated between two values.
+pub trait Lerp: Copy {
+    fn lerp(a: Self, b: Self, t: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(a: Self, b: Self, t: f64) -> Self {
+        (1.0 - t) * a + t * b
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(a: Self, b: Self, t: f64) -> Self {
+        (1.0 - t) * a + t * b
+    }
+}
+
+impl Lerp for Point3 {
+    fn lerp(a: Self, b: Self, t: f64) -> Self {
+        a + t * (b - a)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through the keys, using the key times as knots. This gives smooth motion
+    /// through all keys, but may overshoot between them.
+    CatmullRom,
+}
+
+/// A value that changes over time, given by its value at a set of key times.
+///
+/// Values in between two keys are interpolated, values before the first or after the last key are
+/// held constant. Two keys at the same time make a step: the value jumps from the first key to the
+/// second one, and splines end at the step instead of interpolating across it.
+#[derive(Debug, Clone)]
+pub struct Keyframes<T> {
+    keys: Vec<(f64, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T: Lerp> Keyframes<T> {
+    pub fn new(keys: impl IntoIterator<Item = (f64, T)>) -> Self {
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        assert!(!keys.is_empty(), "Keyframes need at least one key");
+        keys.sort_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+        Self {
+            keys,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new([(0.0, value)])
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keys.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+
+        let (t1, p1) = self.keys[next - 1];
+        let (t2, p2) = self.keys[next];
+        match self.interpolation {
+            Interpolation::Linear => T::lerp(p1, p2, (time - t1) / (t2 - t1)),
+            Interpolation::CatmullRom => {
+                let ((t0, p0), (t3, p3)) = self.outer_keys(next - 1);
+
+                // Barry and Goldman's pyramidal formulation
+                let lerp = |a, b, ta: f64, tb: f64| T::lerp(a, b, (time - ta) / (tb - ta));
+                let a1 = lerp(p0, p1, t0, t1);
+                let a2 = lerp(p1, p2, t1, t2);
+                let a3 = lerp(p2, p3, t2, t3);
+                let b1 = lerp(a1, a2, t0, t2);
+                let b2 = lerp(a2, a3, t1, t3);
+                lerp(b1, b2, t1, t2)
+            }
+        }
+    }
+
+    // The keys before and after the segment starting at key i, mirrored at the ends and at steps,
+    // where the neighbouring key has the same time as the segment's key
+    fn outer_keys(&self, i: usize) -> ((f64, T), (f64, T)) {
+        let (t1, p1) = self.keys[i];
+        let (t2, p2) = self.keys[i + 1];
+        let before = match i.checked_sub(1).map(|j| self.keys[j]) {
+            Some((t0, p0)) if t0 < t1 => (t0, p0),
+            _ => (2.0 * t1 - t2, T::lerp(p2, p1, 2.0)),
+        };
+        let after = match self.keys.get(i + 2) {
+            Some(&(t3, p3)) if t3 > t2 => (t3, p3),
+            _ => (2.0 * t2 - t1, T::lerp(p1, p2, 2.0)),
+        };
+        (before, after)
+    }
+
+    /// Values whose convex hull contains every value the keyframes can take on.
+    pub fn bounding_values(&self) -> Vec<T> {
+        let mut values: Vec<T> = self.keys.iter().map(|(_, v)| *v).collect();
+        if let Interpolation::CatmullRom = self.interpolation {
+            // In the pyramidal formulation, the result is a convex combination of a1, a2 and a3.
+            // Within a segment a2 stays between p1 and p2, while a1 and a3 extrapolate the
+            // neighbouring segments up to the following points.
+            for i in 0..self.keys.len().saturating_sub(1) {
+                let (t1, p1) = self.keys[i];
+                let (t2, p2) = self.keys[i + 1];
+                if t1 == t2 {
+                    // Steps are never interpolated
+                    continue;
+                }
+                let ((t0, p0), (t3, p3)) = self.outer_keys(i);
+                values.push(T::lerp(p0, p1, (t2 - t0) / (t1 - t0)));
+                values.push(T::lerp(p2, p3, (t1 - t2) / (t3 - t2)));
+            }
+        }
+        values
+    }
+}
+
+impl<T: Lerp> FromIterator<(f64, T)> for Keyframes<T> {
+    fn from_iter<I: IntoIterator<Item = (f64, T)>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
@@ -14,6 +14,12 @@ impl Matrix3 {
         }
     }
 
+    pub fn identity() -> Self {
+        Self {
+            data: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
     pub fn rotate(angle_degrees: f64, axis: Axis) -> Self {
         match axis {
             Axis::X => Self::rotate_about_x(angle_degrees),
@@ -56,6 +62,20 @@ impl Matrix3 {
     }
 }
 
+impl Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, rhs: Matrix3) -> Matrix3 {
+        let mut data = [[0.0; 3]; 3];
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.data[i][k] * rhs.data[k][j]).sum();
+            }
+        }
+        Self { data }
+    }
+}
+
 impl Mul<Vec3> for Matrix3 {
     type Output = Vec3;
 
@@ -17,3 +17,7 @@ mod interval;
 pub use interval::Interval;
 mod aabb;
 pub use aabb::*;
+mod keyframes;
+pub use keyframes::*;
+mod onb;
+pub use onb::*;
@@ -0,0 +1,28 @@
+use super::{cross, Vec3};
+
+/// Orthonormal basis whose `w` axis points along a given direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = w.normalized();
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0, 1, 0)
+        } else {
+            Vec3::new(1, 0, 0)
+        };
+        let v = cross(&w, &a).normalized();
+        let u = cross(&w, &v);
+        Self { u, v, w }
+    }
+
+    /// Transforms a vector from the basis' coordinates to world coordinates.
+    pub fn transform(&self, a: f64, b: f64, c: f64) -> Vec3 {
+        a * self.u + b * self.v + c * self.w
+    }
+}
@@ -1,15 +1,66 @@
-use crate::math::vec3::Vec3;
+use crate::{color::Wavelengths, math::vec3::Vec3};
 
 use super::Point3;
 
-#[derive(Debug, Clone, PartialEq, derive_more::Constructor)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
     time: f64,
+    wavelengths: Wavelengths,
+    differentials: Option<RayDifferentials>,
+}
+
+/// Rays that are offset from a camera ray by a pixel along the x and y of the image, which
+/// track the size of the pixel's footprint along the ray (Igehy, 1999).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayDifferentials {
+    pub rx_origin: Point3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point3,
+    pub ry_direction: Vec3,
+}
+
+impl RayDifferentials {
+    /// Moves the offset rays towards the ray with origin `origin` and direction `direction`
+    /// by `scale`, e.g. to the spacing of the samples within a pixel.
+    pub fn scaled(&self, origin: &Point3, direction: &Vec3, scale: f64) -> Self {
+        Self {
+            rx_origin: *origin + scale * (self.rx_origin - *origin),
+            rx_direction: *direction + scale * (self.rx_direction - *direction),
+            ry_origin: *origin + scale * (self.ry_origin - *origin),
+            ry_direction: *direction + scale * (self.ry_direction - *direction),
+        }
+    }
 }
 
 impl Ray {
+    pub fn new(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            wavelengths: Wavelengths::Rgb,
+            differentials: None,
+        }
+    }
+
+    /// The ray, carrying light at `wavelengths`.
+    pub fn with_wavelengths(self, wavelengths: Wavelengths) -> Self {
+        Self {
+            wavelengths,
+            ..self
+        }
+    }
+
+    /// The ray, with offset rays that describe its footprint.
+    pub fn with_differentials(self, differentials: Option<RayDifferentials>) -> Self {
+        Self {
+            differentials,
+            ..self
+        }
+    }
+
     pub fn origin(&self) -> &Point3 {
         &self.origin
     }
@@ -26,7 +77,22 @@ impl Ray {
         self.time
     }
 
+    pub fn wavelengths(&self) -> Wavelengths {
+        self.wavelengths
+    }
+
+    pub fn differentials(&self) -> Option<&RayDifferentials> {
+        self.differentials.as_ref()
+    }
+
     pub fn offset(&self, offset: Vec3) -> Self {
+        let differentials = self.differentials.map(|d| RayDifferentials {
+            rx_origin: d.rx_origin - offset,
+            ry_origin: d.ry_origin - offset,
+            ..d
+        });
         Self::new(self.origin - offset, self.direction, self.time)
+            .with_wavelengths(self.wavelengths)
+            .with_differentials(differentials)
     }
 }
@@ -1,6 +1,6 @@
-use std::ops::Index;
+use std::{f64::consts::PI, ops::Index};
 
-use rand::{distributions::uniform::SampleRange, thread_rng, Rng};
+use rand::{distributions::uniform::SampleRange, Rng};
 
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Vec3 {
@@ -27,8 +27,7 @@ impl Vec3 {
         self.x.abs() < eps && self.y.abs() < eps && self.z.abs() < eps
     }
 
-    pub fn random(r: impl SampleRange<f64> + Clone) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random(rng: &mut impl Rng, r: impl SampleRange<f64> + Clone) -> Self {
         Self::new(
             rng.gen_range(r.clone()),
             rng.gen_range(r.clone()),
@@ -36,15 +35,18 @@ impl Vec3 {
         )
     }
 
-    pub fn random_unit_vector() -> Self {
-        std::iter::repeat_with(|| Self::random(-1.0..1.0))
-            .find(|p| p.length_squared() < 1.0)
-            .expect("The iterator is infinite, so we either have bad luck forever or eventually find a point")
-            .normalized()
+    /// Maps a uniform sample from [0,1) x [0,1) to a uniformly distributed unit vector.
+    pub fn unit_vector_from_sample((u1, u2): (f64, f64)) -> Self {
+        let z = 1.0 - 2.0 * u1;
+        let r = f64::sqrt((1.0 - z * z).max(0.0));
+        let phi = 2.0 * PI * u2;
+        Self::new(r * phi.cos(), r * phi.sin(), z)
     }
 
-    pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
-        let on_unit_sphere = Self::random_unit_vector();
+    /// Maps a uniform sample from [0,1) x [0,1) to a uniformly distributed unit vector on the
+    /// hemisphere around `normal`.
+    pub fn on_hemisphere_from_sample(normal: &Vec3, u: (f64, f64)) -> Vec3 {
+        let on_unit_sphere = Self::unit_vector_from_sample(u);
         if dot(&on_unit_sphere, normal) > 0.0 {
             on_unit_sphere
         } else {
@@ -52,11 +54,30 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = thread_rng();
-        std::iter::repeat_with(|| Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0))
-            .find(|p| p.length_squared() < 1.0)
-            .expect("The iterator is infinite, so we either have bad luck forever or eventually find a point")
+    /// Maps a uniform sample from [0,1) x [0,1) to a unit vector on the hemisphere around `normal`,
+    /// distributed proportionally to the cosine of the angle to the normal.
+    pub fn cosine_weighted_from_sample(normal: &Vec3, u: (f64, f64)) -> Vec3 {
+        let direction = *normal + Self::unit_vector_from_sample(u);
+        if direction.near_zero() {
+            *normal
+        } else {
+            direction.normalized()
+        }
+    }
+
+    /// Maps a uniform sample from [0,1) x [0,1) to a uniformly distributed point in the unit disk,
+    /// using Shirley and Chiu's concentric mapping, which keeps stratified samples stratified.
+    pub fn in_unit_disk_from_sample((u1, u2): (f64, f64)) -> Vec3 {
+        let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vec3::zero();
+        }
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     pub fn length(&self) -> f64 {
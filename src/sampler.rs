@@ -0,0 +1,131 @@
+use enum_dispatch::enum_dispatch;
+
+mod rng;
+pub use rng::Pcg32;
+
+mod independent;
+pub use independent::*;
+
+mod stratified;
+pub use stratified::*;
+
+mod halton;
+pub use halton::*;
+
+mod sobol;
+pub use sobol::*;
+
+mod metropolis;
+pub use metropolis::*;
+
+/// Source of the random numbers used to render a pixel sample.
+///
+/// All random decisions along a path (position within the pixel, position on the lens, time,
+/// scattering directions, ...) consume one or two dimensions of the current sample, in a fixed
+/// order. Samplers can then distribute the values of each dimension better than independent random
+/// numbers would, which reduces noise.
+#[derive(Debug, Clone)]
+#[enum_dispatch(GenerateSamples)]
+pub enum Sampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+    /// Mutates the samples of a Markov chain, not selectable as [`SamplerType`]
+    Metropolis(MetropolisSampler),
+}
+
+/// Kind of sampler to render with, see [`Sampler::new`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SamplerType {
+    Independent,
+    #[default]
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl Sampler {
+    pub fn new(sampler_type: SamplerType, samples_per_pixel: usize, seed: u64) -> Self {
+        match sampler_type {
+            SamplerType::Independent => IndependentSampler::new(seed).into(),
+            SamplerType::Stratified => StratifiedSampler::new(samples_per_pixel, seed).into(),
+            SamplerType::Halton => HaltonSampler::new(seed).into(),
+            SamplerType::Sobol => SobolSampler::new(seed).into(),
+        }
+    }
+}
+
+#[enum_dispatch]
+pub trait GenerateSamples {
+    /// Starts generating sample `sample_index` of the given pixel, beginning with the first
+    /// dimension.
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize);
+
+    /// Returns the next dimension, in [0,1).
+    fn get_1d(&mut self) -> f64;
+
+    /// Returns the next two dimensions, in [0,1) x [0,1).
+    fn get_2d(&mut self) -> (f64, f64);
+
+    /// Returns the position within the pixel, in [0,1) x [0,1).
+    fn get_pixel_2d(&mut self) -> (f64, f64) {
+        self.get_2d()
+    }
+}
+
+/// Finalizer of the 64-bit variant of MurmurHash3, which scrambles all bits of its input.
+pub(crate) fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+/// Hashes a sequence of values into a single value, e.g. for deriving seeds.
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e3779b97f4a7c15, |acc, v| mix_bits(acc ^ mix_bits(*v)))
+}
+
+/// Returns the element at index `i` of a random permutation of 0..n, where the permutation is
+/// determined by `seed`. Uses Kensler's hash-based permutation, which doesn't need to store the
+/// permutation.
+pub(crate) fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % n
+}
+
+/// Largest f64 below 1, for clamping samples to [0,1).
+pub(crate) const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
@@ -0,0 +1,81 @@
+use rand::Rng;
+
+use super::{hash, mix_bits, permutation_element, GenerateSamples, Pcg32, ONE_MINUS_EPSILON};
+
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Samples from the Halton sequence, where dimension d is the radical inverse of the sample index
+/// in the d-th prime base. The digits are Owen scrambled with a different seed per pixel, so that
+/// neighbouring pixels don't use identical sample patterns.
+///
+/// Dimensions beyond the number of supported primes fall back to independent random numbers.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: u64,
+    dimension: usize,
+    rng: Pcg32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_seed: seed,
+            sample_index: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits = 0u64;
+
+    // Also scramble the (infinitely many) leading zero digits, until they no longer affect the
+    // result at f64 precision.
+    while 1.0 - inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_seed = mix_bits(seed ^ reversed_digits) as u32;
+        let digit = permutation_element(digit as u32, base as u32, digit_seed) as u64;
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (inv_base_m * reversed_digits as f64).min(ONE_MINUS_EPSILON)
+}
+
+impl GenerateSamples for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+        self.rng = Pcg32::new(self.pixel_seed, sample_index as u64);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match PRIMES.get(dimension) {
+            Some(base) => owen_scrambled_radical_inverse(
+                *base,
+                self.sample_index,
+                hash(&[self.pixel_seed, dimension as u64]),
+            ),
+            None => self.rng.gen(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
@@ -0,0 +1,36 @@
+use rand::Rng;
+
+use super::{hash, GenerateSamples, Pcg32};
+
+/// Uniform random samples without any correlation between them.
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl GenerateSamples for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.rng = Pcg32::new(
+            hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]),
+            sample_index as u64,
+        );
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
@@ -0,0 +1,128 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::{GenerateSamples, Pcg32, ONE_MINUS_EPSILON};
+
+/// Sampler for Metropolis light transport in primary sample space (Kelemen et al., 2002). The
+/// dimensions of a sample form the state of a Markov chain, which every iteration mutates: either
+/// by a large step, which replaces all dimensions by uniform random numbers, or by a small step,
+/// which perturbs them slightly. The mutated state is then accepted or rejected.
+///
+/// Dimensions are only mutated when they are first used in an iteration, so the cost of an
+/// iteration doesn't depend on how many dimensions earlier paths used.
+#[derive(Debug, Clone)]
+pub struct MetropolisSampler {
+    rng: Pcg32,
+    /// Standard deviation of the small step mutations
+    sigma: f64,
+    large_step_probability: f64,
+    samples: Vec<PrimarySample>,
+    current_iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    /// Next dimension to be used
+    dimension: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PrimarySample {
+    value: f64,
+    /// Iteration in which the value was last mutated
+    last_modification_iteration: u64,
+    /// Value and iteration before the mutation of the current iteration, restored on rejection
+    backup: (f64, u64),
+}
+
+impl MetropolisSampler {
+    /// Creates a chain whose first state, before any call to `start_iteration`, has uniform
+    /// random dimensions. Chains with the same `seed` generate the same states.
+    pub fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        Self {
+            rng: Pcg32::new(seed, 0),
+            sigma,
+            large_step_probability,
+            samples: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Continues the chain with other random numbers, e.g. for several chains that start in the
+    /// same state.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Pcg32::new(seed, 0);
+    }
+
+    /// Starts proposing a mutation of the current state.
+    pub fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+    }
+
+    /// Makes the proposed state the current one.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    /// Discards the proposed state.
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.last_modification_iteration == self.current_iteration {
+                (sample.value, sample.last_modification_iteration) = sample.backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    /// Brings dimension `index` up to date with the current iteration.
+    fn ensure_ready(&mut self, index: usize) {
+        if index >= self.samples.len() {
+            self.samples.resize(index + 1, PrimarySample::default());
+        }
+        let sample = &mut self.samples[index];
+
+        // Dimensions that weren't used since the last large step missed its mutation
+        if sample.last_modification_iteration < self.last_large_step_iteration {
+            sample.value = self.rng.gen();
+            sample.last_modification_iteration = self.last_large_step_iteration;
+        }
+
+        sample.backup = (sample.value, sample.last_modification_iteration);
+        if self.large_step {
+            sample.value = self.rng.gen();
+        } else {
+            // Catch up on the small steps of the iterations in which the dimension was unused,
+            // whose sum is normally distributed as well
+            let steps = self.current_iteration - sample.last_modification_iteration;
+            let sigma = self.sigma * (steps as f64).sqrt();
+            let (u1, u2) = (self.rng.gen::<f64>(), self.rng.gen::<f64>());
+            let normal = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * PI * u2).cos();
+            sample.value += sigma * normal;
+            sample.value = (sample.value - sample.value.floor()).min(ONE_MINUS_EPSILON);
+        }
+        sample.last_modification_iteration = self.current_iteration;
+    }
+}
+
+impl GenerateSamples for MetropolisSampler {
+    /// Starts over at the first dimension. The pixel is part of the state, so it's ignored.
+    fn start_pixel_sample(&mut self, _pixel: (usize, usize), _sample_index: usize) {
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let index = self.dimension;
+        self.ensure_ready(index);
+        self.dimension += 1;
+        self.samples[index].value
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
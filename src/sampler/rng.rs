@@ -0,0 +1,71 @@
+use rand::{RngCore, SeedableRng};
+
+/// The PCG32 random number generator (XSH-RR variant).
+///
+/// Small and fast to seed, so that a fresh generator can be derived for every pixel sample.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 0x5851f42d4c957f2d;
+
+    pub fn new(seed: u64, sequence: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (sequence << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let (state, sequence) = seed.split_at(8);
+        Self::new(
+            u64::from_le_bytes(state.try_into().unwrap()),
+            u64::from_le_bytes(sequence.try_into().unwrap()),
+        )
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::new(seed, super::mix_bits(seed))
+    }
+}
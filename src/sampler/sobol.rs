@@ -0,0 +1,102 @@
+use super::{hash, GenerateSamples, ONE_MINUS_EPSILON};
+
+/// Owen scrambled Sobol samples, following Burley's "Practical Hash-based Owen Scrambling".
+///
+/// Rather than using a separate Sobol dimension for every dimension of the sample, every 1D or 2D
+/// request uses the first two Sobol dimensions, but with the sample index shuffled and the result
+/// scrambled by a hash of the pixel and dimension. This keeps the good 2D stratification of the
+/// first dimensions everywhere, while decorrelating the dimensions from each other.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_seed: seed,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_seed(&mut self) -> u32 {
+        let seed = hash(&[self.pixel_seed, self.dimension]) as u32;
+        self.dimension += 1;
+        seed
+    }
+}
+
+// Direction numbers of the first two Sobol dimensions. The first is the van der Corput sequence,
+// the second uses the primitive polynomial x + 1 with m_1 = 1.
+const DIRECTIONS: [[u32; 32]; 2] = {
+    let mut directions = [[0; 32]; 2];
+    let mut m = 1u32;
+    let mut bit = 0;
+    while bit < 32 {
+        directions[0][bit] = 1 << (31 - bit);
+        directions[1][bit] = m << (31 - bit);
+        m ^= m << 1;
+        bit += 1;
+    }
+    directions
+};
+
+fn sobol(index: u32, dimension: usize) -> u32 {
+    (0..32)
+        .filter(|bit| (index >> bit) & 1 == 1)
+        .fold(0, |acc, bit| acc ^ DIRECTIONS[dimension][bit])
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn to_unit_interval(x: u32) -> f64 {
+    (x as f64 / 2f64.powi(32)).min(ONE_MINUS_EPSILON)
+}
+
+impl GenerateSamples for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample_index, seed);
+        to_unit_interval(nested_uniform_scramble(
+            sobol(index, 0),
+            hash(&[seed as u64, 0]) as u32,
+        ))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample_index, seed);
+        (
+            to_unit_interval(nested_uniform_scramble(
+                sobol(index, 0),
+                hash(&[seed as u64, 0]) as u32,
+            )),
+            to_unit_interval(nested_uniform_scramble(
+                sobol(index, 1),
+                hash(&[seed as u64, 1]) as u32,
+            )),
+        )
+    }
+}
@@ -0,0 +1,63 @@
+use rand::Rng;
+
+use super::{hash, permutation_element, GenerateSamples, Pcg32};
+
+/// Jittered samples: every dimension is divided into as many strata as there are samples per
+/// pixel (for 2D samples, into a grid of sqrt(spp) x sqrt(spp) cells), and each sample of a pixel
+/// lands in a different stratum. The strata are visited in a different random order for every
+/// dimension, so that dimensions are not correlated with each other.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: usize,
+    strata_per_axis: usize,
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: usize,
+    dimension: u64,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        Self {
+            samples_per_pixel,
+            strata_per_axis: f64::sqrt(samples_per_pixel as f64) as usize,
+            seed,
+            pixel_seed: seed,
+            sample_index: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+
+    // Stratum of the current sample in a dimension with `count` strata
+    fn stratum(&mut self, count: usize) -> usize {
+        let seed = hash(&[self.pixel_seed, self.dimension]) as u32;
+        self.dimension += 1;
+        permutation_element((self.sample_index % count) as u32, count as u32, seed) as usize
+    }
+}
+
+impl GenerateSamples for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.rng = Pcg32::new(self.pixel_seed, sample_index as u64);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum(self.samples_per_pixel);
+        (stratum as f64 + self.rng.gen::<f64>()) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let n = self.strata_per_axis;
+        let stratum = self.stratum(n * n);
+        (
+            ((stratum % n) as f64 + self.rng.gen::<f64>()) / n as f64,
+            ((stratum / n) as f64 + self.rng.gen::<f64>()) / n as f64,
+        )
+    }
+}
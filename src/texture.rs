@@ -1,14 +1,24 @@
 use std::path::Path;
 
 use enum_dispatch::enum_dispatch;
-use image::{io::Reader as ImageReader, Rgb32FImage};
-use palette::Srgb;
+use image::io::Reader as ImageReader;
+use rand::Rng;
 
-use crate::{color::Color, math::Point3};
+use crate::{color::Color, hittables::HitRecord};
+
+mod mipmap;
+use mipmap::MipMap;
+pub use mipmap::{TextureFilter, WrapMode};
 
 mod perlin;
 use perlin::Perlin;
 
+mod worley;
+pub use worley::DistanceMetric;
+
+mod procedural;
+pub use procedural::*;
+
 #[derive(Debug, Clone)]
 #[enum_dispatch(TextureValue)]
 pub enum Texture {
@@ -16,6 +26,7 @@ pub enum Texture {
     CheckerTexture(CheckerTexture),
     Image(Image),
     Noise(Noise),
+    Procedural(Procedural),
 }
 
 impl From<Color> for Texture {
@@ -28,11 +39,34 @@ impl From<Color> for Texture {
 pub struct TextureCoords {
     pub u: f64,
     pub v: f64,
+    /// How much the coordinates change from one pixel to the next, if known, which determines
+    /// the area of a texture that a lookup has to average
+    pub derivatives: Option<TextureDerivatives>,
+}
+
+impl TextureCoords {
+    pub fn new(u: f64, v: f64) -> Self {
+        Self {
+            u,
+            v,
+            derivatives: None,
+        }
+    }
+}
+
+/// Partial derivatives of the texture coordinates with respect to the x and y of the image.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureDerivatives {
+    pub du_dx: f64,
+    pub dv_dx: f64,
+    pub du_dy: f64,
+    pub dv_dy: f64,
 }
 
 #[enum_dispatch]
 pub trait TextureValue {
-    fn value(&self, coords: &TextureCoords, p: Point3) -> Color;
+    /// Color at the hit, averaged over the footprint of the pixel if the hit has differentials.
+    fn value(&self, hit_record: &HitRecord) -> Color;
 }
 
 #[derive(Debug, Clone, derive_more::From)]
@@ -41,7 +75,7 @@ pub struct SolidColor {
 }
 
 impl TextureValue for SolidColor {
-    fn value(&self, _: &TextureCoords, _: Point3) -> Color {
+    fn value(&self, _: &HitRecord) -> Color {
         self.albedo
     }
 }
@@ -64,63 +98,133 @@ impl CheckerTexture {
 }
 
 impl TextureValue for CheckerTexture {
-    fn value(&self, coords: &TextureCoords, p: Point3) -> Color {
-        let x_integer = f64::floor(self.inv_scale * p.x()) as i32;
-        let y_integer = f64::floor(self.inv_scale * p.y()) as i32;
-        let z_integer = f64::floor(self.inv_scale * p.z()) as i32;
-
-        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
-        if is_even { &self.even } else { &self.odd }.value(coords, p)
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let p = hit_record.p;
+        let Some(d) = hit_record.differentials else {
+            let x_integer = f64::floor(self.inv_scale * p.x()) as i32;
+            let y_integer = f64::floor(self.inv_scale * p.y()) as i32;
+            let z_integer = f64::floor(self.inv_scale * p.z()) as i32;
+
+            let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+            return if is_even { &self.even } else { &self.odd }.value(hit_record);
+        };
+
+        // Box filter over the footprint, in closed form: the fraction of odd cells along every
+        // axis, combined into the probability that the sum of the cell indices is odd
+        let odd_fraction = |axis: usize| {
+            let center = self.inv_scale * p[axis];
+            let width = self.inv_scale * d.dpdx[axis].abs().max(d.dpdy[axis].abs());
+            if width < 1e-9 {
+                return center.floor().rem_euclid(2.0);
+            }
+            // Integral of the indicator of odd cells from 0 to x
+            let odd_integral = |x: f64| {
+                let half = x / 2.0;
+                half.floor() + 2.0 * (half - half.floor() - 0.5).max(0.0)
+            };
+            (odd_integral(center + width) - odd_integral(center - width)) / (2.0 * width)
+        };
+        let odd = 0.5
+            * (1.0
+                - (0..3)
+                    .map(|axis| 1.0 - 2.0 * odd_fraction(axis))
+                    .product::<f64>());
+        if odd <= 0.0 {
+            self.even.value(hit_record)
+        } else if odd >= 1.0 {
+            self.odd.value(hit_record)
+        } else {
+            (1.0 - odd) * self.even.value(hit_record) + odd * self.odd.value(hit_record)
+        }
     }
 }
 
+/// Texture from an image file in sRGB, which covers the unit square of texture coordinates,
+/// with `v` pointing up. By default, it's filtered trilinearly and clamped at its edges.
 #[derive(Debug, Clone)]
 pub struct Image {
-    img: Rgb32FImage,
+    mipmap: MipMap,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    tiling: (f64, f64),
 }
 
 impl Image {
     pub fn new(path: &Path) -> anyhow::Result<Self> {
         Ok(Self {
-            img: ImageReader::open(path)?.decode()?.to_rgb32f(),
+            mipmap: MipMap::new(&ImageReader::open(path)?.decode()?.to_rgb32f()),
+            filter: TextureFilter::default(),
+            wrap: WrapMode::default(),
+            tiling: (1.0, 1.0),
         })
     }
-}
 
-impl TextureValue for Image {
-    fn value(&self, coords: &TextureCoords, _: Point3) -> Color {
-        // Clamp input texture coordinates to [0,1] x [1,0]
-        let u = coords.u.clamp(0.0, 1.0);
-        let v = 1.0 - coords.v.clamp(0.0, 1.0); // Flip V to image coordinates
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
 
-        let i = (u * self.img.width() as f64) as u32;
-        let j = (v * self.img.height() as f64) as u32;
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
 
-        let pixel = self.img[(i, j)];
+    /// Repeats the image the given number of times along `u` and `v` of the texture coordinates,
+    /// in the way given by the wrap mode.
+    pub fn with_tiling(mut self, u_repeats: f64, v_repeats: f64) -> Self {
+        self.tiling = (u_repeats, v_repeats);
+        self
+    }
+}
 
-        // The image crate loads images in sRGB color space, but our Color class expects linear.
-        Color::from(Srgb::new(pixel[0], pixel[1], pixel[2]).into_linear())
+impl TextureValue for Image {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let coords = &hit_record.texture_coords;
+        let (su, sv) = self.tiling;
+        let derivatives = coords.derivatives.map(|d| TextureDerivatives {
+            du_dx: su * d.du_dx,
+            dv_dx: sv * d.dv_dx,
+            du_dy: su * d.du_dy,
+            dv_dy: sv * d.dv_dy,
+        });
+        self.mipmap.lookup(
+            (su * coords.u, sv * coords.v),
+            derivatives.as_ref(),
+            self.filter,
+            self.wrap,
+        )
     }
 }
 
+/// Marble-like Perlin turbulence in gray, like [`Marble`] with its defaults and a grayscale
+/// [`ColorRamp`].
 #[derive(Debug, Clone)]
 pub struct Noise {
-    noise: Perlin,
+    noise: Box<Perlin>,
     scale: f64,
 }
 
 impl Noise {
-    pub fn new(scale: f64) -> Self {
+    pub fn new(scale: f64, rng: &mut impl Rng) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Box::new(Perlin::new(rng)),
             scale,
         }
     }
 }
 
 impl TextureValue for Noise {
-    fn value(&self, _: &TextureCoords, p: Point3) -> Color {
-        (1.0 + f64::sin(self.scale * p.z() + 10.0 * self.noise.turb(&p, 7)))
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let p = hit_record.p;
+        let footprint = footprint(hit_record);
+        (1.0 + f64::sin(self.scale * p.z() + 10.0 * self.noise.turb(&p, 7, footprint)))
             * Color::new(0.5, 0.5, 0.5)
     }
 }
+
+/// Width of the pixel's footprint around the hit, or zero if the hit has no differentials.
+fn footprint(hit_record: &HitRecord) -> f64 {
+    hit_record
+        .differentials
+        .map_or(0.0, |d| d.dpdx.length().max(d.dpdy.length()))
+}
@@ -0,0 +1,281 @@
+use image::Rgb32FImage;
+use itertools::iproduct;
+use palette::Srgb;
+
+use crate::color::Color;
+
+use super::TextureDerivatives;
+
+/// How an image texture reconstructs its color between texels, and how it averages the texels
+/// within the footprint of a pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TextureFilter {
+    /// The closest texel, which is blocky under magnification and aliases under minification
+    Nearest,
+    /// Linear interpolation between the four closest texels
+    Bilinear,
+    /// Bilinear lookups in the two mipmap levels whose texel size is closest to the footprint,
+    /// linearly interpolated. The footprint is approximated by a square, which blurs textures
+    /// seen at grazing angles.
+    #[default]
+    Trilinear,
+    /// Elliptically weighted average over the footprint (Heckbert, 1989), in the mipmap level
+    /// that resolves its minor axis. Sharper than trilinear filtering at grazing angles.
+    Ewa,
+}
+
+/// How texture coordinates outside of [0,1] are mapped into the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WrapMode {
+    /// Tiles the image
+    Repeat,
+    /// Tiles the image, flipping every other tile so that the edges meet seamlessly
+    Mirror,
+    /// Repeats the texels at the edges
+    #[default]
+    Clamp,
+}
+
+impl WrapMode {
+    /// Maps a texel index along an axis of `size` texels into the image.
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            Self::Repeat => i.rem_euclid(size),
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            Self::Clamp => i.clamp(0, size - 1),
+        };
+        i as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    width: usize,
+    height: usize,
+    /// Linear RGB, row by row from the top
+    texels: Vec<[f32; 3]>,
+}
+
+impl Level {
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> Color {
+        let (x, y) = (wrap.apply(x, self.width), wrap.apply(y, self.height));
+        let [r, g, b] = self.texels[y * self.width + x];
+        Color::new(r as f64, g as f64, b as f64)
+    }
+
+    /// Coordinates in texels of the point `(s, t)` of the unit square, where `t` points down.
+    fn scale(&self, s: f64, t: f64) -> (f64, f64) {
+        (s * self.width as f64, t * self.height as f64)
+    }
+
+    fn nearest(&self, s: f64, t: f64, wrap: WrapMode) -> Color {
+        let (x, y) = self.scale(s, t);
+        self.texel(x.floor() as i64, y.floor() as i64, wrap)
+    }
+
+    fn bilinear(&self, s: f64, t: f64, wrap: WrapMode) -> Color {
+        // Relative to the centers of the texels
+        let (x, y) = self.scale(s, t);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        (1.0 - dx) * (1.0 - dy) * self.texel(x0, y0, wrap)
+            + dx * (1.0 - dy) * self.texel(x0 + 1, y0, wrap)
+            + (1.0 - dx) * dy * self.texel(x0, y0 + 1, wrap)
+            + dx * dy * self.texel(x0 + 1, y0 + 1, wrap)
+    }
+
+    /// Gaussian weighted average of the texels within the ellipse around `(s, t)` with the given
+    /// axes, in coordinates of the unit square.
+    fn ewa(&self, s: f64, t: f64, axes: [(f64, f64); 2], wrap: WrapMode) -> Color {
+        const ALPHA: f64 = 2.0;
+
+        let (x, y) = self.scale(s, t);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let [(ax, ay), (bx, by)] = axes.map(|(ds, dt)| self.scale(ds, dt));
+
+        // Implicit equation A x² + B x y + C y² < F of the ellipse, scaled so that F = 1
+        let a = ay * ay + by * by + 1.0;
+        let b = -2.0 * (ax * ay + bx * by);
+        let c = ax * ax + bx * bx + 1.0;
+        let f = a * c - b * b / 4.0;
+        let (a, b, c) = (a / f, b / f, c / f);
+
+        // Bounding box of the ellipse
+        let determinant = -b * b + 4.0 * a * c;
+        let inv_determinant = 1.0 / determinant;
+        let u_extent = (c * inv_determinant * 4.0).sqrt();
+        let v_extent = (a * inv_determinant * 4.0).sqrt();
+        let (x0, x1) = ((x - u_extent).ceil() as i64, (x + u_extent).floor() as i64);
+        let (y0, y1) = ((y - v_extent).ceil() as i64, (y + v_extent).floor() as i64);
+
+        let mut sum = Color::black();
+        let mut weights = 0.0;
+        for yi in y0..=y1 {
+            let dy = yi as f64 - y;
+            for xi in x0..=x1 {
+                let dx = xi as f64 - x;
+                let r2 = a * dx * dx + b * dx * dy + c * dy * dy;
+                if r2 < 1.0 {
+                    let weight = (-ALPHA * r2).exp() - (-ALPHA).exp();
+                    sum = sum + weight * self.texel(xi, yi, wrap);
+                    weights += weight;
+                }
+            }
+        }
+        if weights <= 0.0 {
+            return self.bilinear(s, t, wrap);
+        }
+        (1.0 / weights) * sum
+    }
+
+    /// Next coarser level, which averages 2x2 texels of this one.
+    fn downsampled(&self) -> Self {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let texels = iproduct!(0..height, 0..width)
+            .map(|(y, x)| {
+                let mut sum = [0.0; 3];
+                for (dx, dy) in iproduct!(0..2, 0..2) {
+                    let xi = (2 * x + dx).min(self.width - 1);
+                    let yi = (2 * y + dy).min(self.height - 1);
+                    let texel = self.texels[yi * self.width + xi];
+                    for (sum, value) in sum.iter_mut().zip(texel) {
+                        *sum += value / 4.0;
+                    }
+                }
+                sum
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/// Image with successively halved copies of itself (Williams, 1983), down to a single texel, so
+/// that the texels within a pixel's footprint can be averaged quickly at any distance.
+#[derive(Debug, Clone)]
+pub(crate) struct MipMap {
+    levels: Vec<Level>,
+}
+
+impl MipMap {
+    /// Largest ratio of the major and minor axes of the footprint of EWA filtering. Longer
+    /// ellipses are made wider, so that lookups don't cover arbitrarily many texels.
+    const MAX_ANISOTROPY: f64 = 8.0;
+
+    /// Pyramid of an image in sRGB.
+    pub(crate) fn new(image: &Rgb32FImage) -> Self {
+        let texels = image
+            .pixels()
+            .map(|pixel| {
+                let linear = Srgb::new(pixel[0], pixel[1], pixel[2]).into_linear();
+                [linear.red, linear.green, linear.blue]
+            })
+            .collect();
+        let mut levels = vec![Level {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            texels,
+        }];
+        while let Some(level) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(level.downsampled());
+        }
+        Self { levels }
+    }
+
+    /// Filtered color at texture coordinates `(u, v)`, with `v` pointing up. Without derivatives,
+    /// the footprint is taken to be smaller than a texel.
+    pub(crate) fn lookup(
+        &self,
+        (u, v): (f64, f64),
+        derivatives: Option<&TextureDerivatives>,
+        filter: TextureFilter,
+        wrap: WrapMode,
+    ) -> Color {
+        let (s, t) = (u, 1.0 - v);
+        let finest = &self.levels[0];
+        let Some(d) = derivatives else {
+            return match filter {
+                TextureFilter::Nearest => finest.nearest(s, t, wrap),
+                _ => finest.bilinear(s, t, wrap),
+            };
+        };
+        // Axes of the footprint, with t pointing down like v doesn't
+        let axes = [(d.du_dx, -d.dv_dx), (d.du_dy, -d.dv_dy)];
+
+        match filter {
+            TextureFilter::Nearest => finest.nearest(s, t, wrap),
+            TextureFilter::Bilinear => finest.bilinear(s, t, wrap),
+            TextureFilter::Trilinear => {
+                let width = axes
+                    .iter()
+                    .flat_map(|(ds, dt)| [ds.abs(), dt.abs()])
+                    .fold(0.0, f64::max);
+                self.between_levels(self.level_of(width), |level| level.bilinear(s, t, wrap))
+            }
+            TextureFilter::Ewa => {
+                let length = |(ds, dt): (f64, f64)| ds.hypot(dt);
+                let [mut major, mut minor] = axes;
+                if length(major) < length(minor) {
+                    std::mem::swap(&mut major, &mut minor);
+                }
+                let (major_length, mut minor_length) = (length(major), length(minor));
+                if major_length == 0.0 {
+                    return finest.bilinear(s, t, wrap);
+                }
+
+                if minor_length * Self::MAX_ANISOTROPY < major_length {
+                    // Scale the minor axis up, keeping it perpendicular to the major one if it
+                    // vanished
+                    let scale = major_length / (minor_length * Self::MAX_ANISOTROPY);
+                    minor = if minor_length > 0.0 {
+                        (minor.0 * scale, minor.1 * scale)
+                    } else {
+                        let length = major_length / Self::MAX_ANISOTROPY;
+                        (
+                            -major.1 / major_length * length,
+                            major.0 / major_length * length,
+                        )
+                    };
+                    minor_length = length(minor);
+                }
+                self.between_levels(self.level_of(minor_length), |level| {
+                    level.ewa(s, t, [major, minor], wrap)
+                })
+            }
+        }
+    }
+
+    /// Continuous level at which texels are `width` wide in coordinates of the unit square.
+    fn level_of(&self, width: f64) -> f64 {
+        let finest = &self.levels[0];
+        let texels = width * finest.width.max(finest.height) as f64;
+        texels.max(f64::MIN_POSITIVE).log2().max(0.0)
+    }
+
+    /// Linear interpolation of the lookups in the two levels around the continuous `level`.
+    fn between_levels(&self, level: f64, lookup: impl Fn(&Level) -> Color) -> Color {
+        let coarsest = self.levels.len() - 1;
+        if level >= coarsest as f64 {
+            return lookup(&self.levels[coarsest]);
+        }
+        let i = level.floor() as usize;
+        let t = level - i as f64;
+        if t == 0.0 {
+            return lookup(&self.levels[i]);
+        }
+        (1.0 - t) * lookup(&self.levels[i]) + t * lookup(&self.levels[i + 1])
+    }
+}
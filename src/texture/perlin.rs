@@ -1,8 +1,10 @@
 use itertools::iproduct;
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{seq::SliceRandom, Rng};
 
 use crate::math::{dot, Point3, Vec3};
 
+use super::Octaves;
+
 const NUM_POINTS: usize = 256;
 
 #[derive(Debug, Clone)]
@@ -13,9 +15,9 @@ pub struct Perlin {
     perm_z: [usize; NUM_POINTS],
 }
 
-fn perlin_generate_perm() -> [usize; NUM_POINTS] {
+fn perlin_generate_perm(rng: &mut impl Rng) -> [usize; NUM_POINTS] {
     let mut p = std::array::from_fn(|i| i);
-    p.shuffle(&mut thread_rng());
+    p.shuffle(rng);
     p
 }
 
@@ -42,12 +44,12 @@ fn trilinear_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
 }
 
 impl Perlin {
-    pub fn new() -> Self {
+    pub fn new(rng: &mut impl Rng) -> Self {
         Self {
-            randvec: std::array::from_fn(|_| Vec3::random(-1.0..1.0).normalized()),
-            perm_x: perlin_generate_perm(),
-            perm_y: perlin_generate_perm(),
-            perm_z: perlin_generate_perm(),
+            randvec: std::array::from_fn(|_| Vec3::random(rng, -1.0..1.0).normalized()),
+            perm_x: perlin_generate_perm(rng),
+            perm_y: perlin_generate_perm(rng),
+            perm_z: perlin_generate_perm(rng),
         }
     }
 
@@ -74,12 +76,51 @@ impl Perlin {
         trilinear_interp(&c, u, v, w)
     }
 
-    pub fn turb(&self, p: &Point3, depth: usize) -> f64 {
-        (0..depth)
-            .fold((0.0, *p, 1.0), |(acc, p, weight), _| {
-                (acc + weight * self.noise(p), 2.0 * p, weight * 0.5)
+    /// Absolute value of [`Perlin::fbm`] with `depth` octaves, each with twice the frequency and
+    /// half the amplitude of the previous one.
+    pub fn turb(&self, p: &Point3, depth: usize, footprint: f64) -> f64 {
+        let octaves = Octaves {
+            count: depth,
+            ..Octaves::default()
+        };
+        self.fbm(p, &octaves, footprint).abs()
+    }
+
+    /// Fractional Brownian motion: the sum of the octaves of noise, each with `lacunarity` times
+    /// the frequency and `gain` times the amplitude of the previous one. Octaves that are too
+    /// fine for a pixel `footprint` wide are faded out, see [`Octaves::fades`].
+    pub fn fbm(&self, p: &Point3, octaves: &Octaves, footprint: f64) -> f64 {
+        octaves
+            .fades(footprint)
+            .fold((0.0, *p, 1.0), |(acc, p, weight), fade| {
+                (
+                    acc + fade * weight * self.noise(p),
+                    octaves.lacunarity * p,
+                    weight * octaves.gain,
+                )
             })
             .0
-            .abs()
+    }
+
+    /// Ridged multifractal (Musgrave, 1994): octaves of `offset - |noise|`, squared into sharp
+    /// ridges, with each octave weighted by the previous one, so that detail accumulates on the
+    /// ridges while the valleys stay smooth. Roughly in [0, 1] for an offset of one. Octaves are
+    /// faded out like in [`Perlin::fbm`].
+    pub fn ridged(&self, p: &Point3, octaves: &Octaves, offset: f64, footprint: f64) -> f64 {
+        let (mut sum, mut amplitudes) = (0.0, 0.0);
+        let (mut p, mut amplitude, mut weight) = (*p, 1.0, 1.0);
+        for fade in octaves.fades(footprint) {
+            let signal = (offset - self.noise(p).abs()).powi(2) * weight;
+            sum += fade * amplitude * signal;
+            amplitudes += fade * amplitude;
+            weight = signal.clamp(0.0, 1.0);
+            p = octaves.lacunarity * p;
+            amplitude *= octaves.gain;
+        }
+        if amplitudes > 0.0 {
+            sum / amplitudes
+        } else {
+            0.0
+        }
     }
 }
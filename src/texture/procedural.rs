@@ -0,0 +1,372 @@
+use enum_dispatch::enum_dispatch;
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    hittables::HitRecord,
+    math::{dot, Interpolation, Keyframes, Point3, Vec3},
+};
+
+use super::{
+    footprint,
+    perlin::Perlin,
+    worley::{DistanceMetric, Worley},
+    TextureValue,
+};
+
+/// How octaves of noise are summed up by fractal patterns: `count` octaves, each with
+/// `lacunarity` times the frequency and `gain` times the amplitude of the previous one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Octaves {
+    pub count: usize,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl Octaves {
+    /// Weights of the octaves for a pixel `footprint` wide, or zero if unknown: one for the
+    /// octaves whose frequency is below the Nyquist limit of the footprint, fading to zero for
+    /// the one that crosses it, and none for the ones above, which would only alias.
+    pub fn fades(&self, footprint: f64) -> impl Iterator<Item = f64> {
+        let limit = if footprint > 0.0 && self.lacunarity > 1.0 {
+            (-(2.0 * footprint).ln() / self.lacunarity.ln()).clamp(0.0, self.count as f64)
+        } else {
+            self.count as f64
+        };
+        (0..self.count)
+            .map(move |i| (limit - i as f64).clamp(0.0, 1.0))
+            .take_while(|fade| *fade > 0.0)
+    }
+}
+
+impl Default for Octaves {
+    fn default() -> Self {
+        Self {
+            count: 7,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+/// Scalar field in space, roughly within [0, 1], that a [`Procedural`] texture maps onto colors.
+#[derive(Debug, Clone)]
+#[enum_dispatch(PatternValue)]
+pub enum Pattern {
+    Fbm(Fbm),
+    RidgedMultifractal(RidgedMultifractal),
+    Cellular(Cellular),
+    Wood(Wood),
+    Marble(Marble),
+}
+
+#[enum_dispatch]
+pub trait PatternValue {
+    /// Value at `p`, for a pixel `footprint` wide around it, or zero if unknown.
+    fn value(&self, p: Point3, footprint: f64) -> f64;
+}
+
+/// Fractional Brownian motion of Perlin noise, which looks like clouds or rough terrain.
+#[derive(Debug, Clone)]
+pub struct Fbm {
+    noise: Box<Perlin>,
+    octaves: Octaves,
+}
+
+impl Fbm {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            noise: Box::new(Perlin::new(rng)),
+            octaves: Octaves::default(),
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl PatternValue for Fbm {
+    fn value(&self, p: Point3, footprint: f64) -> f64 {
+        // Normalized by the sum of the amplitudes, so that the contrast doesn't depend on the
+        // octaves. Most values fall within [0.3, 0.7], which the ramp can stretch.
+        let amplitudes: f64 = (0..self.octaves.count)
+            .map(|i| self.octaves.gain.powi(i as i32))
+            .sum();
+        if amplitudes <= 0.0 {
+            return 0.5;
+        }
+        (0.5 + self.noise.fbm(&p, &self.octaves, footprint) / amplitudes).clamp(0.0, 1.0)
+    }
+}
+
+/// Ridged multifractal of Perlin noise, see [`Perlin::ridged`], which looks like mountain ranges
+/// or veins.
+#[derive(Debug, Clone)]
+pub struct RidgedMultifractal {
+    noise: Box<Perlin>,
+    octaves: Octaves,
+    offset: f64,
+}
+
+impl RidgedMultifractal {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            noise: Box::new(Perlin::new(rng)),
+            octaves: Octaves::default(),
+            offset: 1.0,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// Height of the ridges, which become thinner for smaller offsets.
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl PatternValue for RidgedMultifractal {
+    fn value(&self, p: Point3, footprint: f64) -> f64 {
+        self.noise
+            .ridged(&p, &self.octaves, self.offset, footprint)
+            .clamp(0.0, 1.0)
+    }
+}
+
+/// Which distances to the feature points make up cellular noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CellularFeature {
+    /// Distance to the closest feature point, which is zero at the points and grows towards the
+    /// borders of their cells
+    #[default]
+    F1,
+    /// Distance to the second closest feature point
+    F2,
+    /// Difference of the two, which is zero on the borders of the cells, like cracks or scales
+    F2MinusF1,
+}
+
+/// Worley cellular noise (Worley, 1996): distances to feature points scattered through space,
+/// one per unit cube, which partition it into cells like stones, cracks or skin.
+#[derive(Debug, Clone)]
+pub struct Cellular {
+    points: Box<Worley>,
+    metric: DistanceMetric,
+    feature: CellularFeature,
+    jitter: f64,
+}
+
+impl Cellular {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            points: Box::new(Worley::new(rng)),
+            metric: DistanceMetric::default(),
+            feature: CellularFeature::default(),
+            jitter: 1.0,
+        }
+    }
+
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn with_feature(mut self, feature: CellularFeature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    /// How far the feature points are scattered from the centers of the unit cubes, from zero
+    /// for a regular grid of cells to one for fully random ones.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl PatternValue for Cellular {
+    fn value(&self, p: Point3, _: f64) -> f64 {
+        let (f1, f2) = self.points.distances(p, self.metric, self.jitter);
+        let value = match self.feature {
+            CellularFeature::F1 => f1,
+            CellularFeature::F2 => f2,
+            CellularFeature::F2MinusF1 => f2 - f1,
+        };
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// Growth rings around an axis through the origin, distorted by noise. The value rises from zero
+/// to one across every ring, from the early to the late wood of a year.
+#[derive(Debug, Clone)]
+pub struct Wood {
+    noise: Box<Perlin>,
+    rings_per_unit: f64,
+    axis: Vec3,
+    distortion: f64,
+    octaves: Octaves,
+}
+
+impl Wood {
+    /// Wood along the y axis, with the given number of rings per unit of distance from the axis.
+    pub fn new(rings_per_unit: f64, rng: &mut impl Rng) -> Self {
+        Self {
+            noise: Box::new(Perlin::new(rng)),
+            rings_per_unit,
+            axis: Vec3::new(0, 1, 0),
+            distortion: 0.2,
+            octaves: Octaves {
+                count: 3,
+                ..Octaves::default()
+            },
+        }
+    }
+
+    /// Direction of the trunk that the rings grow around.
+    pub fn with_axis(mut self, axis: Vec3) -> Self {
+        self.axis = axis.normalized();
+        self
+    }
+
+    /// How far the rings are displaced by noise, in rings.
+    pub fn with_distortion(mut self, distortion: f64) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl PatternValue for Wood {
+    fn value(&self, p: Point3, footprint: f64) -> f64 {
+        let radial = *p.as_vec3() - dot(p.as_vec3(), &self.axis) * self.axis;
+        let rings = self.rings_per_unit * radial.length()
+            + self.distortion * self.noise.fbm(&p, &self.octaves, footprint);
+        rings - rings.floor()
+    }
+}
+
+/// Stripes across an axis, whose phase is perturbed by turbulence into veins. With the default
+/// parameters, it is the pattern of [`super::Noise`].
+#[derive(Debug, Clone)]
+pub struct Marble {
+    noise: Box<Perlin>,
+    frequency: f64,
+    axis: Vec3,
+    turbulence: f64,
+    octaves: Octaves,
+}
+
+impl Marble {
+    /// Marble with stripes across the z axis, whose phase advances by `frequency` radians per
+    /// unit along it.
+    pub fn new(frequency: f64, rng: &mut impl Rng) -> Self {
+        Self {
+            noise: Box::new(Perlin::new(rng)),
+            frequency,
+            axis: Vec3::new(0, 0, 1),
+            turbulence: 10.0,
+            octaves: Octaves::default(),
+        }
+    }
+
+    /// Direction across which the stripes alternate.
+    pub fn with_axis(mut self, axis: Vec3) -> Self {
+        self.axis = axis.normalized();
+        self
+    }
+
+    /// How far the phase of the stripes is perturbed by turbulence, in radians.
+    pub fn with_turbulence(mut self, turbulence: f64) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl PatternValue for Marble {
+    fn value(&self, p: Point3, footprint: f64) -> f64 {
+        let turbulence = self.noise.fbm(&p, &self.octaves, footprint).abs();
+        let phase = self.frequency * dot(p.as_vec3(), &self.axis) + self.turbulence * turbulence;
+        0.5 * (1.0 + phase.sin())
+    }
+}
+
+/// Gradient that maps the values of a pattern onto colors, given at a set of positions. Colors
+/// between two positions are interpolated, and held constant before the first or after the last
+/// one.
+#[derive(Debug, Clone)]
+pub struct ColorRamp(Keyframes<Color>);
+
+impl ColorRamp {
+    pub fn new(stops: impl IntoIterator<Item = (f64, Color)>) -> Self {
+        Self(Keyframes::new(stops))
+    }
+
+    /// From black at zero to white at one.
+    pub fn grayscale() -> Self {
+        Self::new([(0.0, Color::black()), (1.0, Color::white())])
+    }
+
+    /// Linear by default. Catmull-Rom splines give smoother gradients, but may overshoot the
+    /// colors of the stops.
+    pub fn with_interpolation(self, interpolation: Interpolation) -> Self {
+        Self(self.0.with_interpolation(interpolation))
+    }
+
+    pub fn color_at(&self, t: f64) -> Color {
+        let [r, g, b] = self.0.at(t).components();
+        Color::new(r.max(0.0), g.max(0.0), b.max(0.0))
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self::grayscale()
+    }
+}
+
+/// Texture given by a pattern in space, mapped onto colors by a ramp.
+#[derive(Debug, Clone)]
+pub struct Procedural {
+    pattern: Pattern,
+    ramp: ColorRamp,
+    scale: f64,
+}
+
+impl Procedural {
+    pub fn new(pattern: impl Into<Pattern>, ramp: ColorRamp) -> Self {
+        Self {
+            pattern: pattern.into(),
+            ramp,
+            scale: 1.0,
+        }
+    }
+
+    /// Scales up the pattern's frequency in space, i.e. shrinks its features.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl TextureValue for Procedural {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let p = self.scale * hit_record.p;
+        let footprint = self.scale * footprint(hit_record);
+        self.ramp.color_at(self.pattern.value(p, footprint))
+    }
+}
@@ -0,0 +1,82 @@
+use itertools::iproduct;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::math::{Point3, Vec3};
+
+const NUM_POINTS: usize = 256;
+
+/// How the distance between a point and the feature points of cellular noise is measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DistanceMetric {
+    /// Straight-line distance, which gives round cells
+    #[default]
+    Euclidean,
+    /// Sum of the distances along the axes, which gives diamond-shaped cells
+    Manhattan,
+    /// Largest distance along an axis, which gives square cells
+    Chebyshev,
+}
+
+impl DistanceMetric {
+    fn distance(self, d: &Vec3) -> f64 {
+        match self {
+            Self::Euclidean => d.length(),
+            Self::Manhattan => d.x.abs() + d.y.abs() + d.z.abs(),
+            Self::Chebyshev => d.x.abs().max(d.y.abs()).max(d.z.abs()),
+        }
+    }
+}
+
+/// Feature points for cellular noise (Worley, 1996): one at a random position in every cell of
+/// the integer lattice, looked up through hashed cell coordinates like [`super::perlin::Perlin`].
+#[derive(Debug, Clone)]
+pub struct Worley {
+    offsets: [Vec3; NUM_POINTS],
+    perm_x: [usize; NUM_POINTS],
+    perm_y: [usize; NUM_POINTS],
+    perm_z: [usize; NUM_POINTS],
+}
+
+fn worley_generate_perm(rng: &mut impl Rng) -> [usize; NUM_POINTS] {
+    let mut p = std::array::from_fn(|i| i);
+    p.shuffle(rng);
+    p
+}
+
+impl Worley {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            offsets: std::array::from_fn(|_| Vec3::random(rng, 0.0..1.0)),
+            perm_x: worley_generate_perm(rng),
+            perm_y: worley_generate_perm(rng),
+            perm_z: worley_generate_perm(rng),
+        }
+    }
+
+    /// Distances from `p` to the closest and the second closest feature point. The feature
+    /// points are moved from the cell centers towards their random positions by `jitter`.
+    pub fn distances(&self, p: Point3, metric: DistanceMetric, jitter: f64) -> (f64, f64) {
+        let (i, j, k) = (
+            p.x().floor() as i32,
+            p.y().floor() as i32,
+            p.z().floor() as i32,
+        );
+        let center = Vec3::new(0.5, 0.5, 0.5);
+        iproduct!(-1..=1, -1..=1, -1..=1).fold(
+            (f64::INFINITY, f64::INFINITY),
+            |(f1, f2), (di, dj, dk)| {
+                let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                let offset = self.offsets[self.perm_x[(ci & 255) as usize]
+                    ^ self.perm_y[(cj & 255) as usize]
+                    ^ self.perm_z[(ck & 255) as usize]];
+                let feature = Point3::new(ci, cj, ck) + center + jitter * (offset - center);
+                let distance = metric.distance(&(feature - p));
+                if distance < f1 {
+                    (distance, f1)
+                } else {
+                    (f1, distance.min(f2))
+                }
+            },
+        )
+    }
+}